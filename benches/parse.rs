@@ -0,0 +1,52 @@
+//! Parsing throughput benchmark.
+//!
+//! Run with `cargo bench`. The input is a generated config with 100k
+//! entries spread over 1000 sections, and the fastest of the runs is
+//! reported.
+
+use std::time::Instant;
+
+const SECTIONS: usize = 1000;
+const ENTRIES_PER_SECTION: usize = 100;
+const ITERATIONS: u32 = 20;
+
+fn generate_input() -> String {
+    let mut input = String::new();
+    for section in 0..SECTIONS {
+        input += &format!("[section {}]\n", section);
+        for entry in 0..ENTRIES_PER_SECTION {
+            input += &format!("key{} = value number {}\n", entry, entry);
+        }
+        input += "\n";
+    }
+    input
+}
+
+fn main() {
+    let input = generate_input();
+
+    // Warm up.
+    nbconf::Conf::parse_str(&input).expect("failed to parse config");
+
+    // Report the fastest run, which is the least affected by other load
+    // on the machine.
+    let elapsed = (0..ITERATIONS)
+        .map(|_| {
+            let start = Instant::now();
+            let conf = nbconf::Conf::parse_str(&input).expect("failed to parse config");
+            let elapsed = start.elapsed();
+            assert_eq!(conf.sections.len(), SECTIONS);
+            elapsed
+        })
+        .min()
+        .unwrap();
+
+    let megabytes = input.len() as f64 / (1024.0 * 1024.0);
+    println!(
+        "parse_str: {} entries, {:.2} MiB in {:?} ({:.1} MiB/s)",
+        SECTIONS * ENTRIES_PER_SECTION,
+        megabytes,
+        elapsed,
+        megabytes / elapsed.as_secs_f64()
+    );
+}
@@ -0,0 +1,50 @@
+/* C bindings for nbconf. Build the library with:
+ *
+ *     cargo rustc --release --features ffi --crate-type cdylib
+ *
+ * Strings passed in must be NUL-terminated UTF-8. Strings returned are
+ * owned by the caller and must be freed with nbconf_string_free. */
+
+#ifndef NBCONF_H
+#define NBCONF_H
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Opaque handle to a config. */
+typedef struct nbconf_conf_t nbconf_conf_t;
+
+/* Create an empty config. Free it with nbconf_conf_free. */
+nbconf_conf_t *nbconf_conf_new(void);
+
+/* Parse text into a config. Free it with nbconf_conf_free. Returns NULL
+ * on failure, and if error is not NULL, sets it to a message that must be
+ * freed with nbconf_string_free. */
+nbconf_conf_t *nbconf_conf_parse(const char *text, char **error);
+
+/* Get the value of key in the first section named section, or NULL if
+ * there is none. The value must be freed with nbconf_string_free. */
+char *nbconf_conf_get(const nbconf_conf_t *conf, const char *section, const char *key);
+
+/* Set key to value in the first section named section, adding the section
+ * if there is none. Returns 0 on success, or -1, leaving the config
+ * unchanged, if an argument is NULL or not UTF-8, or if the section name,
+ * key or value would not read back as given after being serialized. */
+int nbconf_conf_set(nbconf_conf_t *conf, const char *section, const char *key, const char *value);
+
+/* Serialize the config as a string, which must be freed with
+ * nbconf_string_free. Returns NULL if conf is NULL. */
+char *nbconf_conf_serialize(const nbconf_conf_t *conf);
+
+/* Free a config. Does nothing if conf is NULL. */
+void nbconf_conf_free(nbconf_conf_t *conf);
+
+/* Free a string returned by this library. Does nothing if s is NULL. */
+void nbconf_string_free(char *s);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,119 @@
+//! Finding entries that an application never reads.
+//!
+//! Example:
+//!
+//! ```
+//! use nbconf::Conf;
+//!
+//! let conf = Conf::parse_str("[server]\nport = 80\nprot = tcp\n").unwrap();
+//! let tracker = conf.track_access();
+//! assert_eq!(tracker.get_path("server.port"), Some("80"));
+//! for (section, key) in tracker.unreached_keys() {
+//!     eprintln!("warning: {}.{} has no effect", section, key);
+//! }
+//! ```
+
+use crate::Conf;
+use std::collections::HashSet;
+use std::sync::Mutex;
+
+/// A view of a config that records which entries are read through it.
+/// Returned by [`Conf::track_access`].
+///
+/// [`Conf::track_access`]: ../struct.Conf.html#method.track_access
+#[derive(Debug)]
+pub struct AccessTracker<'a> {
+    conf: &'a Conf,
+    /// Section and key of each entry read.
+    read: Mutex<HashSet<(String, String)>>,
+}
+
+impl<'a> AccessTracker<'a> {
+    fn mark(&self, section: &str, key: &str) {
+        self.read
+            .lock()
+            .unwrap()
+            .insert((section.to_string(), key.to_string()));
+    }
+
+    /// The config being tracked.
+    pub fn conf(&self) -> &'a Conf {
+        self.conf
+    }
+
+    /// Get the value of `key` in the first section named `section` that
+    /// has it, recording the access.
+    pub fn get(&self, section: &str, key: &str) -> Option<&'a str> {
+        self.mark(section, key);
+        self.conf
+            .iter()
+            .filter(|s| s.name == section)
+            .find_map(|s| s.get(key))
+    }
+
+    /// Get a value by its dotted path, as [`Conf::get_path`] does,
+    /// recording the access.
+    ///
+    /// [`Conf::get_path`]: ../struct.Conf.html#method.get_path
+    pub fn get_path(&self, path: &str) -> Option<&'a str> {
+        if let Some((section, key)) = self.conf.resolve_path(path) {
+            self.mark(section, key);
+        }
+        self.conf.get_path(path)
+    }
+
+    /// Record that `key` in `section` was read some other way, such as
+    /// through a typed getter on the section.
+    pub fn mark_read(&self, section: &str, key: &str) {
+        self.mark(section, key);
+    }
+
+    /// The section and key of each entry that has not been read, in
+    /// order, without repeats.
+    pub fn unreached_keys(&self) -> Vec<(&'a str, &'a str)> {
+        let read = self.read.lock().unwrap();
+        let mut seen = HashSet::new();
+        let mut unreached = Vec::new();
+        for section in self.conf.iter() {
+            for entry in section.iter() {
+                let pair = (section.name.as_str(), entry.key.as_str());
+                if !read.contains(&(pair.0.to_string(), pair.1.to_string())) && seen.insert(pair) {
+                    unreached.push(pair);
+                }
+            }
+        }
+        unreached
+    }
+}
+
+impl Conf {
+    /// Start recording which entries are read, to find settings that have
+    /// no effect. Only reads made through the returned
+    /// [`AccessTracker`] are recorded.
+    ///
+    /// [`AccessTracker`]: access/struct.AccessTracker.html
+    pub fn track_access(&self) -> AccessTracker<'_> {
+        AccessTracker {
+            conf: self,
+            read: Mutex::new(HashSet::new()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unreached_keys() {
+        let conf =
+            Conf::parse_str("[a.b]\nx = 1\ny = 2\n[c]\nz = 3\n[a.b]\ny = 4\nw = 5\n").unwrap();
+        let tracker = conf.track_access();
+        assert_eq!(tracker.get_path("a.b.x"), Some("1"));
+        assert_eq!(tracker.get("c", "missing"), None);
+        tracker.mark_read("c", "z");
+        assert_eq!(tracker.unreached_keys(), vec![("a.b", "y"), ("a.b", "w")]);
+        assert_eq!(tracker.get("a.b", "y"), Some("2"));
+        assert_eq!(tracker.unreached_keys(), vec![("a.b", "w")]);
+    }
+}
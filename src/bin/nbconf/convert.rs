@@ -0,0 +1,483 @@
+//! Conversion between configs and JSON or TOML.
+//!
+//! A config maps to an object (or TOML document) of sections, each of
+//! which is an object (or table) of string values. Only that shape is
+//! accepted when converting back.
+
+use nbconf::{Conf, Entry, Section, WriteOptions};
+
+/// Format supported by the `convert` subcommand.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Format {
+    Json,
+    Toml,
+}
+
+impl Format {
+    pub fn from_name(name: &str) -> Option<Format> {
+        match name {
+            "json" => Some(Format::Json),
+            "toml" => Some(Format::Toml),
+            _ => None,
+        }
+    }
+}
+
+/// Convert `conf` to `format`.
+pub fn to_format(conf: &Conf, format: Format) -> Result<String, String> {
+    match format {
+        Format::Json => Ok(to_json(conf)),
+        Format::Toml => to_toml(conf),
+    }
+}
+
+/// Parse `input` in `format` into a config.
+pub fn from_format(input: &str, format: Format) -> Result<Conf, String> {
+    match format {
+        Format::Json => from_json(input),
+        Format::Toml => from_toml(input),
+    }
+}
+
+/// Parse `input` in `format` and write it as config text. Fails if a
+/// section name, key or value could not be read back from the text.
+pub fn from_format_to_text(input: &str, format: Format) -> Result<String, String> {
+    from_format(input, format)?
+        .try_to_string_styled(&WriteOptions::default())
+        .map_err(|err| err.to_string())
+}
+
+/// Quote `s` as a string that is valid in both JSON and TOML.
+fn quote(s: &str) -> String {
+    let mut output = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => output += "\\\"",
+            '\\' => output += "\\\\",
+            '\n' => output += "\\n",
+            '\r' => output += "\\r",
+            '\t' => output += "\\t",
+            c if c.is_control() => output += &format!("\\u{:04x}", c as u32),
+            c => output.push(c),
+        }
+    }
+    output.push('"');
+    output
+}
+
+/// Serialize as JSON. Duplicate sections and keys are written as
+/// repeated members, in order.
+fn to_json(conf: &Conf) -> String {
+    let mut output = String::from("{");
+    for (index, section) in conf.sections.iter().enumerate() {
+        if index != 0 {
+            output += ",";
+        }
+        output += &format!("\n  {}: {{", quote(&section.name));
+        for (index, entry) in section.entries.iter().enumerate() {
+            if index != 0 {
+                output += ",";
+            }
+            output += &format!("\n    {}: {}", quote(&entry.key), quote(&entry.value));
+        }
+        output += if section.entries.is_empty() { "}" } else { "\n  }" };
+    }
+    output += if conf.sections.is_empty() { "}\n" } else { "\n}\n" };
+    output
+}
+
+fn is_bare_toml_key(key: &str) -> bool {
+    !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+fn toml_key(key: &str) -> String {
+    if is_bare_toml_key(key) {
+        key.to_string()
+    } else {
+        quote(key)
+    }
+}
+
+/// Serialize as TOML. Fails on duplicate sections or keys, which TOML
+/// does not allow.
+fn to_toml(conf: &Conf) -> Result<String, String> {
+    let mut output = String::new();
+    for (index, section) in conf.sections.iter().enumerate() {
+        if conf.sections[..index].iter().any(|s| s.name == section.name) {
+            return Err(format!("duplicate section {:?} cannot be written as TOML", section.name));
+        }
+        if index != 0 {
+            output += "\n";
+        }
+        output += &format!("[{}]\n", toml_key(&section.name));
+        for (index, entry) in section.entries.iter().enumerate() {
+            if section.entries[..index].iter().any(|e| e.key == entry.key) {
+                return Err(format!(
+                    "duplicate key {:?} in section {:?} cannot be written as TOML",
+                    entry.key, section.name
+                ));
+            }
+            output += &format!("{} = {}\n", toml_key(&entry.key), quote(&entry.value));
+        }
+    }
+    Ok(output)
+}
+
+/// Cursor over the input of the JSON and TOML readers.
+struct Reader<'a> {
+    input: &'a str,
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(input: &'a str) -> Reader<'a> {
+        Reader { input, pos: 0 }
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.input[self.pos..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+
+    fn line(&self) -> usize {
+        self.input[..self.pos].matches('\n').count() + 1
+    }
+
+    fn error(&self, message: &str) -> String {
+        format!("line {}: {}", self.line(), message)
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        if self.bump() == Some(expected) {
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected '{}'", expected)))
+        }
+    }
+
+    /// Skip spaces and tabs, and newlines too if `newlines` is set.
+    fn skip_whitespace(&mut self, newlines: bool) {
+        while let Some(c) = self.peek() {
+            if c == ' ' || c == '\t' || (newlines && (c == '\n' || c == '\r')) {
+                self.bump();
+            } else {
+                break;
+            }
+        }
+    }
+
+    /// Read a double-quoted string with JSON/TOML basic-string escapes.
+    fn quoted_string(&mut self) -> Result<String, String> {
+        self.expect('"')?;
+        let mut output = String::new();
+        loop {
+            match self.bump() {
+                None | Some('\n') => return Err(self.error("unterminated string")),
+                Some('"') => return Ok(output),
+                Some('\\') => {
+                    let c = match self.bump() {
+                        Some('"') => '"',
+                        Some('\\') => '\\',
+                        Some('/') => '/',
+                        Some('b') => '\u{8}',
+                        Some('f') => '\u{c}',
+                        Some('n') => '\n',
+                        Some('r') => '\r',
+                        Some('t') => '\t',
+                        Some('u') => self.unicode_escape(4)?,
+                        Some('U') => self.unicode_escape(8)?,
+                        _ => return Err(self.error("invalid escape")),
+                    };
+                    output.push(c);
+                }
+                Some(c) => output.push(c),
+            }
+        }
+    }
+
+    /// Read `digits` hex digits.
+    fn hex(&mut self, digits: usize) -> Result<u32, String> {
+        let hex = self.rest().get(..digits).unwrap_or("");
+        let code =
+            u32::from_str_radix(hex, 16).map_err(|_| self.error("invalid unicode escape"))?;
+        self.pos += digits;
+        Ok(code)
+    }
+
+    /// Read the hex digits of a `\u` or `\U` escape. A `\u` high
+    /// surrogate is combined with the `\u` low surrogate that must follow
+    /// it, as JSON writes characters outside the Basic Multilingual Plane.
+    fn unicode_escape(&mut self, digits: usize) -> Result<char, String> {
+        let mut code = self.hex(digits)?;
+        if digits == 4 && (0xd800..0xdc00).contains(&code) {
+            if !self.rest().starts_with("\\u") {
+                return Err(self.error("unpaired surrogate in unicode escape"));
+            }
+            self.pos += 2;
+            let low = self.hex(4)?;
+            if !(0xdc00..0xe000).contains(&low) {
+                return Err(self.error("unpaired surrogate in unicode escape"));
+            }
+            code = 0x10000 + ((code - 0xd800) << 10) + (low - 0xdc00);
+        }
+        std::char::from_u32(code).ok_or_else(|| self.error("invalid unicode escape"))
+    }
+
+    /// Read an unquoted scalar (number, boolean) up to a delimiter.
+    fn scalar(&mut self, delimiters: &[char]) -> Result<&'a str, String> {
+        let rest = self.rest();
+        let len = rest
+            .find(|c: char| delimiters.contains(&c) || c.is_whitespace())
+            .unwrap_or(rest.len());
+        let scalar = &rest[..len];
+        let is_number = scalar.parse::<f64>().is_ok() || scalar.replace('_', "").parse::<i64>().is_ok();
+        if scalar == "true" || scalar == "false" || is_number {
+            self.pos += len;
+            Ok(scalar)
+        } else {
+            Err(self.error("expected a string, number, or boolean"))
+        }
+    }
+}
+
+fn from_json(input: &str) -> Result<Conf, String> {
+    let mut reader = Reader::new(input);
+    let mut conf = Conf::new();
+    reader.skip_whitespace(true);
+    reader.expect('{')?;
+    reader.skip_whitespace(true);
+    if reader.peek() == Some('}') {
+        reader.bump();
+    } else {
+        loop {
+            reader.skip_whitespace(true);
+            let name = reader.quoted_string()?;
+            reader.skip_whitespace(true);
+            reader.expect(':')?;
+            reader.skip_whitespace(true);
+            conf.sections.push(Section::new_with_entries(&name, json_entries(&mut reader)?));
+            reader.skip_whitespace(true);
+            match reader.bump() {
+                Some(',') => continue,
+                Some('}') => break,
+                _ => return Err(reader.error("expected ',' or '}'")),
+            }
+        }
+    }
+    reader.skip_whitespace(true);
+    if reader.peek().is_some() {
+        return Err(reader.error("trailing characters"));
+    }
+    Ok(conf)
+}
+
+/// Read a JSON object of scalar values.
+fn json_entries(reader: &mut Reader) -> Result<Vec<Entry>, String> {
+    let mut entries = Vec::new();
+    if reader.peek() != Some('{') {
+        return Err(reader.error("expected an object of entries"));
+    }
+    reader.bump();
+    reader.skip_whitespace(true);
+    if reader.peek() == Some('}') {
+        reader.bump();
+        return Ok(entries);
+    }
+    loop {
+        reader.skip_whitespace(true);
+        let key = reader.quoted_string()?;
+        reader.skip_whitespace(true);
+        reader.expect(':')?;
+        reader.skip_whitespace(true);
+        let value = if reader.peek() == Some('"') {
+            reader.quoted_string()?
+        } else {
+            reader.scalar(&[',', '}'])?.to_string()
+        };
+        entries.push(Entry::new(&key, &value));
+        reader.skip_whitespace(true);
+        match reader.bump() {
+            Some(',') => continue,
+            Some('}') => return Ok(entries),
+            _ => return Err(reader.error("expected ',' or '}'")),
+        }
+    }
+}
+
+/// Read a bare or quoted TOML key.
+fn toml_read_key(reader: &mut Reader) -> Result<String, String> {
+    if reader.peek() == Some('"') {
+        return reader.quoted_string();
+    }
+    let rest = reader.rest();
+    let len = rest
+        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '-'))
+        .unwrap_or(rest.len());
+    if len == 0 {
+        return Err(reader.error("expected a key"));
+    }
+    reader.pos += len;
+    Ok(rest[..len].to_string())
+}
+
+/// Require the rest of the line to be empty or a comment.
+fn toml_end_of_line(reader: &mut Reader) -> Result<(), String> {
+    reader.skip_whitespace(false);
+    if reader.peek() == Some('#') {
+        while !matches!(reader.peek(), None | Some('\n')) {
+            reader.bump();
+        }
+    }
+    if reader.rest().starts_with("\r\n") {
+        reader.bump();
+    }
+    match reader.peek() {
+        None => Ok(()),
+        Some('\n') => {
+            reader.bump();
+            Ok(())
+        }
+        _ => Err(reader.error("unsupported TOML syntax")),
+    }
+}
+
+fn from_toml(input: &str) -> Result<Conf, String> {
+    let mut reader = Reader::new(input);
+    let mut conf = Conf::new();
+    loop {
+        reader.skip_whitespace(true);
+        match reader.peek() {
+            None => return Ok(conf),
+            Some('#') => {}
+            Some('[') => {
+                reader.bump();
+                reader.skip_whitespace(false);
+                let name = toml_read_key(&mut reader)?;
+                reader.skip_whitespace(false);
+                reader.expect(']')?;
+                conf.sections.push(Section::new(&name));
+            }
+            Some(_) => {
+                let key = toml_read_key(&mut reader)?;
+                reader.skip_whitespace(false);
+                reader.expect('=')?;
+                reader.skip_whitespace(false);
+                let value = match reader.peek() {
+                    Some('"') => reader.quoted_string()?,
+                    Some('\'') => {
+                        reader.bump();
+                        let rest = reader.rest();
+                        let len = rest
+                            .find(['\'', '\n'])
+                            .filter(|len| rest[*len..].starts_with('\''))
+                            .ok_or_else(|| reader.error("unterminated string"))?;
+                        reader.pos += len + 1;
+                        rest[..len].to_string()
+                    }
+                    _ => reader.scalar(&['#'])?.to_string(),
+                };
+                match conf.sections.last_mut() {
+                    Some(section) => section.entries.push(Entry::new(&key, &value)),
+                    None => return Err(reader.error("key outside of any table")),
+                }
+            }
+        }
+        toml_end_of_line(&mut reader)?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Conf {
+        Conf::parse_str("[server]\nhost = \"::\"\nport = 80\n\n[log file]\npath = C:\\log").unwrap()
+    }
+
+    #[test]
+    fn test_json() {
+        let json = to_format(&sample(), Format::Json).unwrap();
+        assert_eq!(
+            json,
+            r#"{
+  "server": {
+    "host": "\"::\"",
+    "port": "80"
+  },
+  "log file": {
+    "path": "C:\\log"
+  }
+}
+"#
+        );
+        assert_eq!(from_format(&json, Format::Json), Ok(sample()));
+        assert_eq!(
+            from_format(r#"{"a": {"x": 1.5, "y": true}, "b": {}}"#, Format::Json),
+            Ok(Conf::parse_str("[a]\nx = 1.5\ny = true\n[b]").unwrap())
+        );
+        assert!(from_format(r#"{"a": {"x": [1]}}"#, Format::Json).is_err());
+    }
+
+    #[test]
+    fn test_json_surrogates() {
+        assert_eq!(
+            from_format(r#"{"a": {"x": "\ud83d\ude00 \u00e9"}}"#, Format::Json),
+            Ok(Conf::parse_str("[a]\nx = \u{1f600} \u{e9}").unwrap())
+        );
+        let unpaired = "line 1: unpaired surrogate in unicode escape";
+        let cases = [
+            (r"\ud83d", unpaired),
+            (r"\ud83d\u0041", unpaired),
+            (r"\ud83dx", unpaired),
+            (r"\ude00", "line 1: invalid unicode escape"),
+        ];
+        for (value, error) in cases.iter() {
+            let json = format!(r#"{{"a": {{"x": "{}"}}}}"#, value);
+            assert_eq!(from_format(&json, Format::Json), Err(error.to_string()));
+        }
+    }
+
+    #[test]
+    fn test_from_format_to_text() {
+        assert_eq!(
+            from_format_to_text(r#"{"a": {"x": "1"}}"#, Format::Json),
+            Ok("[a]\nx = 1\n".to_string())
+        );
+        assert_eq!(
+            from_format_to_text(r#"{"a": {"x": "1\n[evil]"}}"#, Format::Json),
+            Err("section \"a\": invalid value for key \"x\"".to_string())
+        );
+        assert_eq!(
+            from_format_to_text("[a]\n\"x=y\" = 1\n", Format::Toml),
+            Err("section \"a\": invalid key \"x=y\"".to_string())
+        );
+    }
+
+    #[test]
+    fn test_toml() {
+        let toml = to_format(&sample(), Format::Toml).unwrap();
+        assert_eq!(
+            toml,
+            "[server]\nhost = \"\\\"::\\\"\"\nport = \"80\"\n\n[\"log file\"]\npath = \"C:\\\\log\"\n"
+        );
+        assert_eq!(from_format(&toml, Format::Toml), Ok(sample()));
+        assert_eq!(
+            from_format("# comment\n[a]\nx = 1 # note\ny = 'lit'\n", Format::Toml),
+            Ok(Conf::parse_str("[a]\nx = 1\ny = lit").unwrap())
+        );
+        assert!(to_format(&Conf::parse_str("[a]\n[a]").unwrap(), Format::Toml).is_err());
+        assert!(from_format("[a]\nx = [1, 2]\n", Format::Toml).is_err());
+    }
+}
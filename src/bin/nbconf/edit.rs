@@ -0,0 +1,167 @@
+//! In-place edits of config text that leave untouched lines as they are.
+
+use nbconf::split_header;
+
+/// Classification of a single line of config text.
+enum Line<'a> {
+    Header(&'a str),
+    Entry(&'a str),
+    Other,
+}
+
+impl<'a> Line<'a> {
+    /// Classify the line at `index`. A byte order mark at the start of the
+    /// first line is skipped, as the parser does.
+    fn classify(index: usize, line: &'a str) -> Line<'a> {
+        let line = if index == 0 {
+            line.strip_prefix('\u{feff}').unwrap_or(line)
+        } else {
+            line
+        };
+        let line = line.trim();
+        if line.starts_with('#') || line.starts_with(';') {
+            Line::Other
+        } else if let Some((name, _)) = split_header(line) {
+            Line::Header(name)
+        } else if let Some(equals) = line.find('=') {
+            Line::Entry(line[..equals].trim_end())
+        } else {
+            Line::Other
+        }
+    }
+}
+
+/// Split a line into its content and its line terminator.
+fn split_terminator(line: &str) -> (&str, &str) {
+    let content = line.trim_end_matches(&['\r', '\n'][..]);
+    (content, &line[content.len()..])
+}
+
+/// The line terminator used by `source`.
+fn eol(source: &str) -> &'static str {
+    if source.contains("\r\n") {
+        "\r\n"
+    } else {
+        "\n"
+    }
+}
+
+/// Set `key` to `value` in the first section named `section`.
+///
+/// The first matching entry has its value replaced. If there is no such
+/// entry, a new one is added at the end of the section, and if there is
+/// no such section, a new one is added at the end of the file.
+pub fn set(source: &str, section: &str, key: &str, value: &str) -> String {
+    let eol = eol(source);
+    let mut lines: Vec<String> = source.split_inclusive('\n').map(str::to_string).collect();
+
+    let mut in_section = false;
+    let mut insert_after = None;
+    let mut indent = "";
+    for (index, line) in source.split_inclusive('\n').enumerate() {
+        match Line::classify(index, line) {
+            Line::Header(name) => {
+                if in_section {
+                    break;
+                }
+                if name == section {
+                    in_section = true;
+                    insert_after = Some(index);
+                }
+            }
+            Line::Entry(entry_key) if in_section => {
+                insert_after = Some(index);
+                indent = &line[..line.len() - line.trim_start().len()];
+                if entry_key == key {
+                    let (content, terminator) = split_terminator(line);
+                    let equals = content.find('=').unwrap();
+                    let old_value = &content[equals + 1..];
+                    let spacing = if old_value.trim().is_empty() {
+                        if content[..equals].ends_with(char::is_whitespace) {
+                            " "
+                        } else {
+                            ""
+                        }
+                    } else {
+                        &old_value[..old_value.len() - old_value.trim_start().len()]
+                    };
+                    lines[index] = format!("{}{}{}{}", &content[..=equals], spacing, value, terminator);
+                    return lines.concat();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let new_entry = format!("{}{} = {}{}", indent, key, value, eol);
+    match insert_after {
+        Some(index) => {
+            if !lines[index].ends_with('\n') {
+                lines[index] += eol;
+            }
+            lines.insert(index + 1, new_entry);
+        }
+        None => {
+            if let Some(last) = lines.last_mut() {
+                if !last.ends_with('\n') {
+                    *last += eol;
+                }
+                lines.push(eol.to_string());
+            }
+            lines.push(format!("[{}]{}", section, eol));
+            lines.push(new_entry);
+        }
+    }
+    lines.concat()
+}
+
+/// Remove every entry with `key` from the sections named `section`.
+///
+/// Returns the edited text and the number of entries removed.
+pub fn del(source: &str, section: &str, key: &str) -> (String, usize) {
+    let mut output = String::new();
+    let mut removed = 0;
+    let mut in_section = false;
+    for (index, line) in source.split_inclusive('\n').enumerate() {
+        match Line::classify(index, line) {
+            Line::Header(name) => in_section = name == section,
+            Line::Entry(entry_key) if in_section && entry_key == key => {
+                removed += 1;
+                continue;
+            }
+            _ => {}
+        }
+        output += line;
+    }
+    (output, removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set() {
+        let source = "[a]\n  x=1\n\n[b]\ny  =  2 \n[b]\ny = 3";
+        assert_eq!(set(source, "b", "y", "4"), "[a]\n  x=1\n\n[b]\ny  =  4\n[b]\ny = 3");
+        assert_eq!(set(source, "a", "z", "5"), "[a]\n  x=1\n  z = 5\n\n[b]\ny  =  2 \n[b]\ny = 3");
+        assert_eq!(set("[a]\r\nx = 1", "c", "z", "5"), "[a]\r\nx = 1\r\n\r\n[c]\r\nz = 5\r\n");
+        assert_eq!(set("", "c", "z", "5"), "[c]\nz = 5\n");
+        assert_eq!(set("\u{feff}[a]\nx = 1", "a", "x", "2"), "\u{feff}[a]\nx = 2");
+        assert_eq!(set("\u{feff}[a]\n", "a", "y", "2"), "\u{feff}[a]\ny = 2\n");
+    }
+
+    #[test]
+    fn test_del() {
+        let source = "[a]\nx = 1\ny = 2\n[b]\nx = 3\n[a]\n# x = 0\nx = 4\n";
+        assert_eq!(
+            del(source, "a", "x"),
+            ("[a]\ny = 2\n[b]\nx = 3\n[a]\n# x = 0\n".to_string(), 2)
+        );
+        assert_eq!(del(source, "a", "z"), (source.to_string(), 0));
+        assert_eq!(
+            del("\u{feff}[a]\nx = 1\n", "a", "x"),
+            ("\u{feff}[a]\n".to_string(), 1)
+        );
+    }
+}
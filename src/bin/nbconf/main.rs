@@ -0,0 +1,371 @@
+//! Command-line tool for reading and editing nbconf files.
+//!
+//! Edits are made in place and leave every other line of the file as it
+//! was.
+
+mod convert;
+mod edit;
+mod linediff;
+
+use convert::Format;
+use linediff::DiffLine;
+use nbconf::fmt::FmtOptions;
+use nbconf::schema::{Schema, Severity};
+use nbconf::{
+    ColorChoice, Conf, Entry, MergePolicy, ParseErrorKind, ParseOptions, Section, WriteOptions,
+};
+use std::fs;
+use std::io::{self, Read};
+use std::process;
+
+const USAGE: &str = "usage:
+    nbconf get <file> <section> <key>
+    nbconf set <file> <section> <key> <value>
+    nbconf del <file> <section> <key>
+    nbconf validate [--schema <schema file>] <file>...
+    nbconf fmt [--check] <file>...
+    nbconf convert --to <json|toml> <file>
+    nbconf convert --from <json|toml> <file>
+    nbconf diff <old file> <new file>
+    nbconf merge [--policy <override|keep|append>] <base file> <file>... [-o <output file>]
+
+Use `-` as the file to read from stdin, except with `set`, `del` and
+`fmt` without `--check`, which write the file back.";
+
+/// Read `path`, or stdin if `path` is `-`.
+fn read_source(path: &str) -> io::Result<String> {
+    if path == "-" {
+        let mut input = String::new();
+        io::stdin().read_to_string(&mut input).map(|_| input)
+    } else {
+        fs::read_to_string(path)
+    }
+}
+
+/// Like [`read_source`], with the path in the error message.
+fn read(path: &str) -> Result<String, String> {
+    read_source(path).map_err(|err| format!("{}: {}", path, err))
+}
+
+/// Read and parse `path`, returning both the text and the config.
+fn load(path: &str) -> Result<(String, Conf), String> {
+    let source = read(path)?;
+    let conf = Conf::parse_str_named(path, &source).map_err(|err| {
+        err.render_with(&source, ColorChoice::Auto)
+            .trim_end()
+            .to_string()
+    })?;
+    Ok((source, conf))
+}
+
+/// Fail if `path` is `-`, for commands that write the file back.
+fn writable(path: &str) -> Result<(), String> {
+    if path == "-" {
+        return Err("stdin cannot be edited in place".to_string());
+    }
+    Ok(())
+}
+
+fn write(path: &str, contents: &str) -> Result<(), String> {
+    fs::write(path, contents).map_err(|err| format!("{}: {}", path, err))
+}
+
+/// Print the value of `key`. Exits with 1 if the key is not set.
+fn get(path: &str, section: &str, key: &str) -> Result<i32, String> {
+    let (_, conf) = load(path)?;
+    let value = conf
+        .sections
+        .iter()
+        .find(|s| s.name == section)
+        .and_then(|s| s.get(key));
+    match value {
+        Some(value) => {
+            println!("{}", value);
+            Ok(0)
+        }
+        None => Ok(1),
+    }
+}
+
+/// Set `key` to `value`. Fails without changing the file if the section
+/// name, key or value would not read back as given.
+fn set(path: &str, section: &str, key: &str, value: &str) -> Result<i32, String> {
+    Section::new_with_entries(section, vec![Entry::new(key, value)])
+        .check_serializable()
+        .map_err(|err| err.to_string())?;
+    writable(path)?;
+    let (source, _) = load(path)?;
+    write(path, &edit::set(&source, section, key, value))?;
+    Ok(0)
+}
+
+/// Remove `key`. Exits with 1 if the key was not set.
+fn del(path: &str, section: &str, key: &str) -> Result<i32, String> {
+    writable(path)?;
+    let (source, _) = load(path)?;
+    let (output, removed) = edit::del(&source, section, key);
+    if removed == 0 {
+        return Ok(1);
+    }
+    write(path, &output)?;
+    Ok(0)
+}
+
+/// Check that each file parses with strict options and follows the
+/// schema, if given. Exits with 1 if any file has an error.
+fn validate(args: &[&str]) -> Result<i32, String> {
+    let (schema, paths) = match args {
+        ["--schema", schema_path, paths @ ..] => {
+            let (_, rules) = load(schema_path)?;
+            let schema =
+                Schema::from_conf(&rules).map_err(|err| format!("{}: {}", schema_path, err))?;
+            (Some(schema), paths)
+        }
+        ["--schema"] => return Err("missing schema file".to_string()),
+        paths => (None, paths),
+    };
+    if paths.is_empty() {
+        return Err("validate needs at least one file".to_string());
+    }
+
+    let (diagnostics, code) = check_files(paths, schema.as_ref());
+    for diagnostic in diagnostics {
+        println!("{}", diagnostic);
+    }
+    Ok(code)
+}
+
+/// The diagnostics for [`validate`] and its exit code. Each diagnostic is
+/// `file:line[:column]: severity: message`, with the column only for
+/// control characters. Missing sections are reported at line 1, and a
+/// file that cannot be read as `file: error: message`.
+fn check_files(paths: &[&str], schema: Option<&Schema>) -> (Vec<String>, i32) {
+    let mut diagnostics = Vec::new();
+    let mut code = 0;
+    for path in paths {
+        let source = match read_source(path) {
+            Ok(source) => source,
+            Err(err) => {
+                diagnostics.push(format!("{}: error: {}", path, err));
+                code = 1;
+                continue;
+            }
+        };
+        let violations = Conf::parse_str_with(&source, &ParseOptions::strict()).and_then(|_| {
+            schema.map_or(Ok(Vec::new()), |schema| schema.validate_str(&source))
+        });
+        let violations = match violations {
+            Ok(violations) => violations,
+            Err(err) => {
+                diagnostics.push(match err.kind {
+                    ParseErrorKind::ControlCharacter { column } => {
+                        format!("{}:{}:{}: error: {}", path, err.line, column, err.kind)
+                    }
+                    _ => format!("{}:{}: error: {}", path, err.line, err.kind),
+                });
+                code = 1;
+                continue;
+            }
+        };
+        for violation in violations {
+            let severity = match violation.severity {
+                Severity::Warning => "warning",
+                Severity::Error => {
+                    code = 1;
+                    "error"
+                }
+            };
+            let line = violation.line.unwrap_or(1);
+            diagnostics.push(format!("{}:{}: {}: {}", path, line, severity, violation));
+        }
+    }
+    (diagnostics, code)
+}
+
+/// Reformat each file in place. With `check`, print what would change
+/// instead, and exit with 1 if any file is not formatted.
+fn fmt(check: bool, paths: &[&str]) -> Result<i32, String> {
+    let mut code = 0;
+    for path in paths {
+        if !check {
+            writable(path)?;
+        }
+        let source = read(path)?;
+        let options = FmtOptions {
+            style: WriteOptions::detect(&source),
+        };
+        let formatted =
+            nbconf::fmt::format(&source, &options).map_err(|err| format!("{}: {}", path, err))?;
+        if formatted == source {
+            continue;
+        }
+        if check {
+            println!("--- {}\n+++ {} (formatted)", path, path);
+            for line in linediff::diff(&source, &formatted) {
+                match line {
+                    DiffLine::Same(_) => {}
+                    DiffLine::Removed(line) => println!("-{}", line),
+                    DiffLine::Added(line) => println!("+{}", line),
+                }
+            }
+            code = 1;
+        } else {
+            write(path, &formatted)?;
+        }
+    }
+    Ok(code)
+}
+
+/// Convert to (if `to` is set) or from another format, printing the
+/// result to stdout.
+fn convert(to: bool, format: &str, path: &str) -> Result<i32, String> {
+    let format = Format::from_name(format).ok_or_else(|| format!("unknown format {:?}", format))?;
+    let input = read(path)?;
+    let output = if to {
+        let conf = Conf::parse_str(&input).map_err(|err| format!("{}: {}", path, err))?;
+        convert::to_format(&conf, format)?
+    } else {
+        convert::from_format_to_text(&input, format).map_err(|err| format!("{}: {}", path, err))?
+    };
+    print!("{}", output);
+    Ok(0)
+}
+
+/// Print the semantic differences between two files. Exits with 1 if
+/// there are any.
+fn diff(old_path: &str, new_path: &str) -> Result<i32, String> {
+    let (_, old) = load(old_path)?;
+    let (_, new) = load(new_path)?;
+    let changes = old.diff(&new);
+    for change in changes.iter() {
+        println!("{}", change);
+    }
+    Ok(if changes.is_empty() { 0 } else { 1 })
+}
+
+/// Merge files onto a base file in order, writing the result to the
+/// `-o` file or stdout.
+fn merge(args: &[&str]) -> Result<i32, String> {
+    let mut policy = MergePolicy::Override;
+    let mut output = None;
+    let mut paths = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match *arg {
+            "--policy" => {
+                policy = match args.next() {
+                    Some(&"override") => MergePolicy::Override,
+                    Some(&"keep") => MergePolicy::KeepExisting,
+                    Some(&"append") => MergePolicy::Append,
+                    other => return Err(format!("invalid merge policy {:?}", other.unwrap_or(&""))),
+                }
+            }
+            "-o" => output = Some(*args.next().ok_or("missing output file")?),
+            path => paths.push(path),
+        }
+    }
+    if paths.len() < 2 {
+        return Err("merge needs a base file and at least one other file".to_string());
+    }
+
+    let (_, mut merged) = load(paths[0])?;
+    for path in &paths[1..] {
+        let (_, conf) = load(path)?;
+        merged.merge(&conf, policy);
+    }
+    match output {
+        Some(path) => write(path, &merged.to_string())?,
+        None => print!("{}", merged),
+    }
+    Ok(0)
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let result = match args.as_slice() {
+        ["get", path, section, key] => get(path, section, key),
+        ["set", path, section, key, value] => set(path, section, key, value),
+        ["del", path, section, key] => del(path, section, key),
+        ["validate", args @ ..] if !args.is_empty() => validate(args),
+        ["fmt", "--check", paths @ ..] if !paths.is_empty() => fmt(true, paths),
+        ["fmt", paths @ ..] if !paths.is_empty() => fmt(false, paths),
+        ["convert", "--to", format, path] => convert(true, format, path),
+        ["convert", "--from", format, path] => convert(false, format, path),
+        ["diff", old_path, new_path] => diff(old_path, new_path),
+        ["merge", args @ ..] => merge(args),
+        _ => {
+            eprintln!("{}", USAGE);
+            process::exit(2);
+        }
+    };
+    match result {
+        Ok(code) => process::exit(code),
+        Err(err) => {
+            eprintln!("nbconf: {}", err);
+            process::exit(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nbconf::schema::{KeySchema, SectionSchema, ValueType};
+
+    #[test]
+    fn test_check_files() {
+        let dir = std::env::temp_dir().join(format!("nbconf-validate-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let files: &[(&str, &[u8])] = &[
+            ("good.conf", b"[server]\nport = 80\n"),
+            ("latin1.conf", b"[server]\nname = caf\xe9\n"),
+            ("bad.conf", b"[server]\nport = 8\x010\n"),
+            ("wrong.conf", b"[server]\nport = 80\n\nport = x\n[extra]\n"),
+        ];
+        let mut paths: Vec<String> = files
+            .iter()
+            .map(|(name, contents)| {
+                let path = dir.join(name);
+                fs::write(&path, contents).unwrap();
+                path.to_str().unwrap().to_string()
+            })
+            .collect();
+        paths.insert(1, dir.join("missing.conf").to_str().unwrap().to_string());
+        let paths: Vec<&str> = paths.iter().map(String::as_str).collect();
+
+        let (diagnostics, code) = check_files(&paths, None);
+        assert_eq!(code, 1);
+        assert_eq!(diagnostics.len(), 3);
+        assert!(diagnostics[0].starts_with(&format!("{}: error: ", paths[1])));
+        assert!(diagnostics[1].starts_with(&format!("{}: error: ", paths[2])));
+        assert_eq!(
+            diagnostics[2],
+            format!("{}:2:9: error: control character at column 9", paths[3])
+        );
+
+        let schema = Schema::new(vec![
+            SectionSchema::new(
+                "server",
+                vec![KeySchema::new("port", ValueType::Int).required()],
+            )
+            .required(),
+            SectionSchema::new("log", vec![]).required(),
+        ]);
+        let (diagnostics, code) = check_files(&paths[4..], Some(&schema));
+        assert_eq!(code, 1);
+        assert_eq!(
+            diagnostics,
+            vec![
+                format!(
+                    "{}:4: error: [server] port: invalid value \"x\", expected an integer",
+                    paths[4]
+                ),
+                format!("{}:1: error: [log]: missing required section", paths[4]),
+                format!("{}:5: warning: [extra]: unknown section", paths[4]),
+            ]
+        );
+        assert_eq!(check_files(&paths[..1], None), (vec![], 0));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
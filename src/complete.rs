@@ -0,0 +1,259 @@
+//! Completions at a position in the text of a config, for editors.
+//!
+//! Example:
+//!
+//! ```
+//! use nbconf::complete::complete_at;
+//! use nbconf::schema::{KeySchema, Schema, SectionSchema, ValueType};
+//!
+//! let schema = Schema::new(vec![SectionSchema::new(
+//!     "server",
+//!     vec![
+//!         KeySchema::new("port", ValueType::Int),
+//!         KeySchema::new("protocol", ValueType::Enum(vec!["tcp".into(), "udp".into()])),
+//!     ],
+//! )]);
+//! let input = "[server]\npro";
+//! let completions = complete_at(input, input.len(), Some(&schema));
+//! assert_eq!(completions.range, 9..12);
+//! assert_eq!(completions.items[0].text, "protocol");
+//! ```
+
+use crate::schema::{Schema, ValueType};
+use crate::{is_comment, split_header};
+use std::ops::Range;
+
+/// What a [`Completion`] completes.
+///
+/// [`Completion`]: struct.Completion.html
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum CompletionKind {
+    /// A section name, inside a header.
+    Section,
+    /// A key in the enclosing section.
+    Key,
+    /// A value for the key on the same line.
+    Value,
+}
+
+/// A candidate for the text being typed.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Completion {
+    /// What the candidate completes.
+    pub kind: CompletionKind,
+    /// The text to put in place of the partial text.
+    pub text: String,
+    /// Description from the schema, if any.
+    pub detail: Option<String>,
+}
+
+/// Returned by [`complete_at`].
+///
+/// [`complete_at`]: fn.complete_at.html
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Completions {
+    /// Byte range of the partial text that a candidate replaces.
+    pub range: Range<usize>,
+    /// Candidates that start with the partial text, in order.
+    pub items: Vec<Completion>,
+}
+
+/// The start of each line of `input`, with the offset and text of the line
+/// without surrounding whitespace. A byte order mark is not part of the
+/// first line.
+fn lines(input: &str) -> Vec<(usize, usize, &str)> {
+    let mut offset = if input.starts_with('\u{feff}') {
+        '\u{feff}'.len_utf8()
+    } else {
+        0
+    };
+    input[offset..]
+        .split_inclusive('\n')
+        .map(|raw_line| {
+            let line = raw_line.trim_start();
+            let start = offset + raw_line.len() - line.len();
+            let raw_start = offset;
+            offset += raw_line.len();
+            (raw_start, start, line.trim_end())
+        })
+        .collect()
+}
+
+/// The section name in a trimmed header line, which may be missing its
+/// closing bracket.
+fn header(line: &str) -> Option<&str> {
+    match split_header(line) {
+        Some((name, _)) => Some(name),
+        None => line.strip_prefix('['),
+    }
+}
+
+/// The candidates for the text at byte `offset` in `input`: section names
+/// inside a header, keys of the enclosing section at the start of an
+/// entry, or the allowed values of the entry's key after its `=`.
+///
+/// Section names come from `schema` and from the headers in `input`.
+/// Keys and values come only from `schema`, and keys already set in the
+/// enclosing section are left out. The input does not need to parse, so
+/// that text being edited can be completed.
+pub fn complete_at(input: &str, offset: usize, schema: Option<&Schema>) -> Completions {
+    let mut completions = Completions {
+        range: offset..offset,
+        items: Vec::new(),
+    };
+    if !input.is_char_boundary(offset) {
+        return completions;
+    }
+    let lines = lines(input);
+    // The line containing `offset`, which is past the last line if the
+    // input ends there with a line break.
+    let index = match lines
+        .iter()
+        .rposition(|(raw_start, _, _)| *raw_start <= offset)
+    {
+        Some(index) if offset == input.len() && input.ends_with('\n') => index + 1,
+        Some(index) => index,
+        None => 0,
+    };
+    let typed = match lines.get(index) {
+        Some((_, start, _)) if *start <= offset => &input[*start..offset],
+        _ => "",
+    };
+    if is_comment(typed) {
+        return completions;
+    }
+    let before = &lines[..index];
+    let after = lines.get(index + 1..).unwrap_or_default();
+    let section = before.iter().rev().find_map(|(_, _, line)| header(line));
+    let section_schema = section.and_then(|name| schema.and_then(|schema| schema.section(name)));
+    let mut add = |kind, text: &str, detail: Option<&String>| {
+        if !completions.items.iter().any(|c| c.text == text) {
+            completions.items.push(Completion {
+                kind,
+                text: text.to_string(),
+                detail: detail.cloned(),
+            });
+        }
+    };
+
+    if let Some(name) = typed.strip_prefix('[') {
+        if name.contains(']') {
+            return completions;
+        }
+        let schema_names = schema
+            .iter()
+            .flat_map(|schema| schema.sections.iter())
+            .map(|s| (s.name.as_str(), s.description.as_ref()));
+        let document_names = before
+            .iter()
+            .chain(after.iter())
+            .filter_map(|(_, _, line)| header(line))
+            .map(|name| (name, None));
+        for (candidate, detail) in schema_names.chain(document_names) {
+            if candidate.starts_with(name) {
+                add(CompletionKind::Section, candidate, detail);
+            }
+        }
+        completions.range = offset - name.len()..offset;
+    } else if let Some(equals) = typed.find('=') {
+        let value = typed[equals + 1..].trim_start();
+        let key_schema = section_schema.and_then(|s| s.key(typed[..equals].trim_end()));
+        let candidates: Vec<String> = match key_schema.map(|k| &k.value_type) {
+            Some(ValueType::Bool) => vec!["true".to_string(), "false".to_string()],
+            Some(ValueType::Enum(variants)) => variants.clone(),
+            _ => Vec::new(),
+        };
+        for candidate in candidates.iter() {
+            if candidate.starts_with(value) {
+                add(CompletionKind::Value, candidate, None);
+            }
+        }
+        completions.range = offset - value.len()..offset;
+    } else if let Some(section_schema) = section_schema {
+        let in_section = |(_, _, line): &&(usize, usize, &str)| header(line).is_none();
+        let present: Vec<&str> = before
+            .iter()
+            .rev()
+            .take_while(in_section)
+            .chain(after.iter().take_while(in_section))
+            .filter(|(_, _, line)| !is_comment(line))
+            .filter_map(|(_, _, line)| line.split('=').next())
+            .map(str::trim)
+            .collect();
+        for key_schema in section_schema.keys.iter() {
+            let name = key_schema.name.as_str();
+            if name.starts_with(typed) && !present.contains(&name) {
+                add(CompletionKind::Key, name, key_schema.description.as_ref());
+            }
+        }
+        completions.range = offset - typed.len()..offset;
+    }
+    completions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::{KeySchema, SectionSchema};
+
+    #[test]
+    fn test_complete_at() {
+        let schema = Schema::new(vec![
+            SectionSchema::new(
+                "server",
+                vec![
+                    KeySchema::new("port", ValueType::Int).description("Port."),
+                    KeySchema::new("public", ValueType::Bool),
+                    KeySchema::new("host", ValueType::String),
+                ],
+            ),
+            SectionSchema::new("log", vec![]),
+        ]);
+        let texts = |input: &str, offset| {
+            let completions = complete_at(input, offset, Some(&schema));
+            let texts: Vec<String> = completions.items.into_iter().map(|c| c.text).collect();
+            (completions.range, texts)
+        };
+
+        let input = "[server]\nhost = a\np\n[s]\n";
+        assert_eq!(
+            texts(input, 19),
+            (18..19, vec!["port".into(), "public".into()])
+        );
+        assert_eq!(
+            texts(input, 18),
+            (18..18, vec!["port".into(), "public".into()])
+        );
+        assert_eq!(
+            texts(input, 21),
+            (21..21, vec!["server".into(), "log".into()])
+        );
+        assert_eq!(texts(input, 22), (21..22, vec!["server".into()]));
+        assert_eq!(texts(input, 23), (23..23, vec![]));
+        assert_eq!(texts(input, 24), (24..24, vec![]));
+
+        let input = "[server]\npublic = t";
+        assert_eq!(texts(input, input.len()), (18..19, vec!["true".into()]));
+        assert_eq!(
+            texts(input, 17),
+            (17..17, vec!["true".into(), "false".into()])
+        );
+
+        let completions = complete_at("[server]\n", 9, Some(&schema));
+        assert_eq!(completions.items[0].kind, CompletionKind::Key);
+        assert_eq!(completions.items[0].detail.as_deref(), Some("Port."));
+        assert_eq!(
+            complete_at("[server]\n# p", 12, Some(&schema)).items,
+            vec![]
+        );
+        assert_eq!(complete_at("[server]\np", 10, None).items, vec![]);
+
+        let input = "\u{feff}[server]\np";
+        assert_eq!(
+            texts(input, input.len()),
+            (12..13, vec!["port".into(), "public".into()])
+        );
+        assert_eq!(texts(input, 6), (4..6, vec!["server".into()]));
+        assert_eq!(texts(input, 0), (0..0, vec![]));
+    }
+}
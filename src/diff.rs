@@ -0,0 +1,283 @@
+//! Semantic differences between configs.
+
+use crate::Conf;
+
+/// A difference between two configs, as produced by [`Conf::diff`].
+///
+/// [`Conf::diff`]: ../struct.Conf.html#method.diff
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Change {
+    /// A section is only in the new config. Each of its entries is also
+    /// reported as [`Change::Added`].
+    ///
+    /// [`Change::Added`]: enum.Change.html#variant.Added
+    SectionAdded { section: String },
+    /// A section is only in the old config. Each of its entries is also
+    /// reported as [`Change::Removed`].
+    ///
+    /// [`Change::Removed`]: enum.Change.html#variant.Removed
+    SectionRemoved { section: String },
+    /// An entry is only in the new config.
+    Added {
+        section: String,
+        key: String,
+        value: String,
+    },
+    /// An entry is only in the old config.
+    Removed {
+        section: String,
+        key: String,
+        value: String,
+    },
+    /// An entry has a different value in the new config.
+    Changed {
+        section: String,
+        key: String,
+        old: String,
+        new: String,
+    },
+}
+
+impl std::fmt::Display for Change {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Change::SectionAdded { section } => write!(f, "+ [{}]", section),
+            Change::SectionRemoved { section } => write!(f, "- [{}]", section),
+            Change::Added {
+                section,
+                key,
+                value,
+            } => write!(f, "+ [{}] {} = {}", section, key, value),
+            Change::Removed {
+                section,
+                key,
+                value,
+            } => write!(f, "- [{}] {} = {}", section, key, value),
+            Change::Changed {
+                section,
+                key,
+                old,
+                new,
+            } => write!(f, "~ [{}] {} = {} -> {}", section, key, old, new),
+        }
+    }
+}
+
+/// A config that remembers its state when loaded, so that the changes
+/// made since can be listed.
+///
+/// `TrackedConf` dereferences to the current [`Conf`] for reading and
+/// editing.
+///
+/// Example:
+///
+/// ```
+/// use nbconf::diff::TrackedConf;
+///
+/// let mut conf = TrackedConf::new(nbconf::Conf::parse_str("[server]\nport = 80").unwrap());
+/// conf.sections[0].set("port", "8080");
+/// assert_eq!(conf.changes()[0].to_string(), "~ [server] port = 80 -> 8080");
+///
+/// conf.mark_saved();
+/// assert!(!conf.is_modified());
+/// ```
+///
+/// [`Conf`]: ../struct.Conf.html
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct TrackedConf {
+    original: Conf,
+    current: Conf,
+}
+
+impl TrackedConf {
+    /// Start tracking changes to `conf`.
+    pub fn new(conf: Conf) -> TrackedConf {
+        TrackedConf {
+            original: conf.clone(),
+            current: conf,
+        }
+    }
+
+    /// The config as it was when tracking started or was last saved.
+    pub fn original(&self) -> &Conf {
+        &self.original
+    }
+
+    /// Whether the config differs from the original in any way,
+    /// including order and repeated sections or keys. Changes that were
+    /// undone do not count.
+    pub fn is_modified(&self) -> bool {
+        self.original != self.current
+    }
+
+    /// The sections and keys added, changed, or removed since tracking
+    /// started or the config was last saved, as by [`Conf::diff`].
+    ///
+    /// [`Conf::diff`]: ../struct.Conf.html#method.diff
+    pub fn changes(&self) -> Vec<Change> {
+        diff(&self.original, &self.current)
+    }
+
+    /// Make the current config the new original, after it was saved.
+    pub fn mark_saved(&mut self) {
+        self.original = self.current.clone();
+    }
+
+    /// Stop tracking and return the current config.
+    pub fn into_inner(self) -> Conf {
+        self.current
+    }
+}
+
+impl std::ops::Deref for TrackedConf {
+    type Target = Conf;
+
+    fn deref(&self) -> &Conf {
+        &self.current
+    }
+}
+
+impl std::ops::DerefMut for TrackedConf {
+    fn deref_mut(&mut self) -> &mut Conf {
+        &mut self.current
+    }
+}
+
+/// Sections keyed by name with duplicate sections merged in order, and
+/// entries keyed by key where the first occurrence wins.
+type Merged<'a> = Vec<(&'a str, Vec<(&'a str, &'a str)>)>;
+
+fn merged(conf: &Conf) -> Merged<'_> {
+    let mut merged: Merged = Vec::new();
+    for section in conf.sections.iter() {
+        let index = match merged.iter().position(|(name, _)| *name == section.name) {
+            Some(index) => index,
+            None => {
+                merged.push((&section.name, Vec::new()));
+                merged.len() - 1
+            }
+        };
+        let entries = &mut merged[index].1;
+        for entry in section.entries.iter() {
+            if !entries.iter().any(|(key, _)| *key == entry.key) {
+                entries.push((&entry.key, &entry.value));
+            }
+        }
+    }
+    merged
+}
+
+fn find<'a, T>(items: &'a [(&str, T)], name: &str) -> Option<&'a T> {
+    items.iter().find(|(n, _)| *n == name).map(|(_, item)| item)
+}
+
+pub(crate) fn diff(old: &Conf, new: &Conf) -> Vec<Change> {
+    let old = merged(old);
+    let new = merged(new);
+    let mut changes = Vec::new();
+
+    let added = |changes: &mut Vec<Change>, section: &str, key: &str, value: &str| {
+        changes.push(Change::Added {
+            section: section.to_string(),
+            key: key.to_string(),
+            value: value.to_string(),
+        })
+    };
+    let removed = |changes: &mut Vec<Change>, section: &str, key: &str, value: &str| {
+        changes.push(Change::Removed {
+            section: section.to_string(),
+            key: key.to_string(),
+            value: value.to_string(),
+        })
+    };
+
+    for (section, old_entries) in old.iter() {
+        let new_entries = match find(&new, section) {
+            Some(new_entries) => new_entries,
+            None => {
+                changes.push(Change::SectionRemoved {
+                    section: section.to_string(),
+                });
+                for (key, value) in old_entries {
+                    removed(&mut changes, section, key, value);
+                }
+                continue;
+            }
+        };
+        for (key, old_value) in old_entries {
+            match find(new_entries, key) {
+                Some(new_value) if new_value != old_value => changes.push(Change::Changed {
+                    section: section.to_string(),
+                    key: key.to_string(),
+                    old: old_value.to_string(),
+                    new: new_value.to_string(),
+                }),
+                Some(_) => {}
+                None => removed(&mut changes, section, key, old_value),
+            }
+        }
+        for (key, value) in new_entries {
+            if find(old_entries, key).is_none() {
+                added(&mut changes, section, key, value);
+            }
+        }
+    }
+
+    for (section, new_entries) in new.iter() {
+        if find(&old, section).is_none() {
+            changes.push(Change::SectionAdded {
+                section: section.to_string(),
+            });
+            for (key, value) in new_entries {
+                added(&mut changes, section, key, value);
+            }
+        }
+    }
+
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff() {
+        let old = Conf::parse_str("[a]\nx = 1\ny = 2\n[b]\nz = 3\n[a]\nw = 4").unwrap();
+        let new = Conf::parse_str("[a]\nw=4\ny = 5\nx= 1\nv = 6\n[c]\nu = 7").unwrap();
+        let changes: Vec<String> = old.diff(&new).iter().map(|c| c.to_string()).collect();
+        assert_eq!(
+            changes,
+            vec![
+                "~ [a] y = 2 -> 5",
+                "+ [a] v = 6",
+                "- [b]",
+                "- [b] z = 3",
+                "+ [c]",
+                "+ [c] u = 7",
+            ]
+        );
+
+        assert_eq!(old.diff(&old), vec![]);
+    }
+
+    #[test]
+    fn test_tracked_conf() {
+        let mut conf = TrackedConf::new(Conf::parse_str("[a]\nx = 1\n[b]\ny = 2").unwrap());
+        assert!(!conf.is_modified());
+        conf.sections[0].set("x", "3");
+        conf.sections[0].set("x", "1");
+        assert!(!conf.is_modified());
+
+        conf.sections[0].set("z", "4");
+        conf.remove_section("b");
+        assert!(conf.is_modified());
+        let changes: Vec<String> = conf.changes().iter().map(|c| c.to_string()).collect();
+        assert_eq!(changes, ["+ [a] z = 4", "- [b]", "- [b] y = 2"]);
+
+        conf.mark_saved();
+        assert_eq!(conf.changes(), vec![]);
+        assert_eq!(conf.original().to_string(), "[a]\nx = 1\nz = 4\n");
+        assert_eq!(conf.into_inner().len(), 1);
+    }
+}
@@ -0,0 +1,306 @@
+//! Documentation written as comments in a config file.
+//!
+//! Consecutive `#` comment lines directly above an entry document that
+//! entry. Lines starting with `;` are treated as commented-out settings
+//! rather than documentation.
+//!
+//! Example:
+//!
+//! ```
+//! use nbconf::docs::Docs;
+//!
+//! let input = "[server]\n# Port to listen on.\n# Use 0 to pick one.\nport = 80\n";
+//! let docs = Docs::parse(input).unwrap();
+//! assert_eq!(docs.get("server", "port"), Some("Port to listen on.\nUse 0 to pick one."));
+//! ```
+//!
+//! [`to_markdown`] writes a settings reference from a schema or from a
+//! commented config:
+//!
+//! ```
+//! use nbconf::docs::to_markdown;
+//!
+//! let markdown = to_markdown("[server]\n# Port to listen on.\nport = 80\n").unwrap();
+//! assert_eq!(
+//!     markdown,
+//!     "## server\n\n\
+//!      | Key | Type | Default | Description |\n\
+//!      | --- | --- | --- | --- |\n\
+//!      | `port` |  | `80` | Port to listen on. |\n"
+//! );
+//! ```
+//!
+//! [`to_markdown`]: fn.to_markdown.html
+
+use crate::schema::Schema;
+use crate::{split_header, Conf, ParseError};
+
+/// The documentation of one entry.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct EntryDoc {
+    /// Name of the section containing the entry.
+    pub section: String,
+    /// Key of the entry.
+    pub key: String,
+    /// Text of the comment lines without the `#` and the space after it,
+    /// joined with `\n`.
+    pub doc: String,
+}
+
+/// The documentation comments in a config file.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Docs {
+    /// Each documented entry, in order.
+    pub entries: Vec<EntryDoc>,
+}
+
+impl Docs {
+    /// Collect the documentation comments in `input`. Fails if `input`
+    /// cannot be parsed.
+    pub fn parse(input: &str) -> Result<Docs, ParseError> {
+        Conf::parse_str(input)?;
+
+        let mut docs = Docs::default();
+        let mut section = "";
+        let mut lines: Vec<&str> = Vec::new();
+        let input = input.strip_prefix('\u{feff}').unwrap_or(input);
+        for line in input.lines() {
+            let line = line.trim();
+            if let Some(text) = line.strip_prefix('#') {
+                lines.push(text.strip_prefix(' ').unwrap_or(text));
+                continue;
+            }
+            if let Some((name, _)) = split_header(line) {
+                section = name;
+            } else if !lines.is_empty() && !line.starts_with(';') {
+                // The input parsed, so this is an entry inside a section.
+                if let Some(equals) = line.find('=') {
+                    docs.entries.push(EntryDoc {
+                        section: section.to_string(),
+                        key: line[..equals].trim_end().to_string(),
+                        doc: lines.join("\n"),
+                    });
+                }
+            }
+            lines.clear();
+        }
+        Ok(docs)
+    }
+
+    /// The documentation of `key` in the first section named `section`
+    /// that documents it.
+    pub fn get(&self, section: &str, key: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|e| e.section == section && e.key == key)
+            .map(|e| e.doc.as_str())
+    }
+}
+
+/// What [`to_markdown`] documents.
+///
+/// [`to_markdown`]: fn.to_markdown.html
+#[derive(Clone, Copy, Debug)]
+pub enum Reference<'a> {
+    /// Every section and key in a schema, with their types, defaults and
+    /// descriptions.
+    Schema(&'a Schema),
+    /// The source text of a config, with its values as defaults and its
+    /// [`Docs`] as descriptions.
+    ///
+    /// [`Docs`]: struct.Docs.html
+    Commented(&'a str),
+}
+
+impl<'a> From<&'a Schema> for Reference<'a> {
+    fn from(schema: &'a Schema) -> Reference<'a> {
+        Reference::Schema(schema)
+    }
+}
+
+impl<'a> From<&'a str> for Reference<'a> {
+    fn from(input: &'a str) -> Reference<'a> {
+        Reference::Commented(input)
+    }
+}
+
+/// A row of a table in [`to_markdown`].
+///
+/// [`to_markdown`]: fn.to_markdown.html
+struct Row {
+    key: String,
+    value_type: String,
+    default: Option<String>,
+    description: String,
+}
+
+/// Escape `text` for a table cell.
+fn cell(text: &str) -> String {
+    text.replace('|', "\\|").replace('\n', "<br>")
+}
+
+/// Write a Markdown reference page for `reference`, with a heading for
+/// each section and a table of its keys. Fails if `reference` is a config
+/// that cannot be parsed.
+pub fn to_markdown<'a, R: Into<Reference<'a>>>(reference: R) -> Result<String, ParseError> {
+    let mut sections: Vec<(String, Option<String>, Vec<Row>)> = Vec::new();
+    match reference.into() {
+        Reference::Schema(schema) => {
+            for section_schema in schema.sections.iter() {
+                let rows = section_schema
+                    .keys
+                    .iter()
+                    .map(|key_schema| {
+                        let value_type = key_schema.value_type.to_string();
+                        let value_type = value_type
+                            .strip_prefix("an ")
+                            .or_else(|| value_type.strip_prefix("a "))
+                            .unwrap_or(&value_type);
+                        let mut description = String::new();
+                        if key_schema.required {
+                            description += "Required.";
+                        }
+                        if let Some(text) = &key_schema.description {
+                            if !description.is_empty() {
+                                description.push(' ');
+                            }
+                            description += text;
+                        }
+                        Row {
+                            key: key_schema.name.clone(),
+                            value_type: value_type.to_string(),
+                            default: key_schema.default.clone(),
+                            description,
+                        }
+                    })
+                    .collect();
+                sections.push((
+                    section_schema.name.clone(),
+                    section_schema.description.clone(),
+                    rows,
+                ));
+            }
+        }
+        Reference::Commented(input) => {
+            let conf = Conf::parse_str(input)?;
+            let docs = Docs::parse(input)?;
+            for section in conf.iter() {
+                let index = match sections
+                    .iter()
+                    .position(|(name, _, _)| *name == section.name)
+                {
+                    Some(index) => index,
+                    None => {
+                        sections.push((section.name.clone(), None, Vec::new()));
+                        sections.len() - 1
+                    }
+                };
+                let rows = &mut sections[index].2;
+                for entry in section.iter() {
+                    if rows.iter().any(|row| row.key == entry.key) {
+                        continue;
+                    }
+                    rows.push(Row {
+                        key: entry.key.clone(),
+                        value_type: String::new(),
+                        default: Some(entry.value.clone()),
+                        description: docs
+                            .get(&section.name, &entry.key)
+                            .unwrap_or_default()
+                            .to_string(),
+                    });
+                }
+            }
+        }
+    }
+
+    let mut output = String::new();
+    for (index, (name, description, rows)) in sections.iter().enumerate() {
+        if index != 0 {
+            output.push('\n');
+        }
+        output += &format!("## {}\n", name);
+        if let Some(description) = description {
+            output += &format!("\n{}\n", description);
+        }
+        if rows.is_empty() {
+            continue;
+        }
+        output += "\n| Key | Type | Default | Description |\n| --- | --- | --- | --- |\n";
+        for row in rows.iter() {
+            let default = match &row.default {
+                Some(default) if !default.is_empty() => format!("`{}`", cell(default)),
+                _ => String::new(),
+            };
+            output += &format!(
+                "| `{}` | {} | {} | {} |\n",
+                cell(&row.key),
+                cell(&row.value_type),
+                default,
+                cell(&row.description)
+            );
+        }
+    }
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_docs() {
+        let input = "# About a.\n[a]\n# One\n#\n#  two\nx = 1\n# Not for y.\n\ny = 2\n\
+                     # Not for z.\n; w = 0\nz = 3\n#Three\nx = 4\n";
+        let docs = Docs::parse(input).unwrap();
+        assert_eq!(docs.get("a", "x"), Some("One\n\n two"));
+        assert_eq!(docs.get("a", "y"), None);
+        assert_eq!(docs.get("a", "z"), None);
+        assert_eq!(docs.entries.len(), 2);
+        assert_eq!(docs.entries[1].doc, "Three");
+        assert!(Docs::parse("x = 1").is_err());
+    }
+
+    #[test]
+    fn test_to_markdown() {
+        use crate::schema::{KeySchema, SectionSchema, ValueType};
+
+        let schema = Schema::new(vec![
+            SectionSchema::new(
+                "server",
+                vec![
+                    KeySchema::new("port", ValueType::Int)
+                        .required()
+                        .description("Port | number."),
+                    KeySchema::new("host", ValueType::String).default("::"),
+                ],
+            )
+            .description("The HTTP server."),
+            SectionSchema::new("empty", vec![]),
+        ]);
+        assert_eq!(
+            to_markdown(&schema).unwrap(),
+            "## server\n\nThe HTTP server.\n\n\
+             | Key | Type | Default | Description |\n\
+             | --- | --- | --- | --- |\n\
+             | `port` | integer |  | Required. Port \\| number. |\n\
+             | `host` | string | `::` |  |\n\
+             \n## empty\n"
+        );
+
+        let input = "[a]\n# One\n# line.\nx = 1\n[b]\ny =\n[a]\nx = 2\nz = 3\n";
+        assert_eq!(
+            to_markdown(input).unwrap(),
+            "## a\n\n\
+             | Key | Type | Default | Description |\n\
+             | --- | --- | --- | --- |\n\
+             | `x` |  | `1` | One<br>line. |\n\
+             | `z` |  | `3` |  |\n\
+             \n## b\n\n\
+             | Key | Type | Default | Description |\n\
+             | --- | --- | --- | --- |\n\
+             | `y` |  |  |  |\n"
+        );
+        assert!(to_markdown("x = 1").is_err());
+    }
+}
@@ -0,0 +1,258 @@
+//! Encrypted values.
+//!
+//! An encrypted value is written as `!enc[...]`, with the ciphertext in
+//! base64. The encryption itself is up to the application, through the
+//! [`Cipher`] trait.
+//!
+//! Example:
+//!
+//! ```
+//! use nbconf::encrypt::Cipher;
+//! use nbconf::Conf;
+//!
+//! // Not actually secure.
+//! struct Rot13;
+//!
+//! impl Cipher for Rot13 {
+//!     fn encrypt(&self, plaintext: &[u8]) -> Vec<u8> {
+//!         plaintext.iter().map(|b| b.wrapping_add(13)).collect()
+//!     }
+//!
+//!     fn decrypt(&self, ciphertext: &[u8]) -> Option<Vec<u8>> {
+//!         Some(ciphertext.iter().map(|b| b.wrapping_sub(13)).collect())
+//!     }
+//! }
+//!
+//! let mut conf = Conf::parse_str("[db]\npassword = hunter2\n").unwrap();
+//! conf.encrypt_key("db", "password", &Rot13);
+//! assert_eq!(conf.to_string(), "[db]\npassword = !enc[dYJ7gXJ/Pw==]\n");
+//!
+//! let plain = conf.decrypted(&Rot13).unwrap();
+//! assert_eq!(plain.get_path("db.password"), Some("hunter2"));
+//! ```
+//!
+//! [`Cipher`]: trait.Cipher.html
+
+use crate::Conf;
+
+const PREFIX: &str = "!enc[";
+const SUFFIX: &str = "]";
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Encryption used for `!enc[...]` values.
+pub trait Cipher {
+    /// Encrypt `plaintext`.
+    fn encrypt(&self, plaintext: &[u8]) -> Vec<u8>;
+
+    /// Decrypt `ciphertext`, or return `None` if it cannot be decrypted,
+    /// for example because it was encrypted with a different key.
+    fn decrypt(&self, ciphertext: &[u8]) -> Option<Vec<u8>>;
+}
+
+/// The specific type of decrypt error.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum DecryptErrorKind {
+    /// The text between `!enc[` and `]` is not valid base64.
+    InvalidBase64,
+    /// [`Cipher::decrypt`] failed.
+    ///
+    /// [`Cipher::decrypt`]: trait.Cipher.html#tymethod.decrypt
+    CipherFailed,
+    /// The decrypted value is not valid UTF-8.
+    InvalidUtf8,
+}
+
+/// Error produced from [`Conf::decrypted`].
+///
+/// [`Conf::decrypted`]: ../struct.Conf.html#method.decrypted
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DecryptError {
+    /// Name of the section containing the value.
+    pub section: String,
+    /// Key of the value.
+    pub key: String,
+    /// Type of error.
+    pub kind: DecryptErrorKind,
+}
+
+impl std::fmt::Display for DecryptError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}.{}: ", self.section, self.key)?;
+        match self.kind {
+            DecryptErrorKind::InvalidBase64 => write!(f, "invalid base64 in encrypted value"),
+            DecryptErrorKind::CipherFailed => write!(f, "failed to decrypt value"),
+            DecryptErrorKind::InvalidUtf8 => write!(f, "decrypted value is not valid UTF-8"),
+        }
+    }
+}
+
+impl std::error::Error for DecryptError {}
+
+/// Whether `value` is written as `!enc[...]`.
+pub fn is_encrypted(value: &str) -> bool {
+    value.starts_with(PREFIX) && value.ends_with(SUFFIX)
+}
+
+fn base64_encode(bytes: &[u8]) -> String {
+    let mut output = String::new();
+    for chunk in bytes.chunks(3) {
+        let b = [
+            chunk[0],
+            chunk.get(1).copied().unwrap_or(0),
+            chunk.get(2).copied().unwrap_or(0),
+        ];
+        let n = (u32::from(b[0]) << 16) | (u32::from(b[1]) << 8) | u32::from(b[2]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                output.push(ALPHABET[(n >> (18 - 6 * i)) as usize & 63] as char);
+            } else {
+                output.push('=');
+            }
+        }
+    }
+    output
+}
+
+fn base64_decode(text: &str) -> Option<Vec<u8>> {
+    let text = text.as_bytes();
+    if !text.len().is_multiple_of(4) {
+        return None;
+    }
+    let mut output = Vec::new();
+    for (index, chunk) in text.chunks(4).enumerate() {
+        let last = index == text.len() / 4 - 1;
+        let padding = chunk.iter().rev().take_while(|b| **b == b'=').count();
+        if padding > 2 || (padding > 0 && !last) {
+            return None;
+        }
+        let mut n = 0u32;
+        for b in chunk[..4 - padding].iter() {
+            let digit = ALPHABET.iter().position(|a| a == b)?;
+            n = (n << 6) | digit as u32;
+        }
+        n <<= 6 * padding as u32;
+        let bytes = [(n >> 16) as u8, (n >> 8) as u8, n as u8];
+        output.extend_from_slice(&bytes[..3 - padding]);
+    }
+    Some(output)
+}
+
+impl Conf {
+    /// Encrypt the value of `key` in the first section named `section`
+    /// that has it, writing it as `!enc[...]`.
+    ///
+    /// Returns false if there is no such entry. A value that is already
+    /// encrypted is left as it is.
+    pub fn encrypt_key<C: Cipher>(&mut self, section: &str, key: &str, cipher: &C) -> bool {
+        let entry = self
+            .iter_mut()
+            .filter(|s| s.name == section)
+            .flat_map(|s| s.iter_mut())
+            .find(|e| e.key == key);
+        match entry {
+            Some(entry) => {
+                if !is_encrypted(&entry.value) {
+                    let ciphertext = cipher.encrypt(entry.value.as_bytes());
+                    entry.value = format!("{}{}{}", PREFIX, base64_encode(&ciphertext), SUFFIX);
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Copy of the config with every `!enc[...]` value decrypted.
+    ///
+    /// The config itself is left encrypted, so that it can be saved
+    /// without writing the plain values.
+    pub fn decrypted<C: Cipher>(&self, cipher: &C) -> Result<Conf, DecryptError> {
+        let mut conf = self.clone();
+        for section in conf.iter_mut() {
+            let name = section.name.clone();
+            for entry in section.entries.iter_mut() {
+                if !is_encrypted(&entry.value) {
+                    continue;
+                }
+                let key = entry.key.clone();
+                let error = |kind| DecryptError {
+                    section: name.clone(),
+                    key: key.clone(),
+                    kind,
+                };
+                let encoded = &entry.value[PREFIX.len()..entry.value.len() - SUFFIX.len()];
+                let ciphertext =
+                    base64_decode(encoded).ok_or_else(|| error(DecryptErrorKind::InvalidBase64))?;
+                let plaintext = cipher
+                    .decrypt(&ciphertext)
+                    .ok_or_else(|| error(DecryptErrorKind::CipherFailed))?;
+                entry.value = String::from_utf8(plaintext)
+                    .map_err(|_| error(DecryptErrorKind::InvalidUtf8))?;
+            }
+        }
+        Ok(conf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// XOR with a one-byte key. Decryption fails for a zero-length
+    /// ciphertext, to test error handling.
+    struct Xor(u8);
+
+    impl Cipher for Xor {
+        fn encrypt(&self, plaintext: &[u8]) -> Vec<u8> {
+            plaintext.iter().map(|b| b ^ self.0).collect()
+        }
+
+        fn decrypt(&self, ciphertext: &[u8]) -> Option<Vec<u8>> {
+            if ciphertext.is_empty() {
+                return None;
+            }
+            Some(ciphertext.iter().map(|b| b ^ self.0).collect())
+        }
+    }
+
+    #[test]
+    fn test_base64() {
+        for (plain, encoded) in [
+            ("", ""),
+            ("f", "Zg=="),
+            ("fo", "Zm8="),
+            ("foo", "Zm9v"),
+            ("foob", "Zm9vYg=="),
+        ] {
+            assert_eq!(base64_encode(plain.as_bytes()), encoded);
+            assert_eq!(base64_decode(encoded), Some(plain.as_bytes().to_vec()));
+        }
+        assert_eq!(base64_decode("Zg="), None);
+        assert_eq!(base64_decode("Zg==Zm8="), None);
+        assert_eq!(base64_decode("Z!=="), None);
+    }
+
+    #[test]
+    fn test_encrypt() {
+        let mut conf = Conf::parse_str("[db]\nuser = app\npassword = hunter2\n").unwrap();
+        assert!(conf.encrypt_key("db", "password", &Xor(1)));
+        assert!(!conf.encrypt_key("db", "missing", &Xor(1)));
+        let encrypted = conf.get_path("db.password").unwrap().to_string();
+        assert!(is_encrypted(&encrypted));
+        assert!(conf.encrypt_key("db", "password", &Xor(1)));
+        assert_eq!(conf.get_path("db.password"), Some(encrypted.as_str()));
+
+        let plain = conf.decrypted(&Xor(1)).unwrap();
+        assert_eq!(plain.get_path("db.password"), Some("hunter2"));
+        assert_eq!(plain.get_path("db.user"), Some("app"));
+
+        let conf = Conf::parse_str("[db]\na = !enc[]\nb = !enc[???]\n").unwrap();
+        assert_eq!(
+            conf.decrypted(&Xor(1)),
+            Err(DecryptError {
+                section: "db".to_string(),
+                key: "a".to_string(),
+                kind: DecryptErrorKind::CipherFailed,
+            })
+        );
+    }
+}
@@ -0,0 +1,240 @@
+//! Exchanging config entries with environment variables.
+//!
+//! Example:
+//!
+//! ```
+//! use nbconf::env::ShellStyle;
+//! use nbconf::Conf;
+//!
+//! let mut conf = Conf::parse_str("[server]\nport = 8080\n").unwrap();
+//! assert_eq!(
+//!     conf.to_env_script("myapp", ShellStyle::Posix),
+//!     "export MYAPP_SERVER_PORT=\"8080\"\n"
+//! );
+//!
+//! let vars = vec![("MYAPP_SERVER__PORT".to_string(), "9090".to_string())];
+//! conf.apply_overrides("myapp", vars);
+//! assert_eq!(conf.get_path("server.port"), Some("9090"));
+//! ```
+
+use crate::{Conf, Entry, Section};
+
+/// Shell syntax used by [`Conf::to_env_script`].
+///
+/// [`Conf::to_env_script`]: ../struct.Conf.html#method.to_env_script
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum ShellStyle {
+    /// `export NAME="value"`, for `sh` and compatible shells.
+    #[default]
+    Posix,
+    /// `$env:NAME = 'value'`, for PowerShell.
+    PowerShell,
+}
+
+/// An entry set by [`Conf::apply_env_overrides`].
+///
+/// [`Conf::apply_env_overrides`]: ../struct.Conf.html#method.apply_env_overrides
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct EnvOverride {
+    /// Name of the environment variable.
+    pub variable: String,
+    /// Name of the section the entry is in.
+    pub section: String,
+    /// Key of the entry.
+    pub key: String,
+    /// Value the entry had before, or `None` if it was added.
+    pub previous: Option<String>,
+}
+
+/// Environment variable name for `parts`: each part upper-cased, with
+/// characters other than ASCII letters and digits replaced by `_`, joined
+/// with `separator`. Empty parts are skipped.
+fn var_name(parts: &[&str], separator: &str) -> String {
+    parts
+        .iter()
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            part.chars()
+                .map(|c| {
+                    if c.is_ascii_alphanumeric() {
+                        c.to_ascii_uppercase()
+                    } else {
+                        '_'
+                    }
+                })
+                .collect::<String>()
+        })
+        .collect::<Vec<_>>()
+        .join(separator)
+}
+
+impl Conf {
+    /// Write every entry as an environment variable assignment, one per
+    /// line, that a shell can source.
+    ///
+    /// The variable for `key` in section `section` is named
+    /// `PREFIX_SECTION_KEY`, upper-cased, with characters other than ASCII
+    /// letters and digits replaced by `_`. Values are quoted so that they
+    /// are taken literally.
+    pub fn to_env_script(&self, prefix: &str, style: ShellStyle) -> String {
+        let mut output = String::new();
+        for section in self.iter() {
+            for entry in section.iter() {
+                let name = var_name(&[prefix, &section.name, &entry.key], "_");
+                let line = match style {
+                    ShellStyle::Posix => {
+                        let mut value = String::new();
+                        for c in entry.value.chars() {
+                            if matches!(c, '\\' | '"' | '$' | '`') {
+                                value.push('\\');
+                            }
+                            value.push(c);
+                        }
+                        format!("export {}=\"{}\"\n", name, value)
+                    }
+                    ShellStyle::PowerShell => {
+                        format!("$env:{} = '{}'\n", name, entry.value.replace('\'', "''"))
+                    }
+                };
+                output += &line;
+            }
+        }
+        output
+    }
+
+    /// Set entries from environment variables named
+    /// `PREFIX_SECTION__KEY`, returning what was set in order of variable
+    /// name.
+    ///
+    /// Section and key names are matched against existing ones the same
+    /// way [`to_env_script`] names variables, so `MYAPP_WEB_SERVER__HOST_NAME`
+    /// overrides `host-name` in section `web.server`. The first matching
+    /// entry is updated. Entries and sections that don't exist are added,
+    /// with lower-cased names.
+    ///
+    /// [`to_env_script`]: #method.to_env_script
+    pub fn apply_env_overrides(&mut self, prefix: &str) -> Vec<EnvOverride> {
+        let vars = std::env::vars_os().filter_map(|(name, value)| {
+            Some((name.into_string().ok()?, value.into_string().ok()?))
+        });
+        self.apply_overrides(prefix, vars)
+    }
+
+    /// Set entries from `vars` as [`apply_env_overrides`] does from the
+    /// environment.
+    ///
+    /// [`apply_env_overrides`]: #method.apply_env_overrides
+    pub fn apply_overrides<I>(&mut self, prefix: &str, vars: I) -> Vec<EnvOverride>
+    where
+        I: IntoIterator<Item = (String, String)>,
+    {
+        let prefix = var_name(&[prefix], "_") + "_";
+        let mut vars: Vec<_> = vars
+            .into_iter()
+            .filter(|(name, _)| name.starts_with(&prefix))
+            .collect();
+        vars.sort();
+
+        let mut overrides = Vec::new();
+        for (variable, value) in vars {
+            let (section_part, key_part) = match variable[prefix.len()..].split_once("__") {
+                Some((section, key)) if !section.is_empty() && !key.is_empty() => (section, key),
+                _ => continue,
+            };
+            let index = match self
+                .sections
+                .iter()
+                .position(|s| var_name(&[&s.name], "_") == section_part)
+            {
+                Some(index) => index,
+                None => {
+                    self.sections
+                        .push(Section::new(&section_part.to_ascii_lowercase()));
+                    self.sections.len() - 1
+                }
+            };
+            let section = &mut self.sections[index];
+            let (key, previous) = match section
+                .iter_mut()
+                .find(|e| var_name(&[&e.key], "_") == key_part)
+            {
+                Some(entry) => (
+                    entry.key.clone(),
+                    Some(std::mem::replace(&mut entry.value, value)),
+                ),
+                None => {
+                    let key = key_part.to_ascii_lowercase();
+                    section.entries.push(Entry::new(&key, &value));
+                    (key, None)
+                }
+            };
+            overrides.push(EnvOverride {
+                variable,
+                section: section.name.clone(),
+                key,
+                previous,
+            });
+        }
+        overrides
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_env_script() {
+        let conf =
+            Conf::parse_str("[web.server]\nhost-name = a\"$b`c\\\n[db]\npass = it's\n").unwrap();
+        assert_eq!(
+            conf.to_env_script("my app", ShellStyle::Posix),
+            "export MY_APP_WEB_SERVER_HOST_NAME=\"a\\\"\\$b\\`c\\\\\"\nexport MY_APP_DB_PASS=\"it's\"\n"
+        );
+        assert_eq!(
+            conf.to_env_script("", ShellStyle::PowerShell),
+            "$env:WEB_SERVER_HOST_NAME = 'a\"$b`c\\'\n$env:DB_PASS = 'it''s'\n"
+        );
+    }
+
+    #[test]
+    fn test_apply_overrides() {
+        let mut conf = Conf::parse_str("[web.server]\nhost-name = a\n").unwrap();
+        let vars = vec![
+            ("MYAPP_WEB_SERVER__HOST_NAME", "b"),
+            ("MYAPP_DB__PORT", "5432"),
+            ("MYAPP_WEB_SERVER__HOST", "c"),
+            ("MYAPP_NOKEY", "d"),
+            ("OTHER_DB__PORT", "e"),
+        ];
+        let overrides = conf.apply_overrides(
+            "myapp",
+            vars.into_iter()
+                .map(|(n, v)| (n.to_string(), v.to_string())),
+        );
+        let report =
+            |variable: &str, section: &str, key: &str, previous: Option<&str>| EnvOverride {
+                variable: variable.to_string(),
+                section: section.to_string(),
+                key: key.to_string(),
+                previous: previous.map(str::to_string),
+            };
+        assert_eq!(
+            overrides,
+            vec![
+                report("MYAPP_DB__PORT", "db", "port", None),
+                report("MYAPP_WEB_SERVER__HOST", "web.server", "host", None),
+                report(
+                    "MYAPP_WEB_SERVER__HOST_NAME",
+                    "web.server",
+                    "host-name",
+                    Some("a")
+                ),
+            ]
+        );
+        assert_eq!(
+            conf.to_string(),
+            "[web.server]\nhost-name = b\nhost = c\n\n[db]\nport = 5432\n"
+        );
+    }
+}
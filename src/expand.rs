@@ -0,0 +1,133 @@
+//! Filling in `${...}` placeholders in values.
+//!
+//! Example:
+//!
+//! ```
+//! use nbconf::Conf;
+//!
+//! let mut conf = Conf::parse_str("[db]\nurl = postgres://${host}:${port}/app\n").unwrap();
+//! let unresolved = conf.expand_with(|name| match name {
+//!     "host" => Some("db.internal".to_string()),
+//!     _ => None,
+//! });
+//! assert_eq!(conf.get_path("db.url"), Some("postgres://db.internal:${port}/app"));
+//! assert_eq!(unresolved[0].to_string(), "db.url: unresolved placeholder ${port}");
+//! ```
+
+use crate::Conf;
+
+/// A placeholder that [`Conf::expand_with`] could not fill in.
+///
+/// [`Conf::expand_with`]: ../struct.Conf.html#method.expand_with
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Unresolved {
+    /// Name of the section containing the value.
+    pub section: String,
+    /// Key of the value.
+    pub key: String,
+    /// Text between `${` and `}`.
+    pub placeholder: String,
+}
+
+impl std::fmt::Display for Unresolved {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{}.{}: unresolved placeholder ${{{}}}",
+            self.section, self.key, self.placeholder
+        )
+    }
+}
+
+/// Replace each `${...}` in `value` with the result of `resolve`, calling
+/// `unresolved` for each placeholder it returns `None` for. Those
+/// placeholders, and a `${` without a closing brace, are left as they are.
+fn expand<F, U>(value: &str, resolve: &mut F, mut unresolved: U) -> String
+where
+    F: FnMut(&str) -> Option<String>,
+    U: FnMut(&str),
+{
+    let mut output = String::new();
+    let mut rest = value;
+    while let Some(start) = rest.find("${") {
+        output += &rest[..start];
+        let after = &rest[start + 2..];
+        let end = match after.find('}') {
+            Some(end) => end,
+            None => {
+                output += &rest[start..];
+                return output;
+            }
+        };
+        let placeholder = &after[..end];
+        match resolve(placeholder) {
+            Some(replacement) => output += &replacement,
+            None => {
+                unresolved(placeholder);
+                output += &rest[start..start + end + 3];
+            }
+        }
+        rest = &after[end + 1..];
+    }
+    output += rest;
+    output
+}
+
+impl Conf {
+    /// Replace each `${...}` placeholder in every value with the result of
+    /// calling `resolve` with the text between the braces.
+    ///
+    /// Placeholders that `resolve` returns `None` for are left as they are
+    /// and returned, in order. Replacements are not expanded again.
+    pub fn expand_with<F>(&mut self, mut resolve: F) -> Vec<Unresolved>
+    where
+        F: FnMut(&str) -> Option<String>,
+    {
+        let mut unresolved = Vec::new();
+        for section in self.sections.iter_mut() {
+            let name = &section.name;
+            for entry in section.entries.iter_mut() {
+                if !entry.value.contains("${") {
+                    continue;
+                }
+                entry.value = expand(&entry.value, &mut resolve, |placeholder| {
+                    unresolved.push(Unresolved {
+                        section: name.clone(),
+                        key: entry.key.clone(),
+                        placeholder: placeholder.to_string(),
+                    })
+                });
+            }
+        }
+        unresolved
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expand_with() {
+        let mut conf =
+            Conf::parse_str("[a]\nx = ${one}-${two}-${one}\ny = ${}${three\nz = ${one}\n").unwrap();
+        let mut calls = 0;
+        let unresolved = conf.expand_with(|name| {
+            calls += 1;
+            match name {
+                "one" => Some("${two}".to_string()),
+                _ => None,
+            }
+        });
+        assert_eq!(calls, 5);
+        assert_eq!(
+            conf.to_string(),
+            "[a]\nx = ${two}-${two}-${two}\ny = ${}${three\nz = ${two}\n"
+        );
+        let placeholders: Vec<(&str, &str)> = unresolved
+            .iter()
+            .map(|u| (u.key.as_str(), u.placeholder.as_str()))
+            .collect();
+        assert_eq!(placeholders, vec![("x", "two"), ("y", "")]);
+    }
+}
@@ -0,0 +1,264 @@
+//! C bindings.
+//!
+//! This module requires the `ffi` feature. The functions are declared in
+//! `include/nbconf.h`. To build a library that C and C++ code can link
+//! against:
+//!
+//! ```text
+//! cargo rustc --release --features ffi --crate-type cdylib
+//! ```
+//!
+//! Strings passed in must be NUL-terminated UTF-8. Strings returned are
+//! owned by the caller and must be freed with [`nbconf_string_free`].
+//!
+//! [`nbconf_string_free`]: fn.nbconf_string_free.html
+
+use crate::{check_entry, check_section_name, Conf, Section};
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_int};
+use std::ptr;
+
+/// Opaque handle to a [`Conf`].
+///
+/// [`Conf`]: ../struct.Conf.html
+#[allow(non_camel_case_types)]
+pub struct nbconf_conf_t {
+    conf: Conf,
+}
+
+/// Borrow `s` as a `&str`, or `None` if it is null or not UTF-8.
+unsafe fn to_str<'a>(s: *const c_char) -> Option<&'a str> {
+    if s.is_null() {
+        return None;
+    }
+    CStr::from_ptr(s).to_str().ok()
+}
+
+/// Copy `s` into a string owned by the caller. Returns null if `s`
+/// contains a NUL.
+fn to_c_string(s: &str) -> *mut c_char {
+    CString::new(s)
+        .map(CString::into_raw)
+        .unwrap_or(ptr::null_mut())
+}
+
+/// Create an empty config. Free it with [`nbconf_conf_free`].
+///
+/// [`nbconf_conf_free`]: fn.nbconf_conf_free.html
+#[no_mangle]
+pub extern "C" fn nbconf_conf_new() -> *mut nbconf_conf_t {
+    Box::into_raw(Box::new(nbconf_conf_t { conf: Conf::new() }))
+}
+
+/// Parse `text` into a config. Free it with [`nbconf_conf_free`].
+///
+/// Returns null on failure. If `error` is not null, it is then set to a
+/// message describing the problem, which must be freed with
+/// [`nbconf_string_free`].
+///
+/// # Safety
+///
+/// `text` must be null or a NUL-terminated string. `error` must be null
+/// or valid for writes.
+///
+/// [`nbconf_conf_free`]: fn.nbconf_conf_free.html
+/// [`nbconf_string_free`]: fn.nbconf_string_free.html
+#[no_mangle]
+pub unsafe extern "C" fn nbconf_conf_parse(
+    text: *const c_char,
+    error: *mut *mut c_char,
+) -> *mut nbconf_conf_t {
+    let result = match to_str(text) {
+        Some(text) => Conf::parse_str(text).map_err(|err| err.to_string()),
+        None => Err("input is null or not valid UTF-8".to_string()),
+    };
+    match result {
+        Ok(conf) => Box::into_raw(Box::new(nbconf_conf_t { conf })),
+        Err(message) => {
+            if !error.is_null() {
+                *error = to_c_string(&message);
+            }
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Get the value of `key` in the first section named `section`.
+///
+/// Returns null if there is no such value. Otherwise the returned string
+/// must be freed with [`nbconf_string_free`].
+///
+/// # Safety
+///
+/// `conf` must be null or a config from this module that has not been
+/// freed. `section` and `key` must be null or NUL-terminated strings.
+///
+/// [`nbconf_string_free`]: fn.nbconf_string_free.html
+#[no_mangle]
+pub unsafe extern "C" fn nbconf_conf_get(
+    conf: *const nbconf_conf_t,
+    section: *const c_char,
+    key: *const c_char,
+) -> *mut c_char {
+    match (conf.as_ref(), to_str(section), to_str(key)) {
+        (Some(conf), Some(section), Some(key)) => conf
+            .conf
+            .section(section)
+            .and_then(|s| s.get(key))
+            .map_or(ptr::null_mut(), to_c_string),
+        _ => ptr::null_mut(),
+    }
+}
+
+/// Set `key` to `value` in the first section named `section`, adding the
+/// section at the end if there is none.
+///
+/// Returns 0 on success, or -1, leaving the config unchanged, if an
+/// argument is null or not UTF-8, or if the section name, key or value
+/// would not read back as given after being serialized.
+///
+/// # Safety
+///
+/// `conf` must be null or a config from this module that has not been
+/// freed. `section`, `key` and `value` must be null or NUL-terminated
+/// strings.
+#[no_mangle]
+pub unsafe extern "C" fn nbconf_conf_set(
+    conf: *mut nbconf_conf_t,
+    section: *const c_char,
+    key: *const c_char,
+    value: *const c_char,
+) -> c_int {
+    let (conf, section, key, value) =
+        match (conf.as_mut(), to_str(section), to_str(key), to_str(value)) {
+            (Some(conf), Some(section), Some(key), Some(value)) => {
+                (&mut conf.conf, section, key, value)
+            }
+            _ => return -1,
+        };
+    if check_section_name(section).is_err() || check_entry(section, key, value).is_err() {
+        return -1;
+    }
+    match conf.iter_mut().find(|s| s.name == section) {
+        Some(section) => section.set(key, value),
+        None => {
+            let mut new_section = Section::new(section);
+            new_section.set(key, value);
+            conf.sections.push(new_section);
+        }
+    }
+    0
+}
+
+/// Serialize the config as a string, which must be freed with
+/// [`nbconf_string_free`]. Returns null if `conf` is null.
+///
+/// # Safety
+///
+/// `conf` must be null or a config from this module that has not been
+/// freed.
+///
+/// [`nbconf_string_free`]: fn.nbconf_string_free.html
+#[no_mangle]
+pub unsafe extern "C" fn nbconf_conf_serialize(conf: *const nbconf_conf_t) -> *mut c_char {
+    match conf.as_ref() {
+        Some(conf) => to_c_string(&conf.conf.to_string()),
+        None => ptr::null_mut(),
+    }
+}
+
+/// Free a config. Does nothing if `conf` is null.
+///
+/// # Safety
+///
+/// `conf` must be null or a config from this module that has not been
+/// freed.
+#[no_mangle]
+pub unsafe extern "C" fn nbconf_conf_free(conf: *mut nbconf_conf_t) {
+    if !conf.is_null() {
+        drop(Box::from_raw(conf));
+    }
+}
+
+/// Free a string returned by this module. Does nothing if `s` is null.
+///
+/// # Safety
+///
+/// `s` must be null or a string returned by this module that has not
+/// been freed.
+#[no_mangle]
+pub unsafe extern "C" fn nbconf_string_free(s: *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Take ownership of a string returned by this module.
+    unsafe fn take(s: *mut c_char) -> Option<String> {
+        if s.is_null() {
+            return None;
+        }
+        let value = CStr::from_ptr(s).to_str().unwrap().to_string();
+        nbconf_string_free(s);
+        Some(value)
+    }
+
+    #[test]
+    fn test_ffi() {
+        let c = |s: &str| CString::new(s).unwrap();
+        unsafe {
+            let mut error = ptr::null_mut();
+            let conf = nbconf_conf_parse(c("[a]\nx = 1\n").as_ptr(), &mut error);
+            assert!(error.is_null());
+            assert_eq!(
+                take(nbconf_conf_get(conf, c("a").as_ptr(), c("x").as_ptr())),
+                Some("1".to_string())
+            );
+            assert_eq!(
+                take(nbconf_conf_get(conf, c("a").as_ptr(), c("y").as_ptr())),
+                None
+            );
+            assert_eq!(
+                nbconf_conf_set(conf, c("a").as_ptr(), c("x").as_ptr(), c("2").as_ptr()),
+                0
+            );
+            assert_eq!(
+                nbconf_conf_set(conf, c("b").as_ptr(), c("y").as_ptr(), c("3").as_ptr()),
+                0
+            );
+            assert_eq!(
+                nbconf_conf_set(conf, ptr::null(), c("y").as_ptr(), c("3").as_ptr()),
+                -1
+            );
+            for (section, key, value) in
+                [("a\n[evil]", "k", "v"), ("a", "k=", "v"), ("a", "k", "v\n")].iter()
+            {
+                assert_eq!(
+                    nbconf_conf_set(
+                        conf,
+                        c(section).as_ptr(),
+                        c(key).as_ptr(),
+                        c(value).as_ptr()
+                    ),
+                    -1
+                );
+            }
+            assert_eq!(
+                take(nbconf_conf_serialize(conf)),
+                Some("[a]\nx = 2\n\n[b]\ny = 3\n".to_string())
+            );
+            nbconf_conf_free(conf);
+
+            let conf = nbconf_conf_parse(c("x = 1").as_ptr(), &mut error);
+            assert!(conf.is_null());
+            assert_eq!(
+                take(error),
+                Some("line 1: entry outside of any section".to_string())
+            );
+        }
+    }
+}
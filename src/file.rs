@@ -0,0 +1,333 @@
+//! Saving configs to files, and locking files between processes.
+//!
+//! Example of a locked read-modify-write cycle:
+//!
+//! ```no_run
+//! use nbconf::file::{Backup, SaveOptions};
+//! use nbconf::Conf;
+//!
+//! let options = SaveOptions {
+//!     backup: Backup::Rotate(3),
+//!     ..Default::default()
+//! };
+//! let (mut conf, mut lock) = Conf::load_locked("app.conf")?;
+//! conf.set_path("stats.runs", "1");
+//! lock.save(&conf, &options)?;
+//! # Ok::<(), nbconf::LoadError>(())
+//! ```
+
+use crate::{Conf, Decoding, LoadError, Section, WriteOptions};
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Backups made by a save before a file is overwritten.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Backup {
+    /// No backup.
+    #[default]
+    None,
+    /// Copy `file.conf` to `file.conf.bak`, replacing any earlier backup.
+    Single,
+    /// Keep the given number of backups, from `file.conf.bak.1` (the
+    /// newest) to `file.conf.bak.N` (the oldest).
+    Rotate(usize),
+}
+
+/// Options for saving a config to a file.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct SaveOptions {
+    /// Style of the written config.
+    pub style: WriteOptions,
+    /// Backups to make of the existing file.
+    pub backup: Backup,
+}
+
+/// `path` with `suffix` appended to its file name.
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+/// Back up the file at `path`, if it exists, as given by `backup`.
+fn back_up(path: &Path, backup: Backup) -> io::Result<()> {
+    if !path.exists() {
+        return Ok(());
+    }
+    let target = match backup {
+        Backup::None | Backup::Rotate(0) => return Ok(()),
+        Backup::Single => with_suffix(path, ".bak"),
+        Backup::Rotate(count) => {
+            for index in (1..count).rev() {
+                let older = with_suffix(path, &format!(".bak.{}", index));
+                if older.exists() {
+                    std::fs::rename(&older, with_suffix(path, &format!(".bak.{}", index + 1)))?;
+                }
+            }
+            with_suffix(path, ".bak.1")
+        }
+    };
+    std::fs::copy(path, target)?;
+    Ok(())
+}
+
+/// An exclusive advisory lock on a config file, returned by
+/// [`Conf::load_locked`]. Other processes that lock the file wait until
+/// this one is dropped.
+///
+/// The lock is advisory: it only keeps out processes that also lock the
+/// file, such as those using [`Conf::load_locked`] or
+/// [`Conf::save_locked`].
+///
+/// [`Conf::load_locked`]: ../struct.Conf.html#method.load_locked
+/// [`Conf::save_locked`]: ../struct.Conf.html#method.save_locked
+#[derive(Debug)]
+pub struct ConfLock {
+    file: File,
+    path: PathBuf,
+}
+
+impl ConfLock {
+    fn open(path: &Path, create: bool) -> io::Result<ConfLock> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(create)
+            .truncate(false)
+            .open(path)?;
+        file.lock()?;
+        Ok(ConfLock {
+            file,
+            path: path.to_path_buf(),
+        })
+    }
+
+    /// Replace the contents of the locked file with `conf`, keeping the
+    /// lock.
+    ///
+    /// The file is rewritten in place rather than replaced, since
+    /// replacing it would leave other processes waiting on a lock of the
+    /// old file.
+    pub fn save(&mut self, conf: &Conf, options: &SaveOptions) -> io::Result<()> {
+        let text = conf.try_to_string_styled(&options.style)?;
+        back_up(&self.path, options.backup)?;
+        self.file.set_len(0)?;
+        self.file.seek(SeekFrom::Start(0))?;
+        self.file.write_all(text.as_bytes())?;
+        self.file.sync_data()
+    }
+}
+
+impl Conf {
+    /// Write the config to the file at `path`, replacing its contents.
+    pub fn save<P: AsRef<Path>>(&self, path: P, options: &SaveOptions) -> io::Result<()> {
+        let text = self.try_to_string_styled(&options.style)?;
+        back_up(path.as_ref(), options.backup)?;
+        std::fs::write(path, text)
+    }
+
+    /// Lock the file at `path`, creating it if needed, and write the
+    /// config to it. The lock is released before returning.
+    pub fn save_locked<P: AsRef<Path>>(&self, path: P, options: &SaveOptions) -> io::Result<()> {
+        ConfLock::open(path.as_ref(), true)?.save(self, options)
+    }
+
+    /// Append `section` to the end of the file at `path`, creating it if
+    /// needed, without reading or rewriting the rest of the file.
+    ///
+    /// A blank line is added before the section when the file is not
+    /// empty. The file is locked as by [`load_locked`] while writing.
+    ///
+    /// [`load_locked`]: #method.load_locked
+    pub fn append_section_to_file<P: AsRef<Path>>(path: P, section: &Section) -> io::Result<()> {
+        section.check_serializable()?;
+        let mut lock = ConfLock::open(path.as_ref(), true)?;
+        let file = &mut lock.file;
+        let len = file.seek(SeekFrom::End(0))?;
+        let mut separator = "";
+        if len > 0 {
+            let mut last = [0];
+            file.seek(SeekFrom::End(-1))?;
+            file.read_exact(&mut last)?;
+            separator = if last[0] == b'\n' { "\n" } else { "\n\n" };
+        }
+        file.seek(SeekFrom::End(0))?;
+        file.write_all(format!("{}{}", separator, section).as_bytes())?;
+        file.sync_data()
+    }
+
+    /// Lock the file at `path` and read the config from it, as by
+    /// [`load`]. The lock is held until the returned [`ConfLock`] is
+    /// dropped, so that the config can be edited and saved with
+    /// [`ConfLock::save`] without another process writing in between.
+    ///
+    /// [`load`]: #method.load
+    /// [`ConfLock`]: file/struct.ConfLock.html
+    /// [`ConfLock::save`]: file/struct.ConfLock.html#method.save
+    pub fn load_locked<P: AsRef<Path>>(path: P) -> Result<(Conf, ConfLock), LoadError> {
+        let path = path.as_ref();
+        let load = || -> Result<(Conf, ConfLock), LoadError> {
+            let mut lock = ConfLock::open(path, false)?;
+            let mut bytes = Vec::new();
+            lock.file.read_to_end(&mut bytes)?;
+            let conf = Conf::parse_bytes(&bytes, Decoding::Strict)?;
+            Ok((conf, lock))
+        };
+        load().map_err(|err| err.with_path(path))
+    }
+
+    /// Read and parse each file in `paths` as by [`load`], spread over as
+    /// many threads as the machine can run in parallel. Returns the result
+    /// for each path, in the same order.
+    ///
+    /// [`load`]: #method.load
+    pub fn load_many<P: AsRef<Path> + Sync>(paths: &[P]) -> Vec<Result<Conf, LoadError>> {
+        let threads = std::thread::available_parallelism()
+            .map_or(1, |n| n.get())
+            .min(paths.len());
+        let next = AtomicUsize::new(0);
+        let mut results: Vec<(usize, Result<Conf, LoadError>)> = std::thread::scope(|scope| {
+            let workers: Vec<_> = (0..threads)
+                .map(|_| {
+                    scope.spawn(|| {
+                        let mut results = Vec::new();
+                        loop {
+                            let index = next.fetch_add(1, Ordering::Relaxed);
+                            match paths.get(index) {
+                                Some(path) => results.push((index, Conf::load(path))),
+                                None => return results,
+                            }
+                        }
+                    })
+                })
+                .collect();
+            workers
+                .into_iter()
+                .flat_map(|worker| worker.join().unwrap())
+                .collect()
+        });
+        results.sort_by_key(|(index, _)| *index);
+        results.into_iter().map(|(_, result)| result).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Barrier};
+
+    #[test]
+    fn test_locked() {
+        let path = std::env::temp_dir().join(format!("nbconf-lock-{}.conf", std::process::id()));
+        let options = SaveOptions::default();
+        Conf::parse_str("[stats]\nruns = 0")
+            .unwrap()
+            .save_locked(&path, &options)
+            .unwrap();
+
+        // Each thread increments the counter under the lock, so no
+        // increment is lost.
+        let barrier = Arc::new(Barrier::new(4));
+        let threads: Vec<_> = (0..4)
+            .map(|_| {
+                let path = path.clone();
+                let barrier = barrier.clone();
+                std::thread::spawn(move || {
+                    barrier.wait();
+                    for _ in 0..10 {
+                        let (mut conf, mut lock) = Conf::load_locked(&path).unwrap();
+                        let runs: u32 = conf["stats"]["runs"].parse().unwrap();
+                        conf.set_path("stats.runs", &(runs + 1).to_string());
+                        lock.save(&conf, &SaveOptions::default()).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        assert_eq!(
+            Conf::load(&path).unwrap().get_path("stats.runs"),
+            Some("40")
+        );
+
+        Conf::new().save(&path, &options).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "");
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_append_section_to_file() {
+        let path = std::env::temp_dir().join(format!("nbconf-append-{}.conf", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let device =
+            |name: &str| Section::new_with_entries("device", vec![crate::Entry::new("name", name)]);
+        Conf::append_section_to_file(&path, &device("a")).unwrap();
+        Conf::append_section_to_file(&path, &device("b")).unwrap();
+        std::fs::write(&path, std::fs::read_to_string(&path).unwrap() + "[x]").unwrap();
+        Conf::append_section_to_file(&path, &device("c")).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "[device]\nname = a\n\n[device]\nname = b\n[x]\n\n[device]\nname = c\n"
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_backup() {
+        let dir = std::env::temp_dir().join(format!("nbconf-backup-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("app.conf");
+        let read = |suffix: &str| std::fs::read_to_string(with_suffix(&path, suffix)).ok();
+        let save = |value: &str, backup| {
+            let conf = Conf::parse_str(&format!("[a]\nx = {}", value)).unwrap();
+            let options = SaveOptions {
+                backup,
+                ..Default::default()
+            };
+            conf.save(&path, &options).unwrap();
+        };
+
+        save("1", Backup::Single);
+        assert_eq!(read(".bak"), None);
+        save("2", Backup::Single);
+        assert_eq!(read(".bak").as_deref(), Some("[a]\nx = 1\n"));
+
+        for value in ["3", "4", "5"].iter() {
+            save(value, Backup::Rotate(2));
+        }
+        assert_eq!(read("").as_deref(), Some("[a]\nx = 5\n"));
+        assert_eq!(read(".bak.1").as_deref(), Some("[a]\nx = 4\n"));
+        assert_eq!(read(".bak.2").as_deref(), Some("[a]\nx = 3\n"));
+        assert_eq!(read(".bak.3"), None);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_load_many() {
+        let dir = std::env::temp_dir().join(format!("nbconf-many-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut paths: Vec<PathBuf> = (0..20)
+            .map(|i| {
+                let path = dir.join(format!("{}.conf", i));
+                std::fs::write(&path, format!("[a]\nx = {}\n", i)).unwrap();
+                path
+            })
+            .collect();
+        paths.push(dir.join("missing.conf"));
+
+        let results = Conf::load_many(&paths);
+        assert_eq!(results.len(), 21);
+        for (i, result) in results[..20].iter().enumerate() {
+            assert_eq!(
+                result.as_ref().unwrap().get_path("a.x"),
+                Some(&*i.to_string())
+            );
+        }
+        assert!(results[20].is_err());
+        assert!(Conf::load_many::<PathBuf>(&[]).is_empty());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
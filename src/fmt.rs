@@ -0,0 +1,270 @@
+//! Formatting and linting of config text.
+//!
+//! Example:
+//!
+//! ```
+//! use nbconf::fmt::{self, FmtOptions, LintRules};
+//!
+//! let input = "[server]\nport=80\nhost = example.com \n";
+//! assert_eq!(
+//!     fmt::format(input, &FmtOptions::default()).unwrap(),
+//!     "[server]\nport = 80\nhost = example.com\n"
+//! );
+//! assert_eq!(fmt::lint(input, &LintRules::default()).unwrap().len(), 2);
+//! ```
+
+use crate::write::{DocumentView, EntryView, SectionView};
+use crate::{is_comment, split_header, Conf, ParseError, WriteOptions};
+
+/// Options for [`format`].
+///
+/// [`format`]: fn.format.html
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct FmtOptions {
+    /// Style of the formatted output.
+    pub style: WriteOptions,
+}
+
+/// Rules checked by [`lint`].
+///
+/// [`lint`]: fn.lint.html
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct LintRules {
+    /// Report entries whose `=` is spaced differently from the first
+    /// entry in the input.
+    pub inconsistent_delimiters: bool,
+    /// Report lines ending in whitespace.
+    pub trailing_whitespace: bool,
+    /// Report keys that appear more than once in a section.
+    pub duplicate_keys: bool,
+    /// Report keys that are not in alphabetical order within a section.
+    pub unsorted_keys: bool,
+}
+
+impl Default for LintRules {
+    fn default() -> LintRules {
+        LintRules {
+            inconsistent_delimiters: true,
+            trailing_whitespace: true,
+            duplicate_keys: true,
+            unsorted_keys: false,
+        }
+    }
+}
+
+/// The specific type of lint finding.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum FindingKind {
+    /// The `=` of an entry is spaced differently than in earlier entries.
+    InconsistentDelimiter { expected: String, found: String },
+    /// The line ends in whitespace.
+    TrailingWhitespace,
+    /// The key already appeared earlier in the same section.
+    DuplicateKey { key: String },
+    /// The key sorts before the key on the previous entry.
+    UnsortedKey { key: String, previous: String },
+}
+
+/// A problem found by [`lint`].
+///
+/// [`lint`]: fn.lint.html
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Finding {
+    /// Line where the problem occurs (starting from 1).
+    pub line: usize,
+    /// Type of problem.
+    pub kind: FindingKind,
+}
+
+impl std::fmt::Display for Finding {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "line {}: ", self.line)?;
+        match &self.kind {
+            FindingKind::InconsistentDelimiter { expected, found } => {
+                write!(f, "expected delimiter {:?}, found {:?}", expected, found)
+            }
+            FindingKind::TrailingWhitespace => write!(f, "trailing whitespace"),
+            FindingKind::DuplicateKey { key } => write!(f, "duplicate key {:?}", key),
+            FindingKind::UnsortedKey { key, previous } => {
+                write!(f, "key {:?} should come before {:?}", key, previous)
+            }
+        }
+    }
+}
+
+/// Reformat `input` in the style given by `options`.
+///
+/// Comments are kept with the section header or entry that follows
+/// them. A comment block at the start of the input that is followed by a
+/// blank line stays set apart from the first section.
+pub fn format(input: &str, options: &FmtOptions) -> Result<String, ParseError> {
+    Conf::parse_str(input)?;
+
+    let mut document = DocumentView::default();
+    let mut comments = Vec::new();
+    let input = input.strip_prefix('\u{feff}').unwrap_or(input);
+    for line in input.lines() {
+        let line = line.trim();
+        if line.is_empty() {
+            if document.sections.is_empty() {
+                document.preamble.append(&mut comments);
+            }
+        } else if is_comment(line) {
+            comments.push(line);
+        } else if let Some((name, text)) = split_header(line) {
+            // A comment after the header moves above it, and other text
+            // after it is dropped, as the parser ignores it.
+            if is_comment(text) {
+                comments.push(text);
+            }
+            document.sections.push(SectionView {
+                comments: std::mem::take(&mut comments),
+                name,
+                entries: Vec::new(),
+            });
+        } else {
+            // The input parsed, so this is an entry inside a section.
+            let equals = line.find('=').unwrap();
+            let section = document.sections.last_mut().unwrap();
+            section.entries.push(EntryView {
+                comments: std::mem::take(&mut comments),
+                key: line[..equals].trim_end(),
+                value: line[equals + 1..].trim_start(),
+            });
+        }
+    }
+    document.trailing = comments;
+    Ok(crate::write::write(document, &options.style))
+}
+
+/// Check `input` against `rules`. Fails if `input` cannot be parsed.
+pub fn lint(input: &str, rules: &LintRules) -> Result<Vec<Finding>, ParseError> {
+    Conf::parse_str(input)?;
+
+    let mut findings = Vec::new();
+    let mut delimiter: Option<&str> = None;
+    let mut section_keys: Vec<&str> = Vec::new();
+    let input = input.strip_prefix('\u{feff}').unwrap_or(input);
+    for (index, raw_line) in input.lines().enumerate() {
+        let line_no = index + 1;
+        let mut push = |kind| findings.push(Finding { line: line_no, kind });
+
+        if rules.trailing_whitespace && raw_line.trim_end() != raw_line {
+            push(FindingKind::TrailingWhitespace);
+        }
+
+        let line = raw_line.trim();
+        if is_comment(line) {
+            continue;
+        }
+        if line.starts_with('[') {
+            section_keys.clear();
+            continue;
+        }
+        let equals = match line.find('=') {
+            Some(equals) => equals,
+            None => continue,
+        };
+        let key = line[..equals].trim_end();
+        let value = line[equals + 1..].trim_start();
+
+        if rules.inconsistent_delimiters && !value.is_empty() {
+            let found = &line[key.len()..line.len() - value.len()];
+            match delimiter {
+                Some(expected) if expected != found => {
+                    push(FindingKind::InconsistentDelimiter {
+                        expected: expected.to_string(),
+                        found: found.to_string(),
+                    });
+                }
+                Some(_) => {}
+                None => delimiter = Some(found),
+            }
+        }
+        if rules.duplicate_keys && section_keys.contains(&key) {
+            push(FindingKind::DuplicateKey {
+                key: key.to_string(),
+            });
+        }
+        if rules.unsorted_keys {
+            if let Some(previous) = section_keys.last() {
+                if key < *previous {
+                    push(FindingKind::UnsortedKey {
+                        key: key.to_string(),
+                        previous: previous.to_string(),
+                    });
+                }
+            }
+        }
+        section_keys.push(key);
+    }
+    Ok(findings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format() {
+        let options = FmtOptions {
+            style: WriteOptions {
+                spaces_around_equals: false,
+                ..Default::default()
+            },
+        };
+        assert_eq!(format("  [a]\n x =  1 \n\n\n[b]", &options), Ok("[a]\nx=1\n\n[b]\n".to_string()));
+    }
+
+    #[test]
+    fn test_format_comments() {
+        let input = "# Preamble\n\n# About a\n[a]\n# About z\nz = 1  \n; About y\n  y=2\n\n# About b\n[b] ; b\n# The end\n";
+        let options = FmtOptions {
+            style: WriteOptions {
+                sort: true,
+                indent: "  ".to_string(),
+                ..Default::default()
+            },
+        };
+        assert_eq!(
+            format(input, &options).unwrap(),
+            "# Preamble\n\n# About a\n[a]\n  ; About y\n  y = 2\n  # About z\n  z = 1\n\n# About b\n; b\n[b]\n# The end\n"
+        );
+    }
+
+    #[test]
+    fn test_lint() {
+        let input = "[a]\nz = 1\ny=2\nz = 3 \n[b]\n# z=0\nz = 4\n";
+        let rules = LintRules {
+            unsorted_keys: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            lint(input, &rules),
+            Ok(vec![
+                Finding {
+                    line: 3,
+                    kind: FindingKind::InconsistentDelimiter {
+                        expected: " = ".to_string(),
+                        found: "=".to_string(),
+                    },
+                },
+                Finding {
+                    line: 3,
+                    kind: FindingKind::UnsortedKey {
+                        key: "y".to_string(),
+                        previous: "z".to_string(),
+                    },
+                },
+                Finding {
+                    line: 4,
+                    kind: FindingKind::TrailingWhitespace,
+                },
+                Finding {
+                    line: 4,
+                    kind: FindingKind::DuplicateKey { key: "z".to_string() },
+                },
+            ])
+        );
+    }
+}
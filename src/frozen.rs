@@ -0,0 +1,205 @@
+//! A compact, read-only copy of a config.
+//!
+//! A [`Conf`] allocates a string for every section name, key and value.
+//! A [`FrozenConf`] copies them all into one buffer and refers to them by
+//! offset, which uses less memory and keeps them close together for
+//! configs that are kept for a long time.
+//!
+//! Example:
+//!
+//! ```
+//! use nbconf::Conf;
+//!
+//! let conf = Conf::parse_str("[server]\nport = 80\n").unwrap();
+//! let frozen = conf.freeze_compact();
+//! assert_eq!(frozen.get("server", "port"), Some("80"));
+//! assert_eq!(frozen.to_conf(), conf);
+//! ```
+//!
+//! [`Conf`]: ../struct.Conf.html
+//! [`FrozenConf`]: struct.FrozenConf.html
+
+use crate::{split_path, Conf, Entry, Section};
+use std::ops::Range;
+
+/// Byte range of a string in the buffer.
+type Span = Range<usize>;
+
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
+struct SectionSpans {
+    name: Span,
+    /// The section's range in `FrozenConf::entries`.
+    entries: Range<usize>,
+}
+
+/// An immutable config with all of its strings in one buffer. Returned
+/// by [`Conf::freeze_compact`].
+///
+/// Lookups give the same results as the equivalent methods of [`Conf`].
+///
+/// [`Conf`]: ../struct.Conf.html
+/// [`Conf::freeze_compact`]: ../struct.Conf.html#method.freeze_compact
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
+pub struct FrozenConf {
+    text: String,
+    sections: Vec<SectionSpans>,
+    /// The key and value of each entry, section by section.
+    entries: Vec<(Span, Span)>,
+}
+
+/// A section of a [`FrozenConf`].
+///
+/// [`FrozenConf`]: struct.FrozenConf.html
+#[derive(Clone, Copy, Debug)]
+pub struct FrozenSection<'a> {
+    conf: &'a FrozenConf,
+    spans: &'a SectionSpans,
+}
+
+impl<'a> FrozenSection<'a> {
+    /// Name of the section.
+    pub fn name(&self) -> &'a str {
+        &self.conf.text[self.spans.name.clone()]
+    }
+
+    /// Number of entries.
+    pub fn len(&self) -> usize {
+        self.spans.entries.len()
+    }
+
+    /// Whether the section has no entries.
+    pub fn is_empty(&self) -> bool {
+        self.spans.entries.is_empty()
+    }
+
+    /// The key and value of each entry, in order.
+    pub fn iter(&self) -> impl Iterator<Item = (&'a str, &'a str)> {
+        let conf = self.conf;
+        conf.entries[self.spans.entries.clone()]
+            .iter()
+            .map(move |(key, value)| (&conf.text[key.clone()], &conf.text[value.clone()]))
+    }
+
+    /// Get the value of the first entry with `key`.
+    pub fn get(&self, key: &str) -> Option<&'a str> {
+        self.iter().find(|(k, _)| *k == key).map(|(_, value)| value)
+    }
+}
+
+impl FrozenConf {
+    /// Number of sections.
+    pub fn len(&self) -> usize {
+        self.sections.len()
+    }
+
+    /// Whether the config has no sections.
+    pub fn is_empty(&self) -> bool {
+        self.sections.is_empty()
+    }
+
+    /// The sections, in order.
+    pub fn iter(&self) -> impl Iterator<Item = FrozenSection<'_>> {
+        self.sections
+            .iter()
+            .map(move |spans| FrozenSection { conf: self, spans })
+    }
+
+    /// Get the first section named `name`.
+    pub fn section(&self, name: &str) -> Option<FrozenSection<'_>> {
+        self.iter().find(|s| s.name() == name)
+    }
+
+    /// Get the value of `key` in the first section named `section` that
+    /// has it.
+    pub fn get(&self, section: &str, key: &str) -> Option<&str> {
+        self.iter()
+            .filter(|s| s.name() == section)
+            .find_map(|s| s.get(key))
+    }
+
+    /// Get a value by its dotted path, as [`Conf::get_path`] does.
+    ///
+    /// [`Conf::get_path`]: ../struct.Conf.html#method.get_path
+    pub fn get_path(&self, path: &str) -> Option<&str> {
+        split_path(path).find_map(|(section, key)| self.get(section, key))
+    }
+
+    /// Copy the config back into a [`Conf`].
+    ///
+    /// [`Conf`]: ../struct.Conf.html
+    pub fn to_conf(&self) -> Conf {
+        Conf::from_sections(
+            self.iter()
+                .map(|section| {
+                    let entries = section.iter().map(|(k, v)| Entry::new(k, v)).collect();
+                    Section::new_with_entries(section.name(), entries)
+                })
+                .collect(),
+        )
+    }
+}
+
+impl From<&Conf> for FrozenConf {
+    fn from(conf: &Conf) -> FrozenConf {
+        conf.freeze_compact()
+    }
+}
+
+impl Conf {
+    /// Copy the config into a [`FrozenConf`], which keeps all of its
+    /// strings in one buffer.
+    ///
+    /// [`FrozenConf`]: frozen/struct.FrozenConf.html
+    pub fn freeze_compact(&self) -> FrozenConf {
+        let size = self
+            .iter()
+            .map(|s| s.name.len() + s.iter().map(|e| e.key.len() + e.value.len()).sum::<usize>())
+            .sum();
+        let mut frozen = FrozenConf {
+            text: String::with_capacity(size),
+            sections: Vec::with_capacity(self.sections.len()),
+            entries: Vec::with_capacity(self.iter().map(Section::len).sum()),
+        };
+        let push = |text: &mut String, s: &str| {
+            text.push_str(s);
+            text.len() - s.len()..text.len()
+        };
+        for section in self.iter() {
+            let name = push(&mut frozen.text, &section.name);
+            let start = frozen.entries.len();
+            for entry in section.iter() {
+                let key = push(&mut frozen.text, &entry.key);
+                let value = push(&mut frozen.text, &entry.value);
+                frozen.entries.push((key, value));
+            }
+            frozen.sections.push(SectionSpans {
+                name,
+                entries: start..frozen.entries.len(),
+            });
+        }
+        frozen
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_freeze_compact() {
+        let conf =
+            Conf::parse_str("[a.b]\nx = 1\nx = 2\n[a]\nb.y = 3\n[a.b]\ny = 4\n[empty]\n").unwrap();
+        let frozen = conf.freeze_compact();
+        for path in ["a.b.x", "a.b.y", "a.b", "b.x"].iter() {
+            assert_eq!(frozen.get_path(path), conf.get_path(path));
+        }
+        assert_eq!(frozen.len(), 4);
+        assert_eq!(frozen.section("a").unwrap().get("b.y"), Some("3"));
+        assert!(frozen.section("empty").unwrap().is_empty());
+        let entries: Vec<_> = frozen.iter().next().unwrap().iter().collect();
+        assert_eq!(entries, vec![("x", "1"), ("x", "2")]);
+        assert_eq!(frozen.text, "a.bx1x2ab.y3a.by4empty");
+        assert_eq!(frozen.to_conf(), conf);
+        assert_eq!(FrozenConf::from(&Conf::new()), FrozenConf::default());
+    }
+}
@@ -0,0 +1,176 @@
+//! Undo and redo of edits to a config.
+//!
+//! Example:
+//!
+//! ```
+//! use nbconf::history::History;
+//!
+//! let mut history = History::new(nbconf::Conf::parse_str("[server]\nport = 80").unwrap());
+//! history.edit(|conf| conf.sections[0].set("port", "8080"));
+//! assert_eq!(history.get_path("server.port"), Some("8080"));
+//!
+//! assert!(history.undo());
+//! assert_eq!(history.get_path("server.port"), Some("80"));
+//! assert!(history.redo());
+//! assert_eq!(history.get_path("server.port"), Some("8080"));
+//! ```
+
+use crate::Conf;
+
+/// A config with a stack of earlier states to undo to.
+///
+/// Each call to [`edit`] or [`try_edit`] is one step. `History`
+/// dereferences to the current [`Conf`] for reading.
+///
+/// [`edit`]: #method.edit
+/// [`try_edit`]: #method.try_edit
+/// [`Conf`]: ../struct.Conf.html
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct History {
+    current: Conf,
+    undo: Vec<Conf>,
+    redo: Vec<Conf>,
+    limit: Option<usize>,
+}
+
+impl History {
+    /// Start a history at `conf`, with no limit on the number of steps
+    /// kept.
+    pub fn new(conf: Conf) -> History {
+        History {
+            current: conf,
+            ..History::default()
+        }
+    }
+
+    /// Keep at most `limit` steps to undo, dropping the oldest first.
+    pub fn with_limit(mut self, limit: usize) -> History {
+        self.limit = Some(limit);
+        self.trim();
+        self
+    }
+
+    fn trim(&mut self) {
+        if let Some(limit) = self.limit {
+            if self.undo.len() > limit {
+                self.undo.drain(..self.undo.len() - limit);
+            }
+        }
+    }
+
+    /// Apply `f` to the config as one step. Steps that were undone can
+    /// no longer be redone.
+    pub fn edit<T, F: FnOnce(&mut Conf) -> T>(&mut self, f: F) -> T {
+        let previous = self.current.clone();
+        let result = f(&mut self.current);
+        self.push(previous);
+        result
+    }
+
+    /// Apply `f` to the config as one step if it succeeds, as by
+    /// [`Conf::transaction`]. If `f` fails, the config and history are
+    /// unchanged.
+    ///
+    /// [`Conf::transaction`]: ../struct.Conf.html#method.transaction
+    pub fn try_edit<T, E, F>(&mut self, f: F) -> Result<T, E>
+    where
+        F: FnOnce(&mut Conf) -> Result<T, E>,
+    {
+        let previous = self.current.clone();
+        let result = self.current.transaction(f)?;
+        self.push(previous);
+        Ok(result)
+    }
+
+    fn push(&mut self, previous: Conf) {
+        if previous != self.current {
+            self.undo.push(previous);
+            self.redo.clear();
+            self.trim();
+        }
+    }
+
+    /// Go back one step. Returns false if there is nothing to undo.
+    pub fn undo(&mut self) -> bool {
+        match self.undo.pop() {
+            Some(previous) => {
+                self.redo
+                    .push(std::mem::replace(&mut self.current, previous));
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Go forward one undone step. Returns false if there is nothing to
+    /// redo.
+    pub fn redo(&mut self) -> bool {
+        match self.redo.pop() {
+            Some(next) => {
+                self.undo.push(std::mem::replace(&mut self.current, next));
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Whether there is a step to undo.
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    /// Whether there is a step to redo.
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    /// Drop the history and return the current config.
+    pub fn into_inner(self) -> Conf {
+        self.current
+    }
+}
+
+impl std::ops::Deref for History {
+    type Target = Conf;
+
+    fn deref(&self) -> &Conf {
+        &self.current
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_history() {
+        let mut history = History::new(Conf::new()).with_limit(2);
+        for name in ["a", "b", "c"].iter() {
+            history.edit(|conf| conf.add_section(name, Vec::new()));
+        }
+        // Edits that change nothing are not steps.
+        history.edit(|conf| conf.rename_section("x", "y"));
+
+        assert!(history.undo());
+        assert!(history.undo());
+        assert!(!history.undo());
+        assert_eq!(history.section_names(), ["a"]);
+
+        assert!(history.redo());
+        assert!(history.can_redo());
+        let result: Result<(), &str> = history.try_edit(|conf| {
+            conf.add_section("d", Vec::new());
+            Err("failed")
+        });
+        assert_eq!(result, Err("failed"));
+        assert!(history.can_redo());
+
+        let result: Result<(), ()> = history.try_edit(|conf| {
+            conf.add_section("d", Vec::new());
+            Ok(())
+        });
+        assert_eq!(result, Ok(()));
+        assert!(!history.can_redo());
+        assert_eq!(history.into_inner().section_names(), ["a", "b", "d"]);
+    }
+}
@@ -0,0 +1,108 @@
+//! Hashed lookups for configs that are read often.
+//!
+//! Looking up a value in a [`Conf`] scans its sections and entries, which
+//! keeps their order and repeats exactly as written. A [`ConfIndex`]
+//! hashes them once so that each lookup takes constant time.
+//!
+//! Example:
+//!
+//! ```
+//! use nbconf::Conf;
+//!
+//! let conf = Conf::parse_str("[server]\nport = 80\n").unwrap();
+//! let index = conf.index();
+//! assert_eq!(index.get("server", "port"), Some("80"));
+//! assert_eq!(index.get_path("server.port"), Some("80"));
+//! ```
+//!
+//! [`Conf`]: ../struct.Conf.html
+//! [`ConfIndex`]: struct.ConfIndex.html
+
+use crate::{split_path, Conf, Section};
+use std::collections::HashMap;
+
+/// A read-only view of a config with hashed lookups. Returned by
+/// [`Conf::index`].
+///
+/// Lookups give the same results as the equivalent methods of [`Conf`].
+///
+/// [`Conf`]: ../struct.Conf.html
+/// [`Conf::index`]: ../struct.Conf.html#method.index
+#[derive(Clone, Debug)]
+pub struct ConfIndex<'a> {
+    conf: &'a Conf,
+    /// The sections with each name, in order.
+    sections: HashMap<&'a str, Vec<&'a Section>>,
+    /// The first value of each key in the sections with each name.
+    values: HashMap<(&'a str, &'a str), &'a str>,
+}
+
+impl<'a> ConfIndex<'a> {
+    /// The config being indexed.
+    pub fn conf(&self) -> &'a Conf {
+        self.conf
+    }
+
+    /// The sections named `name`, in order.
+    pub fn sections(&self, name: &str) -> &[&'a Section] {
+        self.sections.get(name).map_or(&[], Vec::as_slice)
+    }
+
+    /// Get the value of `key` in the first section named `section` that
+    /// has it.
+    pub fn get(&self, section: &str, key: &str) -> Option<&'a str> {
+        self.values.get(&(section, key)).copied()
+    }
+
+    /// Get a value by its dotted path, as [`Conf::get_path`] does.
+    ///
+    /// [`Conf::get_path`]: ../struct.Conf.html#method.get_path
+    pub fn get_path(&self, path: &str) -> Option<&'a str> {
+        split_path(path).find_map(|(section, key)| self.get(section, key))
+    }
+}
+
+impl Conf {
+    /// Hash the sections and entries into a [`ConfIndex`] for faster
+    /// lookups. The index borrows the config, so the config cannot change
+    /// while the index is in use.
+    ///
+    /// [`ConfIndex`]: index/struct.ConfIndex.html
+    pub fn index(&self) -> ConfIndex<'_> {
+        let mut sections: HashMap<&str, Vec<&Section>> = HashMap::new();
+        let mut values = HashMap::new();
+        for section in self.sections.iter() {
+            sections.entry(&section.name).or_default().push(section);
+            for entry in section.iter() {
+                values
+                    .entry((section.name.as_str(), entry.key.as_str()))
+                    .or_insert(entry.value.as_str());
+            }
+        }
+        ConfIndex {
+            conf: self,
+            sections,
+            values,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_index() {
+        let conf =
+            Conf::parse_str("[a.b]\nx = 1\nx = 2\n[a]\nb.y = 3\n[a.b]\ny = 4\nz = 5\n").unwrap();
+        let index = conf.index();
+        for path in ["a.b.x", "a.b.y", "a.b.z", "a.b", "b.x"].iter() {
+            assert_eq!(index.get_path(path), conf.get_path(path));
+        }
+        assert_eq!(index.get("a.b", "x"), Some("1"));
+        assert_eq!(index.get("a.b", "z"), Some("5"));
+        assert_eq!(index.get("a", "x"), None);
+        assert_eq!(index.sections("a.b").len(), 2);
+        assert!(index.sections("c").is_empty());
+    }
+}
@@ -16,6 +16,43 @@
 //! assert_eq!(conf.sections[1].entries[0].key, "nice to");
 //! assert_eq!(conf.sections[1].entries[0].value, "meet you");
 //! ```
+//!
+//! Lines starting with `#` or `;` are comments and are ignored by the
+//! parser. A comment may also follow a section header, as in
+//! `[server] # public`.
+
+pub mod access;
+pub mod complete;
+pub mod diff;
+pub mod docs;
+pub mod encrypt;
+pub mod env;
+pub mod expand;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod file;
+pub mod fmt;
+pub mod frozen;
+pub mod history;
+pub mod index;
+pub mod migrate;
+pub mod redact;
+pub mod resolve;
+pub mod schema;
+pub mod source;
+pub mod stream;
+pub mod tokens;
+pub mod tree;
+pub mod value;
+pub mod visit;
+#[cfg(feature = "watch")]
+pub mod watch;
+mod write;
+
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io;
+use std::path::{Path, PathBuf};
 
 /// The specific type of parse error.
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -24,8 +61,57 @@ pub enum ParseErrorKind {
     EntryOutsideOfSection,
     /// A section was declared, but the closing bracket is missing.
     MissingClosingBracket,
+    /// A section header is followed by text that is not a comment, as in
+    /// `[name] junk`. Only produced when
+    /// [`ParseOptions::reject_text_after_section_headers`] is set.
+    ///
+    /// [`ParseOptions::reject_text_after_section_headers`]: struct.ParseOptions.html#structfield.reject_text_after_section_headers
+    TextAfterSectionHeader,
     /// An entry is missing an equals (`=`).
     MissingEquals,
+    /// An entry has nothing before its equals (`=`). Only produced when
+    /// [`ParseOptions::reject_empty_keys`] is set.
+    ///
+    /// [`ParseOptions::reject_empty_keys`]: struct.ParseOptions.html#structfield.reject_empty_keys
+    EmptyKey,
+    /// A section header has an empty or whitespace-only name. Only
+    /// produced when [`ParseOptions::reject_empty_section_names`] is set.
+    ///
+    /// [`ParseOptions::reject_empty_section_names`]: struct.ParseOptions.html#structfield.reject_empty_section_names
+    EmptySectionName,
+    /// A key, value, or section name contains a control character such
+    /// as NUL. The column is in characters, starting from 1. Only
+    /// produced when [`ParseOptions::reject_control_chars`] is set.
+    ///
+    /// [`ParseOptions::reject_control_chars`]: struct.ParseOptions.html#structfield.reject_control_chars
+    ControlCharacter { column: usize },
+    /// The input is longer than [`ParseLimits::max_input_size`] bytes.
+    ///
+    /// [`ParseLimits::max_input_size`]: struct.ParseLimits.html#structfield.max_input_size
+    InputTooLarge { limit: usize },
+    /// The line is longer than [`ParseLimits::max_line_length`] bytes.
+    ///
+    /// [`ParseLimits::max_line_length`]: struct.ParseLimits.html#structfield.max_line_length
+    LineTooLong { limit: usize },
+    /// The input has more than [`ParseLimits::max_sections`] sections.
+    ///
+    /// [`ParseLimits::max_sections`]: struct.ParseLimits.html#structfield.max_sections
+    TooManySections { limit: usize },
+    /// The section has more than [`ParseLimits::max_entries_per_section`]
+    /// entries.
+    ///
+    /// [`ParseLimits::max_entries_per_section`]: struct.ParseLimits.html#structfield.max_entries_per_section
+    TooManyEntries { limit: usize },
+    /// The input is not valid UTF-8. The offset is in bytes from the
+    /// start of the input.
+    InvalidUtf8 { offset: usize },
+    /// The input starts with a UTF-16 byte order mark but is not valid
+    /// UTF-16. The offset is in bytes from the start of the input.
+    InvalidUtf16 { offset: usize },
+    /// A key does not follow [`ParseOptions::key_policy`].
+    ///
+    /// [`ParseOptions::key_policy`]: struct.ParseOptions.html#structfield.key_policy
+    InvalidKeyName { violation: KeyViolation },
 }
 
 /// Error produced from [`Conf::parse_str`].
@@ -37,6 +123,8 @@ pub struct ParseError {
     pub line: usize,
     /// Type of error.
     pub kind: ParseErrorKind,
+    /// Name of the input, such as a file path, if known.
+    pub source_name: Option<String>,
 }
 
 impl ParseError {
@@ -44,232 +132,3939 @@ impl ParseError {
     ///
     /// [`ParseError`]: struct.ParseError.html
     pub fn new(line: usize, kind: ParseErrorKind) -> ParseError {
-        ParseError { line, kind }
+        ParseError {
+            line,
+            kind,
+            source_name: None,
+        }
     }
-}
 
-/// A single entry within the section.
-#[derive(Clone, Debug, Eq, PartialEq)]
-pub struct Entry {
-    /// Name of the entry.
-    pub key: String,
-    /// Value of the entry.
-    pub value: String,
+    /// Set the name of the input the error occurred in.
+    pub fn with_source_name(mut self, name: &str) -> ParseError {
+        self.source_name = Some(name.to_string());
+        self
+    }
+
+    /// Render the error for display to end users.
+    ///
+    /// `source` is the text that failed to parse. The output shows the
+    /// offending line with a caret under the problem and a short hint:
+    ///
+    /// ```text
+    /// error: missing closing bracket
+    ///  --> app.conf:3
+    ///   |
+    /// 3 | [b
+    ///   |   ^ expected ']' at the end of the section header
+    /// ```
+    pub fn render(&self, source: &str) -> String {
+        self.render_with(source, ColorChoice::Never)
+    }
+
+    /// Render the error for display to end users, optionally with ANSI
+    /// colors.
+    ///
+    /// This is the same as [`render`], except that with color enabled the
+    /// error kind is shown in red, the caret and hint in yellow, and the
+    /// name of a section header in bold.
+    ///
+    /// [`render`]: #method.render
+    pub fn render_with(&self, source: &str, color: ColorChoice) -> String {
+        let color = color.enabled();
+        let paint = |code: &str, text: &str| {
+            if color && !text.is_empty() {
+                format!("\x1b[{}m{}\x1b[0m", code, text)
+            } else {
+                text.to_string()
+            }
+        };
+        let location = match &self.source_name {
+            Some(name) => format!("{}:{}", name, self.line),
+            None => format!("line {}", self.line),
+        };
+        let gutter = " ".repeat(self.line.to_string().len());
+        let mut output = format!(
+            "{}{}\n{}{} {}\n",
+            paint("1;31", "error"),
+            paint("1", &format!(": {}", self.kind)),
+            gutter,
+            paint("1;34", "-->"),
+            location
+        );
+
+        let source = source.strip_prefix('\u{feff}').unwrap_or(source);
+        let line = match source.lines().nth(self.line.wrapping_sub(1)) {
+            Some(line) => line.trim_end(),
+            None => return output,
+        };
+        let content = line.trim_start();
+        let indent: String = line[..line.len() - content.len()]
+            .chars()
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        // The column of the caret after the indentation, and its width.
+        let width = content.chars().count();
+        let (skip, carets, hint) = match self.kind {
+            ParseErrorKind::EntryOutsideOfSection => {
+                (0, width, " add a [section] header before this entry")
+            }
+            ParseErrorKind::MissingClosingBracket => {
+                (width, 1, " expected ']' at the end of the section header")
+            }
+            ParseErrorKind::TextAfterSectionHeader => {
+                let text = split_header(content).map_or(0, |(_, text)| text.chars().count());
+                (width - text, text, " expected a comment or nothing after ']'")
+            }
+            ParseErrorKind::MissingEquals => (width, 1, " expected '=' in entry"),
+            ParseErrorKind::EmptyKey => (0, 1, " expected a key before '='"),
+            ParseErrorKind::InvalidKeyName { ref violation } => {
+                let key = content.split('=').next().unwrap_or_default().trim_end();
+                match violation {
+                    KeyViolation::DisallowedChar { c } => (
+                        key.chars().position(|k| k == *c).unwrap_or_default(),
+                        1,
+                        " not allowed in keys",
+                    ),
+                    _ => (0, key.chars().count(), ""),
+                }
+            }
+            ParseErrorKind::EmptySectionName => (0, width, " expected a section name"),
+            ParseErrorKind::ControlCharacter { column } => (
+                column.saturating_sub(indent.chars().count() + 1),
+                1,
+                " control character",
+            ),
+            ParseErrorKind::InputTooLarge { .. }
+            | ParseErrorKind::LineTooLong { .. }
+            | ParseErrorKind::TooManySections { .. }
+            | ParseErrorKind::TooManyEntries { .. }
+            | ParseErrorKind::InvalidUtf8 { .. }
+            | ParseErrorKind::InvalidUtf16 { .. } => (0, 0, ""),
+        };
+        let pipe = paint("1;34", "|");
+        // Don't pass control characters from the input to the terminal.
+        let line: String = line
+            .chars()
+            .map(|c| if is_control(c) { '\u{fffd}' } else { c })
+            .collect();
+        let shown = match line.trim_start().strip_prefix('[') {
+            Some(name) if color => format!("{}[{}", indent, paint("1", name)),
+            _ => line.clone(),
+        };
+        output += &format!(
+            "{} {}\n{} {} {}\n",
+            gutter,
+            pipe,
+            paint("1;34", &self.line.to_string()),
+            pipe,
+            shown
+        );
+        if carets != 0 {
+            output += &format!(
+                "{} {} {}{}{}\n",
+                gutter,
+                pipe,
+                indent,
+                " ".repeat(skip),
+                paint("1;33", &format!("{}{}", "^".repeat(carets), hint))
+            );
+        }
+        output
+    }
 }
 
-impl Entry {
-    pub fn new(key: &str, value: &str) -> Entry {
-        Entry {
-            key: key.to_string(),
-            value: value.to_string(),
+impl std::fmt::Display for ParseErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ParseErrorKind::EntryOutsideOfSection => write!(f, "entry outside of any section"),
+            ParseErrorKind::MissingClosingBracket => write!(f, "missing closing bracket"),
+            ParseErrorKind::TextAfterSectionHeader => write!(f, "text after section header"),
+            ParseErrorKind::MissingEquals => write!(f, "missing '=' in entry"),
+            ParseErrorKind::EmptyKey => write!(f, "empty key"),
+            ParseErrorKind::EmptySectionName => write!(f, "empty section name"),
+            ParseErrorKind::ControlCharacter { column } => {
+                write!(f, "control character at column {}", column)
+            }
+            ParseErrorKind::InputTooLarge { limit } => {
+                write!(f, "input is larger than {} bytes", limit)
+            }
+            ParseErrorKind::LineTooLong { limit } => {
+                write!(f, "line is longer than {} bytes", limit)
+            }
+            ParseErrorKind::TooManySections { limit } => {
+                write!(f, "more than {} sections", limit)
+            }
+            ParseErrorKind::TooManyEntries { limit } => {
+                write!(f, "more than {} entries in section", limit)
+            }
+            ParseErrorKind::InvalidUtf8 { offset } => {
+                write!(f, "invalid UTF-8 at byte offset {}", offset)
+            }
+            ParseErrorKind::InvalidUtf16 { offset } => {
+                write!(f, "invalid UTF-16 at byte offset {}", offset)
+            }
+            ParseErrorKind::InvalidKeyName { violation } => write!(f, "invalid key: {}", violation),
         }
     }
+}
 
-    pub fn to_string(&self) -> String {
-        format!("{} = {}", self.key, self.value)
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        if let Some(name) = &self.source_name {
+            write!(f, "{}: ", name)?;
+        }
+        write!(f, "line {}: {}", self.line, self.kind)
     }
 }
 
-/// A named section within the config.
-#[derive(Clone, Debug, Eq, PartialEq)]
-pub struct Section {
-    /// Name of the section.
-    pub name: String,
-    /// Entries within the section.
-    pub entries: Vec<Entry>,
+impl std::error::Error for ParseError {}
+
+/// Error produced from [`Conf::load`].
+///
+/// [`Conf::load`]: struct.Conf.html#method.load
+#[derive(Debug)]
+pub enum LoadError {
+    /// The file could not be read.
+    Io(io::Error),
+    /// The file could not be parsed.
+    Parse(ParseError),
 }
 
-impl Section {
-    pub fn new(name: &str) -> Section {
-        Section {
-            name: name.to_string(),
-            entries: Vec::new(),
+impl std::fmt::Display for LoadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            LoadError::Io(err) => write!(f, "{}", err),
+            LoadError::Parse(err) => write!(f, "{}", err),
         }
     }
+}
 
-    pub fn new_with_entries(name: &str, entries: Vec<Entry>) -> Section {
-        Section {
-            name: name.to_string(),
-            entries,
+impl std::error::Error for LoadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            LoadError::Io(err) => Some(err),
+            LoadError::Parse(err) => Some(err),
         }
     }
+}
 
-    pub fn get(&self, key: &str) -> Option<&str> {
-        self.entries.iter().find(|e| e.key == key).map(|e| e.value.as_str())
+impl LoadError {
+    /// Attach the path of the file being loaded to the error.
+    pub(crate) fn with_path(self, path: &Path) -> LoadError {
+        match self {
+            LoadError::Io(err) => LoadError::Io(io::Error::new(
+                err.kind(),
+                format!("{}: {}", path.display(), err),
+            )),
+            LoadError::Parse(err) => {
+                LoadError::Parse(err.with_source_name(&path.display().to_string()))
+            }
+        }
     }
+}
 
-    pub fn to_string(&self) -> String {
-        let mut result = format!("[{}]", self.name);
-        for entry in self.entries.iter() {
-            result += "\n";
-            result += &entry.to_string();
+impl From<io::Error> for LoadError {
+    fn from(err: io::Error) -> LoadError {
+        LoadError::Io(err)
+    }
+}
+
+impl From<ParseError> for LoadError {
+    fn from(err: ParseError) -> LoadError {
+        LoadError::Parse(err)
+    }
+}
+
+/// Whether [`ParseError::render_with`] uses ANSI colors.
+///
+/// [`ParseError::render_with`]: struct.ParseError.html#method.render_with
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum ColorChoice {
+    /// Use colors if stderr is a terminal and the `NO_COLOR` environment
+    /// variable is not set.
+    #[default]
+    Auto,
+    /// Always use colors.
+    Always,
+    /// Never use colors.
+    Never,
+}
+
+impl ColorChoice {
+    fn enabled(self) -> bool {
+        use std::io::IsTerminal;
+
+        match self {
+            ColorChoice::Auto => {
+                io::stderr().is_terminal() && std::env::var_os("NO_COLOR").is_none()
+            }
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
         }
-        result += "\n";
-        result
     }
 }
 
-/// A collection of config sections.
-#[derive(Clone, Debug, Eq, PartialEq)]
-pub struct Conf {
-    pub sections: Vec<Section>,
+/// Options for [`Conf::parse_str_with`].
+///
+/// The default options accept everything [`Conf::parse_str`] accepts.
+///
+/// [`Conf::parse_str_with`]: struct.Conf.html#method.parse_str_with
+/// [`Conf::parse_str`]: struct.Conf.html#method.parse_str
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ParseOptions {
+    /// Fail with [`ParseErrorKind::EmptyKey`] on entries such as
+    /// ` = value`.
+    ///
+    /// [`ParseErrorKind::EmptyKey`]: enum.ParseErrorKind.html#variant.EmptyKey
+    pub reject_empty_keys: bool,
+    /// Fail with [`ParseErrorKind::EmptySectionName`] on headers such as
+    /// `[]` or `[  ]`.
+    ///
+    /// [`ParseErrorKind::EmptySectionName`]: enum.ParseErrorKind.html#variant.EmptySectionName
+    pub reject_empty_section_names: bool,
+    /// Fail with [`ParseErrorKind::ControlCharacter`] if a key, value, or
+    /// section name contains a control character other than tab, such as
+    /// NUL or an escape character.
+    ///
+    /// [`ParseErrorKind::ControlCharacter`]: enum.ParseErrorKind.html#variant.ControlCharacter
+    pub reject_control_chars: bool,
+    /// Fail with [`ParseErrorKind::TextAfterSectionHeader`] on headers
+    /// such as `[name] junk`, rather than ignoring the text with a
+    /// warning.
+    ///
+    /// [`ParseErrorKind::TextAfterSectionHeader`]: enum.ParseErrorKind.html#variant.TextAfterSectionHeader
+    pub reject_text_after_section_headers: bool,
+    /// Put entries before the first section header into a section with
+    /// an empty name, rather than failing with
+    /// [`ParseErrorKind::EntryOutsideOfSection`]. This reads files that
+    /// are only `key = value` lines.
+    ///
+    /// [`ParseErrorKind::EntryOutsideOfSection`]: enum.ParseErrorKind.html#variant.EntryOutsideOfSection
+    pub sectionless: bool,
+    /// Fail with [`ParseErrorKind::InvalidKeyName`] on keys that do not
+    /// follow this policy.
+    ///
+    /// [`ParseErrorKind::InvalidKeyName`]: enum.ParseErrorKind.html#variant.InvalidKeyName
+    pub key_policy: Option<KeyPolicy>,
+    /// Bounds on the size of the input.
+    pub limits: ParseLimits,
 }
 
-impl Conf {
-    /// Create an empty config.
-    pub fn new() -> Conf {
-        Conf {
-            sections: Vec::new(),
+/// Bounds on the size of the input to [`Conf::parse_str_with`], for
+/// parsing untrusted input. Each limit is unbounded if `None`.
+///
+/// [`Conf::parse_str_with`]: struct.Conf.html#method.parse_str_with
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct ParseLimits {
+    /// Maximum size of the input in bytes.
+    pub max_input_size: Option<usize>,
+    /// Maximum length of a line in bytes, not counting the line
+    /// terminator.
+    pub max_line_length: Option<usize>,
+    /// Maximum number of sections.
+    pub max_sections: Option<usize>,
+    /// Maximum number of entries in a single section.
+    pub max_entries_per_section: Option<usize>,
+}
+
+impl ParseOptions {
+    /// Options that reject every questionable construct.
+    pub fn strict() -> ParseOptions {
+        ParseOptions {
+            reject_empty_keys: true,
+            reject_empty_section_names: true,
+            reject_control_chars: true,
+            reject_text_after_section_headers: true,
+            sectionless: false,
+            key_policy: None,
+            limits: ParseLimits::default(),
         }
     }
+}
 
-    /// Create a pre-populated config.
-    pub fn from_sections(sections: Vec<Section>) -> Conf {
-        Conf { sections }
+/// Which characters a key may contain under a [`KeyPolicy`].
+///
+/// [`KeyPolicy`]: struct.KeyPolicy.html
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub enum KeyChars {
+    /// Any character.
+    #[default]
+    Any,
+    /// ASCII letters, digits and `_`.
+    AsciiWord,
+    /// ASCII letters and digits, and the characters in the string.
+    AsciiAlphanumericAnd(String),
+}
+
+impl KeyChars {
+    fn allows(&self, c: char) -> bool {
+        match self {
+            KeyChars::Any => true,
+            KeyChars::AsciiWord => c.is_ascii_alphanumeric() || c == '_',
+            KeyChars::AsciiAlphanumericAnd(extra) => c.is_ascii_alphanumeric() || extra.contains(c),
+        }
     }
+}
 
-    /// Parse a string into a config.
-    pub fn parse_str(s: &str) -> Result<Conf, ParseError> {
-        let mut conf = Conf::new();
-        let mut line_no = 0;
-        for line in s.lines() {
-            line_no += 1;
-            let line = line.trim();
-            if line.starts_with('[') {
-                if line.ends_with(']') {
-                    let name = &line[1..line.len() - 1];
-                    conf.sections.push(Section::new(name));
-                } else {
-                    return Err(ParseError::new(
-                        line_no,
-                        ParseErrorKind::MissingClosingBracket,
-                    ));
-                }
-            } else if line.len() != 0 {
-                let parts: Vec<&str> = line.splitn(2, '=').collect();
-                if parts.len() == 2 {
-                    if let Some(section) = conf.sections.last_mut() {
-                        section.entries.push(Entry {
-                            key: parts[0].trim().to_string(),
-                            value: parts[1].trim().to_string(),
-                        });
-                    } else {
-                        return Err(ParseError::new(
-                            line_no,
-                            ParseErrorKind::EntryOutsideOfSection,
-                        ));
-                    }
-                } else {
-                    return Err(ParseError::new(line_no, ParseErrorKind::MissingEquals));
-                }
+/// The case style required by a [`KeyPolicy`].
+///
+/// [`KeyPolicy`]: struct.KeyPolicy.html
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum KeyCase {
+    /// Lowercase ASCII letters, digits and `_`, as in `max_size`.
+    Snake,
+    /// Uppercase ASCII letters, digits and `_`, as in `MAX_SIZE`.
+    ScreamingSnake,
+    /// Lowercase ASCII letters, digits and `-`, as in `max-size`.
+    Kebab,
+    /// ASCII letters and digits starting with a lowercase letter, as in
+    /// `maxSize`.
+    Camel,
+}
+
+impl KeyCase {
+    fn matches(self, key: &str) -> bool {
+        match self {
+            KeyCase::Snake => key
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_'),
+            KeyCase::ScreamingSnake => key
+                .chars()
+                .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_'),
+            KeyCase::Kebab => key
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-'),
+            KeyCase::Camel => {
+                !key.starts_with(|c: char| c.is_ascii_uppercase() || c.is_ascii_digit())
+                    && key.chars().all(|c| c.is_ascii_alphanumeric())
             }
         }
-        Ok(conf)
     }
+}
 
-    /// Serialize the config as a string.
-    pub fn to_string(&self) -> String {
-        let mut output = String::new();
-        let mut is_first = true;
-        for section in self.sections.iter() {
-            if is_first {
-                is_first = false;
-            } else {
-                output += "\n";
-            }
-            output += &section.to_string();
+impl std::fmt::Display for KeyCase {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let name = match self {
+            KeyCase::Snake => "snake_case",
+            KeyCase::ScreamingSnake => "SCREAMING_SNAKE_CASE",
+            KeyCase::Kebab => "kebab-case",
+            KeyCase::Camel => "camelCase",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Rules for key names, checked by [`Section::set_checked`] and at parse
+/// time with [`ParseOptions::key_policy`]. The default policy allows any
+/// key.
+///
+/// [`Section::set_checked`]: struct.Section.html#method.set_checked
+/// [`ParseOptions::key_policy`]: struct.ParseOptions.html#structfield.key_policy
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct KeyPolicy {
+    /// Characters allowed in keys.
+    pub chars: KeyChars,
+    /// Maximum length of a key in characters, or `None` for no limit.
+    pub max_length: Option<usize>,
+    /// Case style keys must be in, or `None` for any.
+    pub case: Option<KeyCase>,
+}
+
+impl KeyPolicy {
+    /// A policy allowing only ASCII letters, digits and `_`.
+    pub fn ascii_word() -> KeyPolicy {
+        KeyPolicy {
+            chars: KeyChars::AsciiWord,
+            ..KeyPolicy::default()
         }
-        output
     }
 
-    /// Append a section to the config.
-    pub fn add_section(&mut self, name: &str, entries: Vec<Entry>) {
-        self.sections.push(Section { name: name.to_string(), entries });
+    /// Set the maximum length of a key in characters.
+    pub fn max_length(mut self, max_length: usize) -> KeyPolicy {
+        self.max_length = Some(max_length);
+        self
     }
 
-    /// Get all the sections' names.
-    pub fn section_names(&self) -> Vec<&str> {
-        self.sections.iter().map(|section| section.name.as_str()).collect()
+    /// Set the case style keys must be in.
+    pub fn case(mut self, case: KeyCase) -> KeyPolicy {
+        self.case = Some(case);
+        self
+    }
+
+    /// Check that `key` follows the policy.
+    pub fn check(&self, key: &str) -> Result<(), KeyViolation> {
+        if let Some(c) = key.chars().find(|c| !self.chars.allows(*c)) {
+            return Err(KeyViolation::DisallowedChar { c });
+        }
+        if let Some(limit) = self.max_length.filter(|limit| key.chars().count() > *limit) {
+            return Err(KeyViolation::TooLong { limit });
+        }
+        match self.case {
+            Some(case) if !case.matches(key) => Err(KeyViolation::WrongCase { case }),
+            _ => Ok(()),
+        }
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+/// How a key breaks a [`KeyPolicy`].
+///
+/// [`KeyPolicy`]: struct.KeyPolicy.html
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum KeyViolation {
+    /// The key contains a character that is not allowed.
+    DisallowedChar { c: char },
+    /// The key is longer than the limit, in characters.
+    TooLong { limit: usize },
+    /// The key is not in the required case style.
+    WrongCase { case: KeyCase },
+}
 
-    #[test]
-    fn test_section() {
-        let mut expected = Conf::new();
-        expected.add_section("mySection", Vec::new());
-        assert_eq!(Conf::parse_str("[mySection]"), Ok(expected));
+impl std::fmt::Display for KeyViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            KeyViolation::DisallowedChar { c } => write!(f, "character {:?} is not allowed", c),
+            KeyViolation::TooLong { limit } => write!(f, "longer than {} characters", limit),
+            KeyViolation::WrongCase { case } => write!(f, "not in {}", case),
+        }
     }
+}
 
-    #[test]
-    fn test_entry() {
-        let mut expected = Conf::new();
-        expected.add_section("mySection", vec![
-            Entry {
-                key: "a".to_string(),
-                value: "b".to_string(),
-            }
-        ]);
-        assert_eq!(Conf::parse_str("[mySection]\na = b"), Ok(expected));
-    }
+impl std::error::Error for KeyViolation {}
 
-    #[test]
-    fn test_to_string() {
-        let mut conf = Conf::new();
-        conf.add_section("sec1", vec![
-            Entry {
-                key: "a".to_string(),
-                value: "b".to_string(),
-            }
-        ]);
-        conf.add_section("sec2", vec![
-            Entry {
-                key: "c".to_string(),
-                value: "d".to_string(),
+/// The specific type of serialize error.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum SerializeErrorKind {
+    /// An entry has an empty key, which would not read back as the same
+    /// entry.
+    EmptyKey,
+    /// A section has an empty or whitespace-only name.
+    EmptySectionName,
+    /// A section name contains a line break, or a `]` followed by a
+    /// comment.
+    InvalidSectionName,
+    /// A key contains `=` or a line break, starts with `#`, `;` or `[`,
+    /// or starts or ends with whitespace.
+    InvalidKey { key: String },
+    /// A value contains a line break, or starts or ends with whitespace.
+    InvalidValue { key: String },
+}
+
+/// Error produced from [`Conf::try_to_string_styled`] when the config
+/// cannot be written as valid text.
+///
+/// [`Conf::try_to_string_styled`]: struct.Conf.html#method.try_to_string_styled
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SerializeError {
+    /// Name of the section containing the problem.
+    pub section: String,
+    /// Type of error.
+    pub kind: SerializeErrorKind,
+}
+
+impl std::fmt::Display for SerializeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "section {:?}: ", self.section)?;
+        match &self.kind {
+            SerializeErrorKind::EmptyKey => write!(f, "empty key"),
+            SerializeErrorKind::EmptySectionName => write!(f, "empty section name"),
+            SerializeErrorKind::InvalidSectionName => write!(f, "invalid section name"),
+            SerializeErrorKind::InvalidKey { key } => write!(f, "invalid key {:?}", key),
+            SerializeErrorKind::InvalidValue { key } => {
+                write!(f, "invalid value for key {:?}", key)
             }
-        ]);
-        assert_eq!(conf.to_string(), "[sec1]\na = b\n\n[sec2]\nc = d\n");
+        }
     }
+}
 
-    #[test]
-    fn test_section_get() {
-        let conf = Conf::from_sections(vec![
-            Section::new_with_entries("mySection", vec![
-                Entry::new("a", "b"),
-                Entry::new("x", "y"),
-            ])
-        ]);
-        assert_eq!(conf.sections[0].get("x"), Some("y"));
-    }
+impl std::error::Error for SerializeError {}
 
-    #[test]
-    fn test_conf_section_names() {
-        let conf = Conf::from_sections(vec![
-            Section::new("sec1"),
-            Section::new("sec2"),
-        ]);
-        assert_eq!(conf.section_names(), vec!["sec1", "sec2"]);
+impl From<SerializeError> for io::Error {
+    fn from(err: SerializeError) -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidInput, err)
     }
+}
 
-    #[test]
-    fn test_missing_closing_bracket() {
-        assert_eq!(
-            Conf::parse_str("[mySection"),
-            Err(ParseError::new(1, ParseErrorKind::MissingClosingBracket))
-        );
+/// The specific type of [`Warning`].
+///
+/// [`Warning`]: struct.Warning.html
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum WarningKind {
+    /// The entry has an empty value.
+    EmptyValue { key: String },
+    /// The key contains whitespace, which may mean a missing `=`.
+    WhitespaceInKey { key: String },
+    /// The key already appeared earlier in the same section.
+    DuplicateKey { key: String },
+    /// A section with the same name appeared earlier in the input.
+    DuplicateSection { name: String },
+    /// Text that is not a comment follows a section header, and was
+    /// ignored.
+    TextAfterSectionHeader { text: String },
+}
+
+/// A problem found while parsing that does not prevent the input from
+/// being parsed.
+///
+/// Produced by [`Conf::parse_str_with_warnings`].
+///
+/// [`Conf::parse_str_with_warnings`]: struct.Conf.html#method.parse_str_with_warnings
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Warning {
+    /// Line where the problem occurs (starting from 1).
+    pub line: usize,
+    /// Type of problem.
+    pub kind: WarningKind,
+}
+
+impl std::fmt::Display for Warning {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "line {}: ", self.line)?;
+        match &self.kind {
+            WarningKind::EmptyValue { key } => write!(f, "empty value for key {:?}", key),
+            WarningKind::WhitespaceInKey { key } => write!(f, "key {:?} contains whitespace", key),
+            WarningKind::DuplicateKey { key } => write!(f, "duplicate key {:?}", key),
+            WarningKind::DuplicateSection { name } => write!(f, "duplicate section {:?}", name),
+            WarningKind::TextAfterSectionHeader { text } => {
+                write!(f, "ignored text {:?} after section header", text)
+            }
+        }
     }
+}
 
-    #[test]
-    fn test_missing_equals() {
+/// How [`Conf::parse_bytes`] handles input that is not validly encoded.
+///
+/// [`Conf::parse_bytes`]: struct.Conf.html#method.parse_bytes
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Decoding {
+    /// Fail with [`ParseErrorKind::InvalidUtf8`] or
+    /// [`ParseErrorKind::InvalidUtf16`].
+    ///
+    /// [`ParseErrorKind::InvalidUtf8`]: enum.ParseErrorKind.html#variant.InvalidUtf8
+    /// [`ParseErrorKind::InvalidUtf16`]: enum.ParseErrorKind.html#variant.InvalidUtf16
+    #[default]
+    Strict,
+    /// Replace invalid sequences with `U+FFFD REPLACEMENT CHARACTER`.
+    Lossy,
+    /// Decode each byte that is not part of a valid UTF-8 sequence as a
+    /// Latin-1 (ISO 8859-1) character. UTF-16 input is decoded as with
+    /// [`Decoding::Lossy`].
+    ///
+    /// [`Decoding::Lossy`]: enum.Decoding.html#variant.Lossy
+    Latin1,
+}
+
+/// Line terminator used when serializing.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum LineEnding {
+    /// `\n`
+    #[default]
+    Lf,
+    /// `\r\n`
+    CrLf,
+}
+
+impl LineEnding {
+    /// The line terminator as a string.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::CrLf => "\r\n",
+        }
+    }
+}
+
+/// Options controlling how a config is serialized.
+///
+/// The default options produce the same output as `to_string`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct WriteOptions {
+    /// Start the output with a UTF-8 byte order mark.
+    pub bom: bool,
+    /// Line terminator to emit.
+    pub line_ending: LineEnding,
+    /// Line terminator to emit instead of `line_ending`, for readers
+    /// that expect some other string.
+    pub line_terminator: Option<String>,
+    /// Write `key = value` rather than `key=value`.
+    pub spaces_around_equals: bool,
+    /// Number of blank lines between sections.
+    pub blank_lines_between_sections: usize,
+    /// Indentation written before each entry.
+    pub indent: String,
+    /// Terminate the last line with a line ending.
+    pub trailing_newline: bool,
+    /// Pad keys so that the `=` signs within each section line up.
+    pub align_equals: bool,
+    /// Write sections ordered by name and entries ordered by key, as
+    /// with [`Conf::sort`].
+    ///
+    /// [`Conf::sort`]: struct.Conf.html#method.sort
+    pub sort: bool,
+    /// Write the first section without a header if its name is empty,
+    /// as read with [`ParseOptions::sectionless`].
+    ///
+    /// [`ParseOptions::sectionless`]: struct.ParseOptions.html#structfield.sectionless
+    pub sectionless: bool,
+}
+
+impl Default for WriteOptions {
+    fn default() -> WriteOptions {
+        WriteOptions {
+            bom: false,
+            line_ending: LineEnding::Lf,
+            line_terminator: None,
+            spaces_around_equals: true,
+            blank_lines_between_sections: 1,
+            indent: String::new(),
+            trailing_newline: true,
+            align_equals: false,
+            sort: false,
+            sectionless: false,
+        }
+    }
+}
+
+impl WriteOptions {
+    /// Create options that reproduce the byte order mark and line
+    /// endings of `source`, typically the text a config was parsed from.
+    ///
+    /// The line ending is taken from the first line of `source`.
+    pub fn detect(source: &str) -> WriteOptions {
+        let line_ending = match source.find('\n') {
+            Some(index) if source[..index].ends_with('\r') => LineEnding::CrLf,
+            _ => LineEnding::Lf,
+        };
+        WriteOptions {
+            bom: source.starts_with('\u{feff}'),
+            line_ending,
+            ..Default::default()
+        }
+    }
+
+    /// The line terminator these options emit.
+    pub(crate) fn eol(&self) -> &str {
+        self.line_terminator
+            .as_deref()
+            .unwrap_or_else(|| self.line_ending.as_str())
+    }
+}
+
+/// How [`Conf::merge`] combines two configs.
+///
+/// [`Conf::merge`]: struct.Conf.html#method.merge
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum MergePolicy {
+    /// Values from the other config replace existing values.
+    #[default]
+    Override,
+    /// Existing values are kept; only missing sections and keys are
+    /// added.
+    KeepExisting,
+    /// The other config's sections are appended unchanged, keeping any
+    /// duplicates.
+    Append,
+}
+
+/// How [`Section::dedup_keys`] and [`Conf::dedup_sections`] handle
+/// repeats.
+///
+/// [`Section::dedup_keys`]: struct.Section.html#method.dedup_keys
+/// [`Conf::dedup_sections`]: struct.Conf.html#method.dedup_sections
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum DedupPolicy {
+    /// Keep the first occurrence.
+    KeepFirst,
+    /// Keep the last occurrence, at its own position.
+    KeepLast,
+    /// Combine every occurrence into the first. Repeated keys have their
+    /// values joined with `, `. Repeated sections have their entries set
+    /// in the first section, so later values replace earlier ones.
+    Merge,
+}
+
+/// A section name used by more than one section.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DuplicateSection {
+    /// Name of the sections.
+    pub name: String,
+    /// Indices into [`Conf::sections`] of every section with the name.
+    ///
+    /// [`Conf::sections`]: struct.Conf.html#structfield.sections
+    pub indices: Vec<usize>,
+}
+
+/// A key used by more than one entry in a section.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DuplicateKey {
+    /// Index into [`Conf::sections`] of the section.
+    ///
+    /// [`Conf::sections`]: struct.Conf.html#structfield.sections
+    pub section: usize,
+    /// Repeated key.
+    pub key: String,
+    /// Indices into [`Section::entries`] of every entry with the key.
+    ///
+    /// [`Section::entries`]: struct.Section.html#structfield.entries
+    pub indices: Vec<usize>,
+}
+
+/// Repeated sections and keys, as found by [`Conf::find_duplicates`].
+///
+/// [`Conf::find_duplicates`]: struct.Conf.html#method.find_duplicates
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct DuplicateReport {
+    /// Repeated section names, in order of first use.
+    pub sections: Vec<DuplicateSection>,
+    /// Keys repeated within a section, in order of section and then of
+    /// first use.
+    pub keys: Vec<DuplicateKey>,
+}
+
+impl DuplicateReport {
+    /// Whether nothing is repeated.
+    pub fn is_empty(&self) -> bool {
+        self.sections.is_empty() && self.keys.is_empty()
+    }
+}
+
+/// One of the entries with a key in a section, as returned by
+/// [`Section::occurrences`].
+///
+/// Lookups such as [`Section::get`] use the first entry with a key, so
+/// that entry takes effect and shadows the others.
+///
+/// [`Section::occurrences`]: struct.Section.html#method.occurrences
+/// [`Section::get`]: struct.Section.html#method.get
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Occurrence<'a> {
+    /// Index into [`Section::entries`] of the entry.
+    ///
+    /// [`Section::entries`]: struct.Section.html#structfield.entries
+    pub index: usize,
+    /// Number of earlier entries with the key.
+    pub occurrence: usize,
+    /// Number of entries with the key.
+    pub count: usize,
+    /// The entry.
+    pub entry: &'a Entry,
+}
+
+impl Occurrence<'_> {
+    /// Whether this is the entry that lookups return.
+    pub fn takes_effect(&self) -> bool {
+        self.occurrence == 0
+    }
+
+    /// Whether this entry hides later entries with the same key.
+    pub fn shadows(&self) -> bool {
+        self.occurrence == 0 && self.count > 1
+    }
+
+    /// Whether an earlier entry with the same key hides this one.
+    pub fn is_shadowed(&self) -> bool {
+        self.occurrence > 0
+    }
+}
+
+/// A single entry within the section.
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Entry {
+    /// Name of the entry.
+    pub key: String,
+    /// Value of the entry.
+    pub value: String,
+}
+
+impl Entry {
+    pub fn new(key: &str, value: &str) -> Entry {
+        Entry {
+            key: key.to_string(),
+            value: value.to_string(),
+        }
+    }
+}
+
+impl std::fmt::Display for Entry {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{} = {}", self.key, self.value)
+    }
+}
+
+/// A view into the entry for a key in a section, which may be vacant.
+///
+/// Returned by [`Section::entry`].
+///
+/// [`Section::entry`]: struct.Section.html#method.entry
+#[derive(Debug)]
+pub enum KeyEntry<'a> {
+    /// The first entry with the key.
+    Occupied(&'a mut Entry),
+    /// There is no entry with the key.
+    Vacant(VacantEntry<'a>),
+}
+
+impl<'a> KeyEntry<'a> {
+    /// Get the entry's value, inserting `default` at the end of the
+    /// section if it is vacant.
+    pub fn or_insert(self, default: &str) -> &'a mut String {
+        self.or_insert_with(|| default.to_string())
+    }
+
+    /// Get the entry's value, inserting the result of `default` at the
+    /// end of the section if it is vacant.
+    pub fn or_insert_with<F: FnOnce() -> String>(self, default: F) -> &'a mut String {
+        match self {
+            KeyEntry::Occupied(entry) => &mut entry.value,
+            KeyEntry::Vacant(vacant) => vacant.insert(default()),
+        }
+    }
+
+    /// Call `f` with the entry's value if it is occupied.
+    pub fn and_modify<F: FnOnce(&mut String)>(self, f: F) -> KeyEntry<'a> {
+        match self {
+            KeyEntry::Occupied(entry) => {
+                f(&mut entry.value);
+                KeyEntry::Occupied(entry)
+            }
+            KeyEntry::Vacant(vacant) => KeyEntry::Vacant(vacant),
+        }
+    }
+}
+
+/// A key with no entry in a section.
+#[derive(Debug)]
+pub struct VacantEntry<'a> {
+    entries: &'a mut Vec<Entry>,
+    key: &'a str,
+}
+
+impl<'a> VacantEntry<'a> {
+    /// The key that has no entry.
+    pub fn key(&self) -> &str {
+        self.key
+    }
+
+    /// Add an entry with `value` at the end of the section.
+    pub fn insert(self, value: String) -> &'a mut String {
+        self.entries.push(Entry {
+            key: self.key.to_string(),
+            value,
+        });
+        &mut self.entries.last_mut().unwrap().value
+    }
+}
+
+/// A named section within the config.
+#[derive(Clone, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Section {
+    /// Name of the section.
+    pub name: String,
+    /// Entries within the section.
+    pub entries: Vec<Entry>,
+}
+
+impl Section {
+    pub fn new(name: &str) -> Section {
+        Section {
+            name: name.to_string(),
+            entries: Vec::new(),
+        }
+    }
+
+    /// Create an empty section with room for `entries` entries.
+    pub fn with_capacity(name: &str, entries: usize) -> Section {
+        Section {
+            name: name.to_string(),
+            entries: Vec::with_capacity(entries),
+        }
+    }
+
+    pub fn new_with_entries(name: &str, entries: Vec<Entry>) -> Section {
+        Section {
+            name: name.to_string(),
+            entries,
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.entries.iter().find(|e| e.key == key).map(|e| e.value.as_str())
+    }
+
+    /// Every entry with `key`, in order.
+    pub fn occurrences(&self, key: &str) -> Vec<Occurrence<'_>> {
+        let indices: Vec<usize> = (0..self.entries.len())
+            .filter(|&i| self.entries[i].key == key)
+            .collect();
+        indices
+            .iter()
+            .enumerate()
+            .map(|(occurrence, &index)| Occurrence {
+                index,
+                occurrence,
+                count: indices.len(),
+                entry: &self.entries[index],
+            })
+            .collect()
+    }
+
+    /// Get the value of the first entry with `key`, or `default` if there
+    /// is none.
+    pub fn get_or<'a>(&'a self, key: &str, default: &'a str) -> &'a str {
+        self.get(key).unwrap_or(default)
+    }
+
+    /// Get the value of `key` translated for `locale`, such as `de_DE`.
+    /// This tries `key[de_DE]`, then `key[de]`, then `key` itself.
+    ///
+    /// An encoding in `locale`, as in `de_DE.UTF-8`, is ignored. With a
+    /// modifier, as in `sr_RS@latin`, `key[sr_RS@latin]` and
+    /// `key[sr@latin]` are tried before the keys without it.
+    pub fn get_localized(&self, key: &str, locale: &str) -> Option<&str> {
+        let (locale, modifier) = match locale.split_once('@') {
+            Some((locale, modifier)) => (locale, Some(modifier)),
+            None => (locale, None),
+        };
+        let locale = locale.split('.').next().unwrap_or_default();
+        let lang = locale.split('_').next().unwrap_or_default();
+        let mut suffixes = Vec::new();
+        for name in [locale, lang].iter().filter(|name| !name.is_empty()) {
+            if let Some(modifier) = modifier {
+                suffixes.push(format!("{}@{}", name, modifier));
+            }
+            suffixes.push(name.to_string());
+        }
+        suffixes.dedup();
+        suffixes
+            .iter()
+            .find_map(|suffix| self.get(&format!("{}[{}]", key, suffix)))
+            .or_else(|| self.get(key))
+    }
+
+    /// Get the value of the first entry with `key`, first adding an entry
+    /// with the value returned by `f` if there is none.
+    pub fn get_or_insert_with<F: FnOnce() -> String>(&mut self, key: &str, f: F) -> &str {
+        let index = match self.entries.iter().position(|e| e.key == key) {
+            Some(index) => index,
+            None => {
+                self.entries.push(Entry::new(key, &f()));
+                self.entries.len() - 1
+            }
+        };
+        &self.entries[index].value
+    }
+
+    /// Get the value of the first entry with `key` for editing in place.
+    pub fn get_mut(&mut self, key: &str) -> Option<&mut String> {
+        self.entries
+            .iter_mut()
+            .find(|e| e.key == key)
+            .map(|e| &mut e.value)
+    }
+
+    /// Number of entries.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Make room for at least `additional` more entries.
+    pub fn reserve(&mut self, additional: usize) {
+        self.entries.reserve(additional);
+    }
+
+    /// Free unused room for entries.
+    pub fn shrink_to_fit(&mut self) {
+        self.entries.shrink_to_fit();
+    }
+
+    /// Whether there are no entries.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Name of the parent section in a tree of dotted names, which is
+    /// everything before the last dot.
+    ///
+    /// See the [`tree`] module.
+    ///
+    /// [`tree`]: tree/index.html
+    pub fn parent(&self) -> Option<&str> {
+        self.name.rfind('.').map(|dot| &self.name[..dot])
+    }
+
+    /// Iterate over the entries.
+    pub fn iter(&self) -> std::slice::Iter<'_, Entry> {
+        self.entries.iter()
+    }
+
+    /// Iterate over the entries, allowing changes.
+    pub fn iter_mut(&mut self) -> std::slice::IterMut<'_, Entry> {
+        self.entries.iter_mut()
+    }
+
+    /// Rename every entry with key `old` to `new`, keeping its position.
+    /// Returns whether any entry was renamed.
+    pub fn rename_key(&mut self, old: &str, new: &str) -> bool {
+        let mut changed = false;
+        for entry in self.entries.iter_mut().filter(|e| e.key == old) {
+            entry.key = new.to_string();
+            changed = true;
+        }
+        changed
+    }
+
+    /// Sort the entries with `compare`. The sort is stable.
+    pub fn sort_entries_by<F>(&mut self, compare: F)
+    where
+        F: FnMut(&Entry, &Entry) -> std::cmp::Ordering,
+    {
+        self.entries.sort_by(compare)
+    }
+
+    /// Remove repeated keys according to `policy`.
+    pub fn dedup_keys(&mut self, policy: DedupPolicy) {
+        dedup_by(
+            &mut self.entries,
+            policy,
+            |a, b| a.key == b.key,
+            |first, repeat| {
+                first.value += ", ";
+                first.value += &repeat.value;
+            },
+        );
+    }
+
+    /// Replace each value with the result of calling `f` with its key
+    /// and value.
+    pub fn map_values<F: FnMut(&str, &str) -> String>(&mut self, mut f: F) {
+        for entry in self.entries.iter_mut() {
+            entry.value = f(&entry.key, &entry.value);
+        }
+    }
+
+    /// Keep only the entries for which `f` returns true.
+    pub fn retain<F: FnMut(&Entry) -> bool>(&mut self, f: F) {
+        self.entries.retain(f)
+    }
+
+    /// Check that the section would read back the same after being
+    /// written, as [`Conf::try_to_string_styled`] does.
+    ///
+    /// [`Conf::try_to_string_styled`]: struct.Conf.html#method.try_to_string_styled
+    pub fn check_serializable(&self) -> Result<(), SerializeError> {
+        check_section_name(&self.name)?;
+        self.check_entries_serializable()
+    }
+
+    /// Check that the entries would read back the same after being
+    /// written.
+    pub(crate) fn check_entries_serializable(&self) -> Result<(), SerializeError> {
+        let error = |kind| SerializeError {
+            section: self.name.clone(),
+            kind,
+        };
+        if self.entries.iter().any(|e| e.key.is_empty()) {
+            return Err(error(SerializeErrorKind::EmptyKey));
+        }
+        for entry in self.entries.iter() {
+            check_entry(&self.name, &entry.key, &entry.value)?;
+        }
+        Ok(())
+    }
+
+    /// Get the first entry with `key` for in-place changes, or a vacant
+    /// entry if there is none.
+    pub fn entry<'a>(&'a mut self, key: &'a str) -> KeyEntry<'a> {
+        match self.entries.iter().position(|e| e.key == key) {
+            Some(index) => KeyEntry::Occupied(&mut self.entries[index]),
+            None => KeyEntry::Vacant(VacantEntry {
+                entries: &mut self.entries,
+                key,
+            }),
+        }
+    }
+
+    /// Set `key` to `value`. The first entry with `key` is updated, or a
+    /// new entry is added at the end if there is none.
+    pub fn set(&mut self, key: &str, value: &str) {
+        match self.entries.iter_mut().find(|e| e.key == key) {
+            Some(entry) => entry.value = value.to_string(),
+            None => self.entries.push(Entry::new(key, value)),
+        }
+    }
+
+    /// Set `key` to `value` as by [`set`], if `key` follows `policy`.
+    ///
+    /// [`set`]: #method.set
+    pub fn set_checked(
+        &mut self,
+        key: &str,
+        value: &str,
+        policy: &KeyPolicy,
+    ) -> Result<(), KeyViolation> {
+        policy.check(key)?;
+        self.set(key, value);
+        Ok(())
+    }
+}
+
+impl std::ops::Index<&str> for Section {
+    type Output = str;
+
+    /// Get the value of the first entry with `key`.
+    ///
+    /// Panics if there is no such entry.
+    fn index(&self, key: &str) -> &str {
+        match self.get(key) {
+            Some(value) => value,
+            None => panic!("no key {:?} in section {:?}", key, self.name),
+        }
+    }
+}
+
+impl IntoIterator for Section {
+    type Item = Entry;
+    type IntoIter = std::vec::IntoIter<Entry>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a Section {
+    type Item = &'a Entry;
+    type IntoIter = std::slice::Iter<'a, Entry>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a mut Section {
+    type Item = &'a mut Entry;
+    type IntoIter = std::slice::IterMut<'a, Entry>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.iter_mut()
+    }
+}
+
+impl std::fmt::Display for Section {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "[{}]", self.name)?;
+        for entry in self.entries.iter() {
+            write!(f, "\n{}", entry)?;
+        }
+        writeln!(f)
+    }
+}
+
+/// Create a [`Conf`] from sections and entries written inline.
+///
+/// Section names and keys are identifiers or string literals. Values
+/// are expressions, converted with `ToString`.
+///
+/// Example:
+///
+/// ```
+/// let conf = nbconf::conf! {
+///     [server]
+///     port = 8080;
+///     host = "::";
+///     ["log files"]
+///     "max size" = "10MB";
+/// };
+/// assert_eq!(
+///     conf.to_string(),
+///     "[server]\nport = 8080\nhost = ::\n\n[log files]\nmax size = 10MB\n"
+/// );
+/// ```
+///
+/// An entry before the first section does not compile:
+///
+/// ```compile_fail
+/// let conf = nbconf::conf! { port = 8080; [server] };
+/// ```
+///
+/// [`Conf`]: struct.Conf.html
+#[macro_export]
+macro_rules! conf {
+    ($($body:tt)*) => {{
+        #[allow(unused_mut)]
+        let mut conf = $crate::Conf::new();
+        $crate::__conf_body!(@start conf; $($body)*);
+        conf
+    }};
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __conf_body {
+    (@start $conf:ident;) => {};
+    (@start $conf:ident; [$name:tt] $($rest:tt)*) => {
+        $conf.sections.push($crate::Section::new($crate::__conf_name!($name)));
+        $crate::__conf_body!(@entries $conf; $($rest)*);
+    };
+    (@entries $conf:ident;) => {};
+    (@entries $conf:ident; [$name:tt] $($rest:tt)*) => {
+        $crate::__conf_body!(@start $conf; [$name] $($rest)*);
+    };
+    (@entries $conf:ident; $key:tt = $value:expr; $($rest:tt)*) => {
+        $conf.sections.last_mut().unwrap().entries.push($crate::Entry::new(
+            $crate::__conf_name!($key),
+            &::std::string::ToString::to_string(&$value),
+        ));
+        $crate::__conf_body!(@entries $conf; $($rest)*);
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __conf_name {
+    ($name:ident) => {
+        stringify!($name)
+    };
+    ($name:literal) => {
+        $name
+    };
+}
+
+/// Builder for a [`Conf`].
+///
+/// Example:
+///
+/// ```
+/// let conf = nbconf::ConfBuilder::new()
+///     .section("server")
+///     .entry("port", "8080")
+///     .entry("host", "::")
+///     .section("log")
+///     .build();
+/// assert_eq!(conf.to_string(), "[server]\nport = 8080\nhost = ::\n\n[log]\n");
+/// ```
+///
+/// [`Conf`]: struct.Conf.html
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ConfBuilder {
+    conf: Conf,
+}
+
+impl ConfBuilder {
+    /// Create a builder for an empty config.
+    pub fn new() -> ConfBuilder {
+        ConfBuilder::default()
+    }
+
+    /// Start a new section. Later entries are added to it.
+    pub fn section(mut self, name: &str) -> ConfBuilder {
+        self.conf.sections.push(Section::new(name));
+        self
+    }
+
+    /// Add an entry to the current section.
+    ///
+    /// Panics if no section has been started.
+    pub fn entry(mut self, key: &str, value: &str) -> ConfBuilder {
+        match self.conf.sections.last_mut() {
+            Some(section) => section.entries.push(Entry::new(key, value)),
+            None => panic!("entry {:?} added before any section", key),
+        }
+        self
+    }
+
+    /// Finish building the config.
+    pub fn build(self) -> Conf {
+        self.conf
+    }
+}
+
+/// A config in a normal form where section and entry order do not
+/// matter, for comparing and hashing.
+///
+/// Two configs are equal when normalized if they have the same sections
+/// with the same entries, in any order, except that the values of a
+/// repeated key must be in the same order. See [`Conf::semantic_eq`].
+///
+/// [`Conf::semantic_eq`]: struct.Conf.html#method.semantic_eq
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Normalized(Conf);
+
+impl Normalized {
+    /// Normalize `conf`.
+    pub fn new(mut conf: Conf) -> Normalized {
+        for section in conf.sections.iter_mut() {
+            section.sort_entries_by(|a, b| a.key.cmp(&b.key));
+        }
+        conf.sections.sort();
+        Normalized(conf)
+    }
+
+    /// Get the normalized config, which has its sections sorted and its
+    /// entries sorted by key.
+    pub fn into_inner(self) -> Conf {
+        self.0
+    }
+}
+
+/// A shared, immutable config that is cheap to clone, for handing to
+/// many readers at once.
+///
+/// Created with [`Conf::freeze`]. It dereferences to [`Conf`] for
+/// reading.
+///
+/// [`Conf::freeze`]: struct.Conf.html#method.freeze
+/// [`Conf`]: struct.Conf.html
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
+pub struct ConfSnapshot(std::sync::Arc<Conf>);
+
+impl ConfSnapshot {
+    /// Get an editable config with the snapshot's contents. The config
+    /// is only copied if other clones of the snapshot still exist.
+    pub fn thaw(self) -> Conf {
+        std::sync::Arc::try_unwrap(self.0).unwrap_or_else(|shared| (*shared).clone())
+    }
+}
+
+impl std::ops::Deref for ConfSnapshot {
+    type Target = Conf;
+
+    fn deref(&self) -> &Conf {
+        &self.0
+    }
+}
+
+/// A config whose sections are shared between clones until they are
+/// changed, for making variants of a large config cheaply.
+///
+/// Cloning a `CowConf` only clones a pointer to each section. Changing a
+/// section copies that section alone, leaving the other clones and the
+/// other sections as they are.
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
+pub struct CowConf {
+    sections: Vec<std::sync::Arc<Section>>,
+}
+
+impl CowConf {
+    /// Number of sections.
+    pub fn len(&self) -> usize {
+        self.sections.len()
+    }
+
+    /// Whether there are no sections.
+    pub fn is_empty(&self) -> bool {
+        self.sections.is_empty()
+    }
+
+    /// Iterate over the sections.
+    pub fn iter(&self) -> impl Iterator<Item = &Section> {
+        self.sections.iter().map(|s| &**s)
+    }
+
+    /// Get the value of `key` in the first section named `section` that
+    /// has it.
+    pub fn get(&self, section: &str, key: &str) -> Option<&str> {
+        self.iter()
+            .filter(|s| s.name == section)
+            .find_map(|s| s.get(key))
+    }
+
+    /// Get a value by its dotted path, as [`Conf::get_path`] does.
+    ///
+    /// [`Conf::get_path`]: struct.Conf.html#method.get_path
+    pub fn get_path(&self, path: &str) -> Option<&str> {
+        split_path(path).find_map(|(section, key)| self.get(section, key))
+    }
+
+    /// Get the first section named `name` for editing, copying it first
+    /// if it is shared.
+    pub fn section_mut(&mut self, name: &str) -> Option<&mut Section> {
+        self.sections
+            .iter_mut()
+            .find(|s| s.name == name)
+            .map(std::sync::Arc::make_mut)
+    }
+
+    /// Set `key` in the first section named `section` that has it, or
+    /// else in the first section named `section`, adding the section if
+    /// there is none. Only that section is copied.
+    pub fn set(&mut self, section: &str, key: &str, value: &str) {
+        let position = self
+            .sections
+            .iter()
+            .position(|s| s.name == section && s.get(key).is_some())
+            .or_else(|| self.sections.iter().position(|s| s.name == section));
+        let index = match position {
+            Some(index) => index,
+            None => {
+                self.sections
+                    .push(std::sync::Arc::new(Section::new(section)));
+                self.sections.len() - 1
+            }
+        };
+        std::sync::Arc::make_mut(&mut self.sections[index]).set(key, value);
+    }
+
+    /// Add a section at the end.
+    pub fn push(&mut self, section: Section) {
+        self.sections.push(std::sync::Arc::new(section));
+    }
+
+    /// Remove the sections for which `f` returns false.
+    pub fn retain_sections<F: FnMut(&Section) -> bool>(&mut self, mut f: F) {
+        self.sections.retain(|s| f(s));
+    }
+
+    /// Get a plain config with the same sections. Sections are only
+    /// copied if other clones still share them.
+    pub fn into_conf(self) -> Conf {
+        let sections = self
+            .sections
+            .into_iter()
+            .map(|s| std::sync::Arc::try_unwrap(s).unwrap_or_else(|shared| (*shared).clone()))
+            .collect();
+        Conf { sections }
+    }
+}
+
+impl From<Conf> for CowConf {
+    fn from(conf: Conf) -> CowConf {
+        CowConf {
+            sections: conf.sections.into_iter().map(std::sync::Arc::new).collect(),
+        }
+    }
+}
+
+/// A collection of config sections.
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
+pub struct Conf {
+    pub sections: Vec<Section>,
+}
+
+impl Conf {
+    /// Create an empty config.
+    pub fn new() -> Conf {
+        Conf {
+            sections: Vec::new(),
+        }
+    }
+
+    /// Create an empty config with room for `sections` sections.
+    pub fn with_capacity(sections: usize) -> Conf {
+        Conf {
+            sections: Vec::with_capacity(sections),
+        }
+    }
+
+    /// Create a pre-populated config.
+    pub fn from_sections(sections: Vec<Section>) -> Conf {
+        Conf { sections }
+    }
+
+    /// Parse a string into a config, naming the input in any error.
+    ///
+    /// This is the same as [`parse_str`], except that the returned
+    /// [`ParseError`] has its `source_name` set to `name`.
+    ///
+    /// [`parse_str`]: #method.parse_str
+    /// [`ParseError`]: struct.ParseError.html
+    pub fn parse_str_named(name: &str, s: &str) -> Result<Conf, ParseError> {
+        Conf::parse_str(s).map_err(|err| err.with_source_name(name))
+    }
+
+    /// Parse a string into a config.
+    pub fn parse_str(s: &str) -> Result<Conf, ParseError> {
+        Conf::parse_str_with(s, &ParseOptions::default())
+    }
+
+    /// Parse a string into a config, also returning problems that did
+    /// not prevent parsing, such as duplicate keys or empty values.
+    pub fn parse_str_with_warnings(s: &str) -> Result<(Conf, Vec<Warning>), ParseError> {
+        Conf::parse(s, &ParseOptions::default(), true)
+    }
+
+    /// Parse a string into a config, with stricter checks enabled by
+    /// `options`.
+    pub fn parse_str_with(s: &str, options: &ParseOptions) -> Result<Conf, ParseError> {
+        Conf::parse(s, options, false).map(|(conf, _)| conf)
+    }
+
+    /// Parse `s`, also returning warnings if `collect_warnings` is set.
+    /// Callers that discard the warnings leave it unset, so that the
+    /// duplicate checks and warning text are skipped.
+    fn parse(
+        s: &str,
+        options: &ParseOptions,
+        collect_warnings: bool,
+    ) -> Result<(Conf, Vec<Warning>), ParseError> {
+        let mut warnings = Vec::new();
+        let mut section_names = HashSet::new();
+        let mut section_keys = HashSet::new();
+        let limits = &options.limits;
+        let exceeds = |limit: Option<usize>, value| limit.filter(|limit| value > *limit);
+        if let Some(limit) = exceeds(limits.max_input_size, s.len()) {
+            return Err(ParseError::new(1, ParseErrorKind::InputTooLarge { limit }));
+        }
+        let s = s.strip_prefix('\u{feff}').unwrap_or(s);
+        // Count the headers first so that the sections are allocated once.
+        let headers = s
+            .lines()
+            .filter(|line| line.trim_start().starts_with('['))
+            .count();
+        let mut conf = Conf::with_capacity(headers.min(limits.max_sections.unwrap_or(usize::MAX)));
+        for (index, line) in s.lines().enumerate() {
+            let line_no = index + 1;
+            let mut warn = |kind| {
+                warnings.push(Warning {
+                    line: line_no,
+                    kind,
+                })
+            };
+            if let Some(limit) = exceeds(limits.max_line_length, line.len()) {
+                return Err(ParseError::new(line_no, ParseErrorKind::LineTooLong { limit }));
+            }
+            let raw_line = line;
+            let line = line.trim();
+            if line.is_empty() || is_comment(line) {
+                continue;
+            }
+            if options.reject_control_chars {
+                if let Some(index) = line.find(is_control) {
+                    let start = raw_line.len() - raw_line.trim_start().len();
+                    let column = raw_line[..start + index].chars().count() + 1;
+                    return Err(ParseError::new(
+                        line_no,
+                        ParseErrorKind::ControlCharacter { column },
+                    ));
+                }
+            }
+            if line.starts_with('[') {
+                if let Some((name, text)) = split_header(line) {
+                    if !text.is_empty() && !is_comment(text) {
+                        if options.reject_text_after_section_headers {
+                            return Err(ParseError::new(
+                                line_no,
+                                ParseErrorKind::TextAfterSectionHeader,
+                            ));
+                        }
+                        if collect_warnings {
+                            warn(WarningKind::TextAfterSectionHeader {
+                                text: text.to_string(),
+                            });
+                        }
+                    }
+                    if name.trim().is_empty() && options.reject_empty_section_names {
+                        return Err(ParseError::new(
+                            line_no,
+                            ParseErrorKind::EmptySectionName,
+                        ));
+                    }
+                    if collect_warnings && !section_names.insert(name) {
+                        warn(WarningKind::DuplicateSection {
+                            name: name.to_string(),
+                        });
+                    }
+                    if let Some(limit) = exceeds(limits.max_sections, conf.sections.len() + 1) {
+                        return Err(ParseError::new(
+                            line_no,
+                            ParseErrorKind::TooManySections { limit },
+                        ));
+                    }
+                    section_keys.clear();
+                    conf.sections.push(Section::new(name));
+                } else {
+                    return Err(ParseError::new(
+                        line_no,
+                        ParseErrorKind::MissingClosingBracket,
+                    ));
+                }
+            } else if let Some(equals) = line.find('=') {
+                if conf.sections.is_empty() && options.sectionless {
+                    if let Some(limit) = exceeds(limits.max_sections, 1) {
+                        return Err(ParseError::new(
+                            line_no,
+                            ParseErrorKind::TooManySections { limit },
+                        ));
+                    }
+                    section_names.insert("");
+                    conf.sections.push(Section::new(""));
+                }
+                if let Some(section) = conf.sections.last_mut() {
+                    let count = section.entries.len() + 1;
+                    if let Some(limit) = exceeds(limits.max_entries_per_section, count) {
+                        return Err(ParseError::new(
+                            line_no,
+                            ParseErrorKind::TooManyEntries { limit },
+                        ));
+                    }
+                    let key = line[..equals].trim_end();
+                    let value = line[equals + 1..].trim_start();
+                    if key.is_empty() && options.reject_empty_keys {
+                        return Err(ParseError::new(line_no, ParseErrorKind::EmptyKey));
+                    }
+                    if let Some(policy) = &options.key_policy {
+                        if let Err(violation) = policy.check(key) {
+                            return Err(ParseError::new(
+                                line_no,
+                                ParseErrorKind::InvalidKeyName { violation },
+                            ));
+                        }
+                    }
+                    if collect_warnings {
+                        if key.contains(char::is_whitespace) {
+                            warn(WarningKind::WhitespaceInKey {
+                                key: key.to_string(),
+                            });
+                        }
+                        if !section_keys.insert(key) {
+                            warn(WarningKind::DuplicateKey {
+                                key: key.to_string(),
+                            });
+                        }
+                        if value.is_empty() {
+                            warn(WarningKind::EmptyValue {
+                                key: key.to_string(),
+                            });
+                        }
+                    }
+                    section.entries.push(Entry::new(key, value));
+                } else {
+                    return Err(ParseError::new(
+                        line_no,
+                        ParseErrorKind::EntryOutsideOfSection,
+                    ));
+                }
+            } else {
+                return Err(ParseError::new(line_no, ParseErrorKind::MissingEquals));
+            }
+        }
+        Ok((conf, warnings))
+    }
+
+    /// Parse raw bytes into a config.
+    ///
+    /// The bytes are decoded as UTF-16 if they start with a UTF-16 byte
+    /// order mark (either endianness), and as UTF-8 otherwise.
+    pub fn parse_bytes(bytes: &[u8], decoding: Decoding) -> Result<Conf, ParseError> {
+        Conf::parse_str(&decode(bytes, decoding)?)
+    }
+
+    /// Read and parse the file at `path`. It is decoded as by
+    /// [`parse_bytes`] with [`Decoding::Strict`].
+    ///
+    /// [`parse_bytes`]: #method.parse_bytes
+    /// [`Decoding::Strict`]: enum.Decoding.html#variant.Strict
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Conf, LoadError> {
+        let path = path.as_ref();
+        let load = || -> Result<Conf, LoadError> {
+            let bytes = std::fs::read(path)?;
+            Ok(Conf::parse_bytes(&bytes, Decoding::Strict)?)
+        };
+        load().map_err(|err| err.with_path(path))
+    }
+
+    /// Read and parse the file at `path` as by [`load`], with stricter
+    /// checks enabled by `options`.
+    ///
+    /// With [`ParseLimits::max_input_size`] set, no more of the file than
+    /// the limit is read.
+    ///
+    /// [`load`]: #method.load
+    /// [`ParseLimits::max_input_size`]: struct.ParseLimits.html#structfield.max_input_size
+    pub fn load_with<P: AsRef<Path>>(path: P, options: &ParseOptions) -> Result<Conf, LoadError> {
+        use std::io::Read;
+
+        let path = path.as_ref();
+        let load = || -> Result<Conf, LoadError> {
+            let mut file = std::fs::File::open(path)?;
+            let mut bytes = Vec::new();
+            match options.limits.max_input_size {
+                Some(limit) => file.take(limit as u64 + 1).read_to_end(&mut bytes)?,
+                None => file.read_to_end(&mut bytes)?,
+            };
+            Ok(Conf::parse_str_with(&decode(&bytes, Decoding::Strict)?, options)?)
+        };
+        load().map_err(|err| err.with_path(path))
+    }
+
+    /// Read and parse the file at `path` as by [`load`], also returning
+    /// problems that did not prevent parsing.
+    ///
+    /// [`load`]: #method.load
+    pub fn load_with_warnings<P: AsRef<Path>>(
+        path: P,
+    ) -> Result<(Conf, Vec<Warning>), LoadError> {
+        let path = path.as_ref();
+        let load = || -> Result<(Conf, Vec<Warning>), LoadError> {
+            let bytes = std::fs::read(path)?;
+            Ok(Conf::parse_str_with_warnings(&decode(&bytes, Decoding::Strict)?)?)
+        };
+        load().map_err(|err| err.with_path(path))
+    }
+
+    /// Serialize the config as a string using the given options.
+    pub fn to_string_styled(&self, options: &WriteOptions) -> String {
+        let document = write::DocumentView {
+            sections: self
+                .sections
+                .iter()
+                .map(|section| write::SectionView {
+                    comments: Vec::new(),
+                    name: &section.name,
+                    entries: section
+                        .entries
+                        .iter()
+                        .map(|entry| write::EntryView {
+                            comments: Vec::new(),
+                            key: &entry.key,
+                            value: &entry.value,
+                        })
+                        .collect(),
+                })
+                .collect(),
+            ..Default::default()
+        };
+        write::write(document, options)
+    }
+
+    /// Serialize the config as a string using the given options, failing
+    /// if it contains entries that would not read back the same, such as
+    /// entries with an empty key.
+    pub fn try_to_string_styled(&self, options: &WriteOptions) -> Result<String, SerializeError> {
+        for (index, section) in self.sections.iter().enumerate() {
+            if index == 0 && options.sectionless && section.name.is_empty() {
+                section.check_entries_serializable()?;
+            } else {
+                section.check_serializable()?;
+            }
+        }
+        Ok(self.to_string_styled(options))
+    }
+
+    /// Serialize the config into `writer` using the given options. Fails
+    /// with `InvalidInput` if the config cannot be serialized, as in
+    /// [`try_to_string_styled`].
+    ///
+    /// [`try_to_string_styled`]: #method.try_to_string_styled
+    pub fn write_to<W: io::Write>(&self, writer: &mut W, options: &WriteOptions) -> io::Result<()> {
+        writer.write_all(self.try_to_string_styled(options)?.as_bytes())
+    }
+
+    /// Write the sections into separate files in `dir`, which is created
+    /// if needed. `file_name` gives the name of the file for each
+    /// section. Sections with the same file name are written to that
+    /// file together, in order.
+    ///
+    /// Returns the paths of the files written, in order of their first
+    /// section. Fails with `InvalidInput` if a file name is not a plain
+    /// file name.
+    ///
+    /// Example:
+    ///
+    /// ```no_run
+    /// # let conf = nbconf::Conf::new();
+    /// let options = nbconf::WriteOptions::default();
+    /// conf.write_split("conf.d".as_ref(), |s| format!("{}.conf", s.name), &options)?;
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    pub fn write_split<F: FnMut(&Section) -> String>(
+        &self,
+        dir: &Path,
+        mut file_name: F,
+        options: &WriteOptions,
+    ) -> io::Result<Vec<PathBuf>> {
+        let mut files: Vec<(String, Conf)> = Vec::new();
+        for section in self.sections.iter() {
+            let name = file_name(section);
+            if Path::new(&name).file_name() != Some(name.as_ref()) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("invalid file name {:?}", name),
+                ));
+            }
+            match files.iter_mut().find(|(n, _)| *n == name) {
+                Some((_, conf)) => conf.sections.push(section.clone()),
+                None => files.push((name, Conf::from_sections(vec![section.clone()]))),
+            }
+        }
+
+        let files = files
+            .into_iter()
+            .map(|(name, conf)| Ok((name, conf.try_to_string_styled(options)?)))
+            .collect::<Result<Vec<_>, SerializeError>>()?;
+
+        std::fs::create_dir_all(dir)?;
+        let mut paths = Vec::new();
+        for (name, text) in files {
+            let path = dir.join(name);
+            std::fs::write(&path, text)?;
+            paths.push(path);
+        }
+        Ok(paths)
+    }
+
+    /// Turn the config into a [`ConfSnapshot`] that can be shared
+    /// between threads.
+    ///
+    /// [`ConfSnapshot`]: struct.ConfSnapshot.html
+    pub fn freeze(self) -> ConfSnapshot {
+        ConfSnapshot(std::sync::Arc::new(self))
+    }
+
+    /// Apply the edits made by `f` only if it succeeds.
+    ///
+    /// `f` edits a copy of the config. If it returns `Ok`, the copy
+    /// replaces the config; if it returns `Err`, the copy is dropped and
+    /// the config is unchanged.
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// let mut conf = nbconf::Conf::parse_str("[server]\nport = 80").unwrap();
+    /// let result = conf.transaction(|tx| {
+    ///     tx.sections[0].set("port", "8080");
+    ///     tx.sections[0].get_port("host")
+    /// });
+    /// assert!(result.is_err());
+    /// assert_eq!(conf.get_path("server.port"), Some("80"));
+    /// ```
+    pub fn transaction<T, E, F>(&mut self, f: F) -> Result<T, E>
+    where
+        F: FnOnce(&mut Conf) -> Result<T, E>,
+    {
+        let mut staged = self.clone();
+        let result = f(&mut staged)?;
+        *self = staged;
+        Ok(result)
+    }
+
+    /// Sort sections by name and the entries within each section by
+    /// key. The sort is stable, so duplicates keep their relative order.
+    pub fn sort(&mut self) {
+        self.sort_sections_by_name();
+        for section in self.sections.iter_mut() {
+            section.sort_entries_by(|a, b| a.key.cmp(&b.key));
+        }
+    }
+
+    /// Sort the sections with `compare`. The sort is stable, and the
+    /// entries within each section are not reordered.
+    pub fn sort_sections_by<F>(&mut self, compare: F)
+    where
+        F: FnMut(&Section, &Section) -> std::cmp::Ordering,
+    {
+        self.sections.sort_by(compare)
+    }
+
+    /// Sort the sections by name. The sort is stable, and the entries
+    /// within each section are not reordered.
+    pub fn sort_sections_by_name(&mut self) {
+        self.sort_sections_by(|a, b| a.name.cmp(&b.name))
+    }
+
+    /// Put the config in a deterministic normal form, so that configs
+    /// with the same content serialize to the same bytes.
+    ///
+    /// Whitespace around keys and values is trimmed, repeated sections and
+    /// then repeated keys are resolved by `policy`, and the result is
+    /// sorted as by [`sort`].
+    ///
+    /// [`sort`]: #method.sort
+    pub fn canonicalize(&mut self, policy: DedupPolicy) {
+        for entry in self.sections.iter_mut().flat_map(|s| s.entries.iter_mut()) {
+            entry.key = entry.key.trim().to_string();
+            entry.value = entry.value.trim().to_string();
+        }
+        self.dedup_sections(policy);
+        for section in self.sections.iter_mut() {
+            section.dedup_keys(policy);
+        }
+        self.sort();
+    }
+
+    /// Whether this config has the same sections with the same entries
+    /// as `other`, in any order.
+    ///
+    /// Unlike [`diff`], repeated sections and keys count, so `[a] x = 1`
+    /// and `[a] x = 1 x = 1` differ. The values of a repeated key must be
+    /// in the same order, since a later value overrides an earlier one.
+    ///
+    /// [`diff`]: #method.diff
+    pub fn semantic_eq(&self, other: &Conf) -> bool {
+        self.len() == other.len()
+            && self.total_entries() == other.total_entries()
+            && Normalized::new(self.clone()) == Normalized::new(other.clone())
+    }
+
+    /// A 64-bit hash of the config's content that ignores the order of
+    /// sections and entries and whitespace around keys and values.
+    ///
+    /// Configs equal under [`semantic_eq`] after trimming have the same
+    /// digest. The digest is FNV-1a, and is the same on every platform
+    /// and run.
+    ///
+    /// [`semantic_eq`]: #method.semantic_eq
+    pub fn digest(&self) -> u64 {
+        let mut conf = self.clone();
+        for entry in conf.sections.iter_mut().flat_map(|s| s.entries.iter_mut()) {
+            entry.key = entry.key.trim().to_string();
+            entry.value = entry.value.trim().to_string();
+        }
+        let conf = Normalized::new(conf).into_inner();
+
+        let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+        let mut write = |bytes: &[u8]| {
+            for byte in bytes {
+                hash ^= u64::from(*byte);
+                hash = hash.wrapping_mul(0x0100_0000_01b3);
+            }
+        };
+        // Each string is prefixed with its length so that field
+        // boundaries are part of the hash.
+        let mut write_str = |tag: u8, s: &str| {
+            write(&[tag]);
+            write(&(s.len() as u64).to_le_bytes());
+            write(s.as_bytes());
+        };
+        for section in conf.sections.iter() {
+            write_str(b's', &section.name);
+            for entry in section.entries.iter() {
+                write_str(b'k', &entry.key);
+                write_str(b'v', &entry.value);
+            }
+        }
+        hash
+    }
+
+    /// Compare this config with a `new` one, ignoring formatting and
+    /// order.
+    ///
+    /// Sections that share a name are compared as if they were merged,
+    /// and a key repeated within a section only counts its first value.
+    pub fn diff(&self, new: &Conf) -> Vec<diff::Change> {
+        diff::diff(self, new)
+    }
+
+    /// Merge `other` into this config according to `policy`.
+    ///
+    /// Except with [`MergePolicy::Append`], each section of `other` is
+    /// merged into the first section of the same name, which is created
+    /// at the end of the config if needed, and each key is merged into
+    /// the first entry with that key.
+    ///
+    /// [`MergePolicy::Append`]: enum.MergePolicy.html#variant.Append
+    pub fn merge(&mut self, other: &Conf, policy: MergePolicy) {
+        if policy == MergePolicy::Append {
+            self.sections.extend(other.sections.iter().cloned());
+            return;
+        }
+        for other_section in other.sections.iter() {
+            let index = match self.sections.iter().position(|s| s.name == other_section.name) {
+                Some(index) => index,
+                None => {
+                    self.sections.push(Section::new(&other_section.name));
+                    self.sections.len() - 1
+                }
+            };
+            let section = &mut self.sections[index];
+            for other_entry in other_section.entries.iter() {
+                match section.entries.iter_mut().find(|e| e.key == other_entry.key) {
+                    Some(entry) => {
+                        if policy == MergePolicy::Override {
+                            entry.value = other_entry.value.clone();
+                        }
+                    }
+                    None => section.entries.push(other_entry.clone()),
+                }
+            }
+        }
+    }
+
+    /// Append a section to the config.
+    pub fn add_section(&mut self, name: &str, entries: Vec<Entry>) {
+        self.sections.push(Section { name: name.to_string(), entries });
+    }
+
+    /// Number of sections.
+    pub fn len(&self) -> usize {
+        self.sections.len()
+    }
+
+    /// Make room for at least `additional` more sections.
+    pub fn reserve(&mut self, additional: usize) {
+        self.sections.reserve(additional);
+    }
+
+    /// Free unused room for sections, and for entries in each section.
+    pub fn shrink_to_fit(&mut self) {
+        self.sections.shrink_to_fit();
+        for section in self.sections.iter_mut() {
+            section.shrink_to_fit();
+        }
+    }
+
+    /// Whether there are no sections.
+    pub fn is_empty(&self) -> bool {
+        self.sections.is_empty()
+    }
+
+    /// Number of entries in all sections.
+    pub fn total_entries(&self) -> usize {
+        self.sections.iter().map(Section::len).sum()
+    }
+
+    /// Iterate over the sections.
+    pub fn iter(&self) -> std::slice::Iter<'_, Section> {
+        self.sections.iter()
+    }
+
+    /// Iterate over the sections, allowing changes.
+    pub fn iter_mut(&mut self) -> std::slice::IterMut<'_, Section> {
+        self.sections.iter_mut()
+    }
+
+    /// Iterate over every entry in the config as `(section, key, value)`.
+    pub fn entries(&self) -> impl Iterator<Item = (&str, &str, &str)> {
+        self.sections.iter().flat_map(|section| {
+            section
+                .entries
+                .iter()
+                .map(move |e| (section.name.as_str(), e.key.as_str(), e.value.as_str()))
+        })
+    }
+
+    /// Iterate over every entry in the config as `(path, value)`, where
+    /// the path is the section name and key joined by `separator`, such
+    /// as `server.port` for a `.` separator. Repeated keys are included.
+    pub fn flatten<'a>(&'a self, separator: &'a str) -> impl Iterator<Item = (String, &'a str)> {
+        self.entries()
+            .map(move |(section, key, value)| (format!("{}{}{}", section, separator, key), value))
+    }
+
+    /// Collect [`flatten`] into a map, sorted by path. For repeated paths
+    /// the first value is kept, as lookups return.
+    ///
+    /// [`flatten`]: #method.flatten
+    pub fn flatten_map(&self, separator: &str) -> BTreeMap<String, String> {
+        let mut map = BTreeMap::new();
+        for (path, value) in self.flatten(separator) {
+            map.entry(path).or_insert_with(|| value.to_string());
+        }
+        map
+    }
+
+    /// Convert to nested maps from section name to key to value.
+    ///
+    /// Sections with the same name are combined. For a key set more than
+    /// once, the first value is kept, as lookups return. Order is lost.
+    pub fn to_map(&self) -> HashMap<String, HashMap<String, String>> {
+        let mut map: HashMap<String, HashMap<String, String>> = HashMap::new();
+        for section in self.sections.iter() {
+            let keys = map.entry(section.name.clone()).or_default();
+            for entry in section.iter() {
+                keys.entry(entry.key.clone())
+                    .or_insert_with(|| entry.value.clone());
+            }
+        }
+        map
+    }
+
+    /// Create a config from nested maps from section name to key to
+    /// value, as returned by [`to_map`]. Sections are ordered by name, and
+    /// entries by key, so the result does not depend on the maps'
+    /// iteration order.
+    ///
+    /// [`to_map`]: #method.to_map
+    pub fn from_map(map: &HashMap<String, HashMap<String, String>>) -> Conf {
+        let mut conf = Conf::with_capacity(map.len());
+        for (name, keys) in map.iter() {
+            let mut section = Section::with_capacity(name, keys.len());
+            for (key, value) in keys.iter() {
+                section.entries.push(Entry::new(key, value));
+            }
+            section.entries.sort();
+            conf.sections.push(section);
+        }
+        conf.sections.sort_by(|a, b| a.name.cmp(&b.name));
+        conf
+    }
+
+    /// Convert to nested sorted maps from section name to key to value,
+    /// combining sections and keeping the first value of each key as
+    /// [`to_map`] does. Iteration order is by name, so the result is
+    /// deterministic.
+    ///
+    /// [`to_map`]: #method.to_map
+    pub fn to_btree_map(&self) -> BTreeMap<String, BTreeMap<String, String>> {
+        let mut map: BTreeMap<String, BTreeMap<String, String>> = BTreeMap::new();
+        for section in self.sections.iter() {
+            let keys = map.entry(section.name.clone()).or_default();
+            for entry in section.iter() {
+                keys.entry(entry.key.clone())
+                    .or_insert_with(|| entry.value.clone());
+            }
+        }
+        map
+    }
+
+    /// Create a config from nested sorted maps, as returned by
+    /// [`to_btree_map`], keeping their order.
+    ///
+    /// [`to_btree_map`]: #method.to_btree_map
+    pub fn from_btree_map(map: &BTreeMap<String, BTreeMap<String, String>>) -> Conf {
+        let sections = map
+            .iter()
+            .map(|(name, keys)| {
+                let entries = keys.iter().map(|(k, v)| Entry::new(k, v)).collect();
+                Section::new_with_entries(name, entries)
+            })
+            .collect();
+        Conf::from_sections(sections)
+    }
+
+    /// Iterate over every entry in the config as `(section, key, value)`,
+    /// allowing changes to the values.
+    pub fn entries_mut(&mut self) -> impl Iterator<Item = (&str, &str, &mut String)> {
+        self.sections.iter_mut().flat_map(|section| {
+            let Section { name, entries } = section;
+            let name = name.as_str();
+            entries
+                .iter_mut()
+                .map(move |e| (name, e.key.as_str(), &mut e.value))
+        })
+    }
+
+    /// Replace each value with the result of calling `f` with its
+    /// section name, key and value.
+    pub fn map_values<F: FnMut(&str, &str, &str) -> String>(&mut self, mut f: F) {
+        for section in self.sections.iter_mut() {
+            let name = &section.name;
+            for entry in section.entries.iter_mut() {
+                entry.value = f(name, &entry.key, &entry.value);
+            }
+        }
+    }
+
+    /// Put `prefix` before every section name, such as `tenant_a.` to
+    /// keep the sections apart when merged with other configs.
+    pub fn prefix_sections(&mut self, prefix: &str) {
+        for section in self.sections.iter_mut() {
+            section.name.insert_str(0, prefix);
+        }
+    }
+
+    /// Remove `prefix` from the start of every section name that has it,
+    /// undoing [`prefix_sections`]. Other sections are unchanged. Returns
+    /// whether any section was renamed, which is never the case for an
+    /// empty prefix.
+    ///
+    /// [`prefix_sections`]: #method.prefix_sections
+    pub fn strip_section_prefix(&mut self, prefix: &str) -> bool {
+        if prefix.is_empty() {
+            return false;
+        }
+        let mut changed = false;
+        for section in self.sections.iter_mut() {
+            if section.name.starts_with(prefix) {
+                section.name.replace_range(..prefix.len(), "");
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    /// Rename every section named `old` to `new`, keeping its position.
+    /// Returns whether any section was renamed.
+    pub fn rename_section(&mut self, old: &str, new: &str) -> bool {
+        let mut changed = false;
+        for section in self.sections.iter_mut().filter(|s| s.name == old) {
+            section.name = new.to_string();
+            changed = true;
+        }
+        changed
+    }
+
+    /// Remove the first section named `name` and return it.
+    pub fn remove_section(&mut self, name: &str) -> Option<Section> {
+        let index = self.sections.iter().position(|s| s.name == name)?;
+        Some(self.sections.remove(index))
+    }
+
+    /// Remove every section named `name` and return them in order.
+    pub fn remove_sections(&mut self, name: &str) -> Vec<Section> {
+        let (removed, kept) = std::mem::take(&mut self.sections)
+            .into_iter()
+            .partition(|s| s.name == name);
+        self.sections = kept;
+        removed
+    }
+
+    /// Move the first entry with `key` in the first section named
+    /// `from` into the first section named `to`, which is created at the
+    /// end of the config if needed. If `to` already has `key`, its value
+    /// is replaced instead of adding a second entry.
+    ///
+    /// Returns whether an entry was moved.
+    pub fn move_entry(&mut self, from: &str, key: &str, to: &str) -> bool {
+        let from = match self.sections.iter_mut().find(|s| s.name == from) {
+            Some(section) => section,
+            None => return false,
+        };
+        let entry = match from.entries.iter().position(|e| e.key == key) {
+            Some(index) => from.entries.remove(index),
+            None => return false,
+        };
+        let to = match self.sections.iter().position(|s| s.name == to) {
+            Some(index) => &mut self.sections[index],
+            None => {
+                self.sections.push(Section::new(to));
+                self.sections.last_mut().unwrap()
+            }
+        };
+        to.set(&entry.key, &entry.value);
+        true
+    }
+
+    /// Remove repeated sections according to `policy`. Repeated keys
+    /// within a section are left alone.
+    pub fn dedup_sections(&mut self, policy: DedupPolicy) {
+        dedup_by(
+            &mut self.sections,
+            policy,
+            |a, b| a.name == b.name,
+            |first, repeat| {
+                for entry in repeat.entries {
+                    first.set(&entry.key, &entry.value);
+                }
+            },
+        );
+    }
+
+    /// Find repeated section names, and keys repeated within a section,
+    /// without changing anything.
+    pub fn find_duplicates(&self) -> DuplicateReport {
+        let names = self.sections.iter().map(|s| s.name.as_str());
+        let mut report = DuplicateReport {
+            sections: repeated_indices(names)
+                .into_iter()
+                .map(|(name, indices)| DuplicateSection { name: name.to_string(), indices })
+                .collect(),
+            keys: Vec::new(),
+        };
+        for (section, s) in self.sections.iter().enumerate() {
+            let keys = s.entries.iter().map(|e| e.key.as_str());
+            report.keys.extend(repeated_indices(keys).into_iter().map(|(key, indices)| {
+                DuplicateKey { section, key: key.to_string(), indices }
+            }));
+        }
+        report
+    }
+
+    /// Keep only the sections for which `f` returns true.
+    pub fn retain_sections<F: FnMut(&Section) -> bool>(&mut self, f: F) {
+        self.sections.retain(f)
+    }
+
+    /// Remove the sections for which `f` returns true and return them as
+    /// a new config. Both keep the sections in order.
+    pub fn extract<F: FnMut(&Section) -> bool>(&mut self, mut f: F) -> Conf {
+        let (extracted, kept) = std::mem::take(&mut self.sections)
+            .into_iter()
+            .partition(|s| f(s));
+        self.sections = kept;
+        Conf::from_sections(extracted)
+    }
+
+    /// Copy the sections for which `f` returns true into a new config,
+    /// in order.
+    pub fn filter<F: FnMut(&Section) -> bool>(&self, mut f: F) -> Conf {
+        Conf::from_sections(self.sections.iter().filter(|s| f(s)).cloned().collect())
+    }
+
+    /// Get the first section named `name`.
+    pub fn section(&self, name: &str) -> Option<&Section> {
+        self.sections.iter().find(|s| s.name == name)
+    }
+
+    /// Get the value of `key` in the first section named `section` that
+    /// has it, or `default` if there is none.
+    pub fn get_or<'a>(&'a self, section: &str, key: &str, default: &'a str) -> &'a str {
+        self.sections
+            .iter()
+            .filter(|s| s.name == section)
+            .find_map(|s| s.get(key))
+            .unwrap_or(default)
+    }
+
+    /// Get the value of `key` in the first section named `section` that
+    /// has it, for editing in place.
+    pub fn get_mut(&mut self, section: &str, key: &str) -> Option<&mut String> {
+        self.sections
+            .iter_mut()
+            .filter(|s| s.name == section)
+            .find_map(|s| s.get_mut(key))
+    }
+
+    /// Get every section named `name`, in order.
+    pub fn sections_named<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Section> {
+        self.sections.iter().filter(move |s| s.name == name)
+    }
+
+    /// Combine every section named `name` into one section with all of
+    /// their entries, in order. Returns `None` if there is no such
+    /// section.
+    pub fn merged_section(&self, name: &str) -> Option<Section> {
+        let mut sections = self.sections_named(name).peekable();
+        sections.peek()?;
+        let entries = sections.flat_map(|s| s.entries.iter().cloned()).collect();
+        Some(Section::new_with_entries(name, entries))
+    }
+
+    /// Get the sections whose names match `pattern`, where `*` matches
+    /// any run of characters, including dots, and `?` matches any single
+    /// character.
+    pub fn sections_matching<'a>(&'a self, pattern: &'a str) -> impl Iterator<Item = &'a Section> {
+        self.sections.iter().filter(move |s| glob_match(pattern, &s.name))
+    }
+
+    /// Get the sections whose [`parent`] is `name`.
+    ///
+    /// [`parent`]: struct.Section.html#method.parent
+    pub fn children<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Section> {
+        self.sections.iter().filter(move |s| s.parent() == Some(name))
+    }
+
+    /// Get a nested view of the sections, treating dots in section names
+    /// as separators between a parent and child. See the [`tree`] module.
+    ///
+    /// [`tree`]: tree/index.html
+    pub fn tree(&self) -> tree::TreeNode<'_> {
+        tree::build(self)
+    }
+
+    /// Get a value by its dotted path, `section.key`.
+    ///
+    /// Section names may contain dots, so `net.tls.cert` is the key
+    /// `cert` in section `net.tls` if there is one, and otherwise the key
+    /// `tls.cert` in section `net`. Sections that share a name are
+    /// searched in order.
+    pub fn get_path(&self, path: &str) -> Option<&str> {
+        split_path(path).find_map(|(section, key)| {
+            self.sections
+                .iter()
+                .filter(|s| s.name == section)
+                .find_map(|s| s.get(key))
+        })
+    }
+
+    /// The section and key that `path` refers to, as resolved by
+    /// [`get_path`]: the first split whose section has the key, then the
+    /// first split whose section exists.
+    ///
+    /// [`get_path`]: #method.get_path
+    pub(crate) fn resolve_path<'a>(&self, path: &'a str) -> Option<(&'a str, &'a str)> {
+        let has_section = |section: &str| self.sections.iter().any(|s| s.name == section);
+        let has_key = |section: &str, key: &str| {
+            self.sections.iter().any(|s| s.name == section && s.get(key).is_some())
+        };
+        split_path(path)
+            .find(|(section, key)| has_key(section, key))
+            .or_else(|| split_path(path).find(|(section, _)| has_section(section)))
+    }
+
+    /// Set a value by its dotted path, `section.key`.
+    ///
+    /// The path is split as in [`get_path`]. If no section matches, a
+    /// section named by everything before the last dot is added. Returns
+    /// false, changing nothing, if the path has no dot.
+    ///
+    /// [`get_path`]: #method.get_path
+    pub fn set_path(&mut self, path: &str, value: &str) -> bool {
+        let split = self.resolve_path(path).or_else(|| split_path(path).next());
+        let (section, key) = match split {
+            Some(split) => split,
+            None => return false,
+        };
+        let with_key = self
+            .sections
+            .iter()
+            .position(|s| s.name == section && s.get(key).is_some());
+        let index = match with_key.or_else(|| self.sections.iter().position(|s| s.name == section)) {
+            Some(index) => index,
+            None => {
+                self.sections.push(Section::new(section));
+                self.sections.len() - 1
+            }
+        };
+        self.sections[index].set(key, value);
+        true
+    }
+
+    /// Remove a value by its dotted path, `section.key`, splitting the
+    /// path as in [`get_path`]. Every entry with the key in every
+    /// section of that name is removed.
+    ///
+    /// Returns whether anything was removed.
+    ///
+    /// [`get_path`]: #method.get_path
+    pub fn remove_path(&mut self, path: &str) -> bool {
+        let (section, key) = match self.resolve_path(path) {
+            Some(split) => split,
+            None => return false,
+        };
+        let mut removed = false;
+        for s in self.sections.iter_mut().filter(|s| s.name == section) {
+            let len = s.entries.len();
+            s.entries.retain(|e| e.key != key);
+            removed |= s.entries.len() != len;
+        }
+        removed
+    }
+
+    /// Get all the sections' names.
+    pub fn section_names(&self) -> Vec<&str> {
+        self.sections.iter().map(|section| section.name.as_str()).collect()
+    }
+}
+
+/// Split a trimmed section header line into the section name and the
+/// text after the closing bracket, as the parser does. Returns `None` if
+/// the line does not start with `[` or has no `]`.
+///
+/// The name ends at the first `]` if only whitespace or a comment follows
+/// it. Otherwise a line that ends in `]` is all name, so that names may
+/// contain `]`.
+///
+/// ```
+/// assert_eq!(nbconf::split_header("[a] # note"), Some(("a", "# note")));
+/// assert_eq!(nbconf::split_header("[a]b]"), Some(("a]b", "")));
+/// ```
+pub fn split_header(line: &str) -> Option<(&str, &str)> {
+    let rest = line.strip_prefix('[')?;
+    let close = rest.find(']')?;
+    let text = rest[close + 1..].trim_start();
+    if text.is_empty() || is_comment(text) {
+        return Some((&rest[..close], text));
+    }
+    match rest.strip_suffix(']') {
+        Some(name) => Some((name, "")),
+        None => Some((&rest[..close], text)),
+    }
+}
+
+/// Check that a section header for `name` would read back as the same
+/// name.
+pub(crate) fn check_section_name(name: &str) -> Result<(), SerializeError> {
+    let error = |kind| SerializeError {
+        section: name.to_string(),
+        kind,
+    };
+    if name.trim().is_empty() {
+        return Err(error(SerializeErrorKind::EmptySectionName));
+    }
+    // After a `]`, a comment ends the header early when read back.
+    let comment_after_bracket = name
+        .match_indices(']')
+        .any(|(index, _)| is_comment(name[index + 1..].trim_start()));
+    if name.contains(is_line_break) || comment_after_bracket {
+        return Err(error(SerializeErrorKind::InvalidSectionName));
+    }
+    Ok(())
+}
+
+/// Check that an entry in `section` would read back as the same entry.
+pub(crate) fn check_entry(section: &str, key: &str, value: &str) -> Result<(), SerializeError> {
+    let error = |kind| SerializeError {
+        section: section.to_string(),
+        kind,
+    };
+    if key.is_empty() {
+        return Err(error(SerializeErrorKind::EmptyKey));
+    }
+    if key.contains(|c| c == '=' || is_line_break(c))
+        || key.starts_with(&['#', ';', '['][..])
+        || key.trim() != key
+    {
+        return Err(error(SerializeErrorKind::InvalidKey {
+            key: key.to_string(),
+        }));
+    }
+    if value.contains(is_line_break) || value.trim() != value {
+        return Err(error(SerializeErrorKind::InvalidValue {
+            key: key.to_string(),
+        }));
+    }
+    Ok(())
+}
+
+/// Whether `c` ends a line when read back.
+fn is_line_break(c: char) -> bool {
+    c == '\n' || c == '\r'
+}
+
+/// Whether a trimmed line is a comment.
+pub(crate) fn is_comment(line: &str) -> bool {
+    line.starts_with('#') || line.starts_with(';')
+}
+
+/// Whether `c` is a control character that doesn't belong in a key, value,
+/// or section name. Tabs are allowed.
+fn is_control(c: char) -> bool {
+    c.is_control() && c != '\t'
+}
+
+/// Remove repeats from `items`, where `same` tells whether two items are
+/// repeats and `merge` combines a repeat into the first occurrence.
+fn dedup_by<T>(
+    items: &mut Vec<T>,
+    policy: DedupPolicy,
+    same: impl Fn(&T, &T) -> bool,
+    merge: impl Fn(&mut T, T),
+) {
+    let mut kept: Vec<T> = Vec::with_capacity(items.len());
+    let add = |kept: &mut Vec<T>, item: T| {
+        match kept.iter_mut().find(|k| same(k, &item)) {
+            Some(first) => {
+                if policy == DedupPolicy::Merge {
+                    merge(first, item);
+                }
+            }
+            None => kept.push(item),
+        }
+    };
+    if policy == DedupPolicy::KeepLast {
+        for item in items.drain(..).rev() {
+            add(&mut kept, item);
+        }
+        kept.reverse();
+    } else {
+        for item in items.drain(..) {
+            add(&mut kept, item);
+        }
+    }
+    *items = kept;
+}
+
+/// Whether `name` matches the glob `pattern`, which may use `*` and `?`.
+pub(crate) fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    // Position of the last `*` seen and the name position it was tried at.
+    let mut star: Option<(usize, usize)> = None;
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+            p += 1;
+            n += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, n));
+            p += 1;
+        } else if let Some((star_p, star_n)) = star {
+            // Let the last `*` match one more character.
+            p = star_p + 1;
+            n = star_n + 1;
+            star = Some((star_p, star_n + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+/// Ways of splitting a dotted path into a section and key, longest
+/// section name first.
+pub(crate) fn split_path(path: &str) -> impl Iterator<Item = (&str, &str)> {
+    path.rmatch_indices('.')
+        .map(move |(dot, _)| (&path[..dot], &path[dot + 1..]))
+}
+
+/// Group the indices of `names` by name, keeping only names used more
+/// than once.
+fn repeated_indices<'a, I: Iterator<Item = &'a str>>(names: I) -> Vec<(&'a str, Vec<usize>)> {
+    let mut groups: Vec<(&str, Vec<usize>)> = Vec::new();
+    for (index, name) in names.enumerate() {
+        match groups.iter_mut().find(|(n, _)| *n == name) {
+            Some((_, indices)) => indices.push(index),
+            None => groups.push((name, vec![index])),
+        }
+    }
+    groups.retain(|(_, indices)| indices.len() > 1);
+    groups
+}
+
+/// Decode `bytes` as described in [`Conf::parse_bytes`].
+///
+/// [`Conf::parse_bytes`]: struct.Conf.html#method.parse_bytes
+fn decode(bytes: &[u8], decoding: Decoding) -> Result<Cow<'_, str>, ParseError> {
+    if let Some(rest) = bytes.strip_prefix(b"\xff\xfe") {
+        return decode_utf16(rest, u16::from_le_bytes, decoding).map(Cow::Owned);
+    }
+    if let Some(rest) = bytes.strip_prefix(b"\xfe\xff") {
+        return decode_utf16(rest, u16::from_be_bytes, decoding).map(Cow::Owned);
+    }
+    match std::str::from_utf8(bytes) {
+        Ok(s) => Ok(Cow::Borrowed(s)),
+        Err(err) => match decoding {
+            Decoding::Strict => {
+                let offset = err.valid_up_to();
+                let line = bytes[..offset].iter().filter(|b| **b == b'\n').count() + 1;
+                Err(ParseError::new(line, ParseErrorKind::InvalidUtf8 { offset }))
+            }
+            Decoding::Lossy => Ok(String::from_utf8_lossy(bytes)),
+            Decoding::Latin1 => Ok(Cow::Owned(decode_utf8_latin1(bytes))),
+        },
+    }
+}
+
+/// Decode `bytes` as UTF-8, falling back to Latin-1 for invalid bytes.
+fn decode_utf8_latin1(mut bytes: &[u8]) -> String {
+    let mut output = String::with_capacity(bytes.len());
+    loop {
+        match std::str::from_utf8(bytes) {
+            Ok(s) => {
+                output += s;
+                return output;
+            }
+            Err(err) => {
+                let (valid, rest) = bytes.split_at(err.valid_up_to());
+                // The prefix was just validated.
+                output += std::str::from_utf8(valid).unwrap();
+                let invalid_len = err.error_len().unwrap_or(rest.len());
+                output.extend(rest[..invalid_len].iter().map(|b| char::from(*b)));
+                bytes = &rest[invalid_len..];
+            }
+        }
+    }
+}
+
+/// Decode the UTF-16 `bytes` that follow a two-byte byte order mark.
+fn decode_utf16(
+    bytes: &[u8],
+    unit: fn([u8; 2]) -> u16,
+    decoding: Decoding,
+) -> Result<String, ParseError> {
+    let mut output = String::new();
+    let mut offset = 2;
+    let invalid = |output: &mut String, offset: usize| match decoding {
+        Decoding::Strict => {
+            let line = output.matches('\n').count() + 1;
+            Err(ParseError::new(line, ParseErrorKind::InvalidUtf16 { offset }))
+        }
+        Decoding::Lossy | Decoding::Latin1 => {
+            output.push(std::char::REPLACEMENT_CHARACTER);
+            Ok(())
+        }
+    };
+    let pairs = bytes.chunks_exact(2);
+    let has_trailing_byte = !pairs.remainder().is_empty();
+    let units = pairs.map(|pair| unit([pair[0], pair[1]]));
+    for c in std::char::decode_utf16(units) {
+        match c {
+            Ok(c) => {
+                output.push(c);
+                offset += c.len_utf16() * 2;
+            }
+            Err(_) => {
+                invalid(&mut output, offset)?;
+                offset += 2;
+            }
+        }
+    }
+    if has_trailing_byte {
+        invalid(&mut output, offset)?;
+    }
+    Ok(output)
+}
+
+impl std::fmt::Display for Conf {
+    /// Serialize the config as a string.
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for (index, section) in self.sections.iter().enumerate() {
+            if index != 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}", section)?;
+        }
+        Ok(())
+    }
+}
+
+impl IntoIterator for Conf {
+    type Item = Section;
+    type IntoIter = std::vec::IntoIter<Section>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.sections.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a Conf {
+    type Item = &'a Section;
+    type IntoIter = std::slice::Iter<'a, Section>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.sections.iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a mut Conf {
+    type Item = &'a mut Section;
+    type IntoIter = std::slice::IterMut<'a, Section>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.sections.iter_mut()
+    }
+}
+
+impl std::ops::Index<&str> for Conf {
+    type Output = Section;
+
+    /// Get the first section named `name`.
+    ///
+    /// Panics if there is no such section.
+    fn index(&self, name: &str) -> &Section {
+        match self.section(name) {
+            Some(section) => section,
+            None => panic!("no section named {:?}", name),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_section() {
+        let mut expected = Conf::new();
+        expected.add_section("mySection", Vec::new());
+        assert_eq!(Conf::parse_str("[mySection]"), Ok(expected));
+    }
+
+    #[test]
+    fn test_entry() {
+        let mut expected = Conf::new();
+        expected.add_section("mySection", vec![
+            Entry {
+                key: "a".to_string(),
+                value: "b".to_string(),
+            }
+        ]);
+        assert_eq!(Conf::parse_str("[mySection]\na = b"), Ok(expected));
+    }
+
+    #[test]
+    fn test_comments() {
+        let conf = Conf::parse_str("# top\n[mySection]\n  ; note = x\na = b # not a comment").unwrap();
+        assert_eq!(conf.sections[0].entries, vec![Entry::new("a", "b # not a comment")]);
+    }
+
+    #[test]
+    fn test_to_string() {
+        let mut conf = Conf::new();
+        conf.add_section("sec1", vec![
+            Entry {
+                key: "a".to_string(),
+                value: "b".to_string(),
+            }
+        ]);
+        conf.add_section("sec2", vec![
+            Entry {
+                key: "c".to_string(),
+                value: "d".to_string(),
+            }
+        ]);
+        assert_eq!(conf.to_string(), "[sec1]\na = b\n\n[sec2]\nc = d\n");
+    }
+
+    #[test]
+    fn test_parse_bytes_utf16() {
+        let utf16le: Vec<u8> = "\u{feff}[sec]\na = b\u{e9}"
+            .encode_utf16()
+            .flat_map(|unit| unit.to_le_bytes().to_vec())
+            .collect();
+        let conf = Conf::parse_bytes(&utf16le, Decoding::Strict).unwrap();
+        assert_eq!(conf.sections[0].get("a"), Some("b\u{e9}"));
+
+        let mut utf16be: Vec<u8> = "\u{feff}[sec]\na = "
+            .encode_utf16()
+            .flat_map(|unit| unit.to_be_bytes().to_vec())
+            .collect();
+        // Unpaired high surrogate.
+        utf16be.extend_from_slice(&[0xd8, 0x00, 0x00, b'x']);
+        assert_eq!(
+            Conf::parse_bytes(&utf16be, Decoding::Strict),
+            Err(ParseError::new(2, ParseErrorKind::InvalidUtf16 { offset: 22 }))
+        );
+    }
+
+    #[test]
+    fn test_bom() {
+        let conf = Conf::parse_str("\u{feff}[mySection]\na = b").unwrap();
+        assert_eq!(conf.section_names(), vec!["mySection"]);
+
+        let options = WriteOptions {
+            bom: true,
+            ..Default::default()
+        };
+        assert_eq!(conf.to_string_styled(&options), "\u{feff}[mySection]\na = b\n");
+    }
+
+    #[test]
+    fn test_capacity() {
+        let mut conf = Conf::parse_str("[a]\nx = 1\n[b]\n ; [c]\n").unwrap();
+        assert_eq!(conf.sections.capacity(), 2);
+        conf.reserve(10);
+        assert!(conf.sections.capacity() >= 12);
+        conf.sections[0].reserve(5);
+        assert!(conf.sections[0].entries.capacity() >= 6);
+        conf.shrink_to_fit();
+        assert_eq!(conf.sections.capacity(), 2);
+        assert_eq!(conf.sections[0].entries.capacity(), 1);
+
+        assert_eq!(Conf::with_capacity(3).sections.capacity(), 3);
+        assert_eq!(Section::with_capacity("a", 4).entries.capacity(), 4);
+    }
+
+    #[test]
+    fn test_line_ending() {
+        let source = "[sec1]\r\na = b\r\n\r\n[sec2]\r\n";
+        let conf = Conf::parse_str(source).unwrap();
+        let options = WriteOptions::detect(source);
+        assert_eq!(options.line_ending, LineEnding::CrLf);
+        assert_eq!(conf.to_string_styled(&options), source);
+
+        assert_eq!(WriteOptions::detect("[sec1]\na = b\r\n").line_ending, LineEnding::Lf);
+    }
+
+    #[test]
+    fn test_write_options() {
+        let conf = Conf::parse_str("[sec1]\na = b\nc = d\n[sec2]\ne = f").unwrap();
+        let options = WriteOptions {
+            spaces_around_equals: false,
+            blank_lines_between_sections: 2,
+            indent: "  ".to_string(),
+            trailing_newline: false,
+            ..Default::default()
+        };
+        let expected = "[sec1]\n  a=b\n  c=d\n\n\n[sec2]\n  e=f";
+        assert_eq!(conf.to_string_styled(&options), expected);
+
+        let mut output = Vec::new();
+        conf.write_to(&mut output, &options).unwrap();
+        assert_eq!(output, expected.as_bytes());
+
+        assert_eq!(conf.to_string_styled(&WriteOptions::default()), conf.to_string());
+
+        let options = WriteOptions {
+            line_terminator: Some("\r".to_string()),
+            trailing_newline: false,
+            ..Default::default()
+        };
+        let mut output = Vec::new();
+        conf.write_to(&mut output, &options).unwrap();
+        assert_eq!(output, b"[sec1]\ra = b\rc = d\r\r[sec2]\re = f");
+    }
+
+    #[test]
+    fn test_sort() {
+        let mut conf = Conf::parse_str("[b]\ny = 1\nx = 2\ny = 3\n[a]\nz = 4").unwrap();
+        let options = WriteOptions {
+            sort: true,
+            ..Default::default()
+        };
+        let expected = "[a]\nz = 4\n\n[b]\nx = 2\ny = 1\ny = 3\n";
+        assert_eq!(conf.to_string_styled(&options), expected);
+
+        conf.sort();
+        assert_eq!(conf.to_string(), expected);
+    }
+
+    #[test]
+    fn test_align_equals() {
+        let conf = Conf::parse_str("[sec1]\na = 1\nlong key = 2\n[sec2]\nab = 3\nc = 4").unwrap();
+        let options = WriteOptions {
+            align_equals: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            conf.to_string_styled(&options),
+            "[sec1]\na        = 1\nlong key = 2\n\n[sec2]\nab = 3\nc  = 4\n"
+        );
+    }
+
+    #[test]
+    fn test_merge() {
+        let base = Conf::parse_str("[a]\nx = 1\ny = 2\n[b]\nz = 3").unwrap();
+        let other = Conf::parse_str("[a]\nx = 4\nw = 5\n[c]\nv = 6").unwrap();
+
+        let mut conf = base.clone();
+        conf.merge(&other, MergePolicy::Override);
+        assert_eq!(conf.to_string(), "[a]\nx = 4\ny = 2\nw = 5\n\n[b]\nz = 3\n\n[c]\nv = 6\n");
+
+        let mut conf = base.clone();
+        conf.merge(&other, MergePolicy::KeepExisting);
+        assert_eq!(conf.to_string(), "[a]\nx = 1\ny = 2\nw = 5\n\n[b]\nz = 3\n\n[c]\nv = 6\n");
+
+        let mut conf = base.clone();
+        conf.merge(&other, MergePolicy::Append);
+        assert_eq!(conf.section_names(), vec!["a", "b", "a", "c"]);
+    }
+
+    #[test]
+    fn test_section_get() {
+        let conf = Conf::from_sections(vec![
+            Section::new_with_entries("mySection", vec![
+                Entry::new("a", "b"),
+                Entry::new("x", "y"),
+            ])
+        ]);
+        assert_eq!(conf.sections[0].get("x"), Some("y"));
+    }
+
+    #[test]
+    fn test_index() {
+        let conf = Conf::parse_str("[a]\nx = 1\n[b]\ny = 2\n[b]\ny = 3").unwrap();
+        assert_eq!(&conf["b"]["y"], "2");
+        assert_eq!(conf.section("a"), Some(&conf.sections[0]));
+        assert_eq!(conf.section("c"), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "no section named \"c\"")]
+    fn test_index_missing_section() {
+        let _ = &Conf::new()["c"];
+    }
+
+    #[test]
+    #[should_panic(expected = "no key \"y\" in section \"a\"")]
+    fn test_index_missing_key() {
+        let _ = &Section::new("a")["y"];
+    }
+
+    #[test]
+    fn test_section_entry() {
+        let mut section = Section::new("stats");
+        let bump = |value: &mut String| {
+            *value = (value.parse::<u32>().unwrap() + 1).to_string()
+        };
+        section.entry("runs").and_modify(bump).or_insert("1");
+        section.entry("runs").and_modify(bump).or_insert("1");
+        assert_eq!(section.get("runs"), Some("2"));
+
+        section.entry("name").or_insert_with(|| "x".to_string()).push('y');
+        assert_eq!(section.get("name"), Some("xy"));
+        match section.entry("other") {
+            KeyEntry::Vacant(vacant) => assert_eq!(vacant.key(), "other"),
+            KeyEntry::Occupied(_) => panic!("expected a vacant entry"),
+        }
+        assert_eq!(section.entries.len(), 2);
+    }
+
+    #[test]
+    fn test_iter() {
+        let mut conf = Conf::parse_str("[a]\nx = 1\ny = 2\n[b]\nz = 3").unwrap();
+        for section in &mut conf {
+            for entry in section {
+                entry.value += "0";
+            }
+        }
+        let mut values = Vec::new();
+        for section in &conf {
+            for entry in section {
+                values.push(entry.value.as_str());
+            }
+        }
+        assert_eq!(values, ["10", "20", "30"]);
+        assert_eq!(conf.iter().map(|s| s.iter().count()).sum::<usize>(), 3);
+
+        conf.iter_mut().for_each(|s| s.iter_mut().for_each(|e| e.value.clear()));
+        let keys: Vec<String> = conf.into_iter().flatten().map(|e| e.key).collect();
+        assert_eq!(keys, ["x", "y", "z"]);
+    }
+
+    #[test]
+    fn test_occurrences() {
+        let conf = Conf::parse_str("[a]\nx = 1\ny = 2\nx = 3\nx = 4").unwrap();
+        let occurrences = conf.sections[0].occurrences("x");
+        let summary: Vec<_> = occurrences
+            .iter()
+            .map(|o| (o.index, o.occurrence, o.count, o.entry.value.as_str()))
+            .collect();
+        assert_eq!(summary, vec![(0, 0, 3, "1"), (2, 1, 3, "3"), (3, 2, 3, "4")]);
+        assert!(occurrences[0].takes_effect() && occurrences[0].shadows());
+        assert!(occurrences[2].is_shadowed() && !occurrences[2].takes_effect());
+
+        let single = conf.sections[0].occurrences("y");
+        assert!(single[0].takes_effect() && !single[0].shadows() && !single[0].is_shadowed());
+        assert!(conf.sections[0].occurrences("z").is_empty());
+    }
+
+    #[test]
+    fn test_flatten() {
+        let conf = Conf::parse_str("[b]\nx = 1\n[a.c]\ny = 2\n[b]\nx = 3").unwrap();
+        let flat: Vec<(String, &str)> = conf.flatten("_").collect();
+        assert_eq!(
+            flat,
+            vec![
+                ("b_x".to_string(), "1"),
+                ("a.c_y".to_string(), "2"),
+                ("b_x".to_string(), "3")
+            ]
+        );
+        let map = conf.flatten_map(".");
+        let pairs: Vec<(&str, &str)> = map.iter().map(|(k, v)| (k.as_str(), v.as_str())).collect();
+        assert_eq!(pairs, vec![("a.c.y", "2"), ("b.x", "1")]);
+    }
+
+    #[test]
+    fn test_map() {
+        let conf = Conf::parse_str("[b]\ny = 1\nx = 2\ny = 3\n[a]\n[b]\nz = 4\n").unwrap();
+        let map = conf.to_map();
+        assert_eq!(map.len(), 2);
+        assert!(map["a"].is_empty());
+        assert_eq!(map["b"]["y"], "1");
+        assert_eq!(map["b"]["z"], "4");
+        assert_eq!(
+            Conf::from_map(&map).to_string(),
+            "[a]\n\n[b]\nx = 2\ny = 1\nz = 4\n"
+        );
+    }
+
+    #[test]
+    fn test_btree_map() {
+        let conf = Conf::parse_str("[b]\ny = 1\nx = 2\ny = 3\n[a]\n[b]\nz = 4\n").unwrap();
+        let map = conf.to_btree_map();
+        let names: Vec<&str> = map.keys().map(String::as_str).collect();
+        assert_eq!(names, vec!["a", "b"]);
+        let keys: Vec<(&str, &str)> = map["b"].iter().map(|(k, v)| (k.as_str(), v.as_str())).collect();
+        assert_eq!(keys, vec![("x", "2"), ("y", "1"), ("z", "4")]);
+        let round_trip = Conf::from_btree_map(&map);
+        assert_eq!(round_trip, Conf::from_map(&conf.to_map()));
+        assert_eq!(round_trip.to_btree_map(), map);
+    }
+
+    #[test]
+    fn test_map_values() {
+        let mut conf = Conf::parse_str("[a]\nhost = old.example\nx = 1\n[b]\nhost = old.example\n").unwrap();
+        conf.map_values(|section, key, value| match (section, key) {
+            ("b", "host") => value.replace("old", "new"),
+            _ => value.to_string(),
+        });
+        conf.sections[0].map_values(|key, value| format!("{}={}", key, value));
+        assert_eq!(
+            conf.to_string(),
+            "[a]\nhost = host=old.example\nx = x=1\n\n[b]\nhost = new.example\n"
+        );
+    }
+
+    #[test]
+    fn test_get_or() {
+        let mut conf = Conf::parse_str("[a]\n[a]\nx = 1").unwrap();
+        assert_eq!(conf.get_or("a", "x", "0"), "1");
+        assert_eq!(conf.get_or("a", "y", "0"), "0");
+        assert_eq!(conf.get_or("b", "x", "0"), "0");
+        assert_eq!(conf.sections[1].get_or("x", "0"), "1");
+        assert_eq!(conf.sections[0].get_or("x", "0"), "0");
+
+        let section = &mut conf.sections[1];
+        assert_eq!(section.get_or_insert_with("x", || unreachable!()), "1");
+        assert_eq!(section.get_or_insert_with("y", || "2".to_string()), "2");
+        assert_eq!(conf.to_string(), "[a]\n\n[a]\nx = 1\ny = 2\n");
+    }
+
+    #[test]
+    fn test_get_localized() {
+        let section = Conf::parse_str(
+            "[a]\nname = Hello\nname[de] = Hallo\nname[de_AT] = Servus\n\
+             name[sr] = Zdravo\nname[sr@latin] = Zdravo!\n",
+        )
+        .unwrap()
+        .sections
+        .remove(0);
+        assert_eq!(section.get_localized("name", "de_AT"), Some("Servus"));
+        assert_eq!(section.get_localized("name", "de_DE.UTF-8"), Some("Hallo"));
+        assert_eq!(section.get_localized("name", "de"), Some("Hallo"));
+        assert_eq!(section.get_localized("name", "fr_FR"), Some("Hello"));
+        assert_eq!(section.get_localized("name", ""), Some("Hello"));
+        assert_eq!(section.get_localized("name", "sr_RS@latin"), Some("Zdravo!"));
+        assert_eq!(section.get_localized("name", "sr_RS"), Some("Zdravo"));
+        assert_eq!(section.get_localized("comment", "de"), None);
+    }
+
+    #[test]
+    fn test_get_mut() {
+        let mut conf = Conf::parse_str("[a]\nx = 1\n[a]\ny = /usr/lib\ny = 3").unwrap();
+        conf.get_mut("a", "x").unwrap().push_str(", 2");
+        let value = conf.get_mut("a", "y").unwrap();
+        *value = value.replacen("/usr", "/opt", 1);
+        assert!(conf.get_mut("a", "z").is_none());
+        assert!(conf.get_mut("b", "x").is_none());
+        assert_eq!(conf.to_string(), "[a]\nx = 1, 2\n\n[a]\ny = /opt/lib\ny = 3\n");
+    }
+
+    #[test]
+    fn test_cow_conf() {
+        let base = CowConf::from(Conf::parse_str("[a]\nx = 1\n[b]\ny = 2\n[a]\nz = 3").unwrap());
+        let mut tenant = base.clone();
+        tenant.set("a", "z", "4");
+        tenant.set("c", "w", "5");
+        tenant.section_mut("b").unwrap().set("y", "6");
+        assert!(tenant.section_mut("d").is_none());
+
+        assert_eq!(base.get_path("a.z"), Some("3"));
+        assert_eq!(tenant.get_path("a.z"), Some("4"));
+        assert!(std::sync::Arc::ptr_eq(&base.sections[0], &tenant.sections[0]));
+        assert!(!std::sync::Arc::ptr_eq(&base.sections[1], &tenant.sections[1]));
+        assert!(!std::sync::Arc::ptr_eq(&base.sections[2], &tenant.sections[2]));
+
+        tenant.retain_sections(|s| s.name != "a");
+        tenant.push(Section::new("d"));
+        assert_eq!(tenant.len(), 3);
+        assert_eq!(tenant.into_conf().to_string(), "[b]\ny = 6\n\n[c]\nw = 5\n\n[d]\n");
+        assert_eq!(base.into_conf().to_string(), "[a]\nx = 1\n\n[b]\ny = 2\n\n[a]\nz = 3\n");
+    }
+
+    #[test]
+    fn test_snapshot() {
+        let snapshot = Conf::parse_str("[server]\nport = 80").unwrap().freeze();
+        let readers: Vec<_> = (0..4)
+            .map(|_| {
+                let snapshot = snapshot.clone();
+                std::thread::spawn(move || snapshot["server"]["port"].to_string())
+            })
+            .collect();
+        for reader in readers {
+            assert_eq!(reader.join().unwrap(), "80");
+        }
+
+        let copy = snapshot.clone();
+        let mut conf = snapshot.thaw();
+        conf.sections[0].set("port", "8080");
+        assert_eq!(copy.get_path("server.port"), Some("80"));
+        assert_eq!(copy.thaw().get_path("server.port"), Some("80"));
+    }
+
+    #[test]
+    fn test_load() {
+        let path = std::env::temp_dir().join(format!("nbconf-load-{}.conf", std::process::id()));
+        std::fs::write(&path, "[a]\nx = 1\n[b").unwrap();
+        match Conf::load(&path) {
+            Err(LoadError::Parse(err)) => assert_eq!(
+                err.to_string(),
+                format!("{}: line 3: missing closing bracket", path.display())
+            ),
+            other => panic!("unexpected result: {:?}", other),
+        }
+        std::fs::write(&path, "[a]\nx = 1\n").unwrap();
+        assert_eq!(Conf::load(&path).unwrap().get_path("a.x"), Some("1"));
+        std::fs::remove_file(&path).unwrap();
+        match Conf::load(&path) {
+            Err(LoadError::Io(err)) => assert!(err.to_string().starts_with(&*path.to_string_lossy())),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_render() {
+        let source = "[a]\nx = 1\n  [b\n";
+        let err = Conf::parse_str_named("app.conf", source).unwrap_err();
+        assert_eq!(
+            err.render(source),
+            "error: missing closing bracket\n --> app.conf:3\n  |\n3 |   [b\n  |     ^ expected ']' at the end of the section header\n"
+        );
+
+        let source = "x = 1\n";
+        let err = Conf::parse_str(source).unwrap_err();
+        assert_eq!(
+            err.render(source),
+            "error: entry outside of any section\n --> line 1\n  |\n1 | x = 1\n  | ^^^^^ add a [section] header before this entry\n"
+        );
+        assert_eq!(
+            err.render(""),
+            "error: entry outside of any section\n --> line 1\n"
+        );
+    }
+
+    #[test]
+    fn test_render_colored() {
+        let source = "[b\n";
+        let err = Conf::parse_str(source).unwrap_err();
+        assert_eq!(
+            err.render_with(source, ColorChoice::Always),
+            "\x1b[1;31merror\x1b[0m\x1b[1m: missing closing bracket\x1b[0m\n \x1b[1;34m-->\x1b[0m line 1\n  \x1b[1;34m|\x1b[0m\n\x1b[1;34m1\x1b[0m \x1b[1;34m|\x1b[0m [\x1b[1mb\x1b[0m\n  \x1b[1;34m|\x1b[0m   \x1b[1;33m^ expected ']' at the end of the section header\x1b[0m\n"
+        );
+        assert_eq!(err.render_with(source, ColorChoice::Never), err.render(source));
+    }
+
+    #[test]
+    fn test_parse_str_with_warnings() {
+        let (conf, warnings) =
+            Conf::parse_str_with_warnings("[a]\nx = 1\nx\ty = \nx = 2\n[b]\n[a]\nx = 3\n").unwrap();
+        assert_eq!(conf.total_entries(), 4);
+        assert_eq!(
+            warnings,
+            vec![
+                Warning {
+                    line: 3,
+                    kind: WarningKind::WhitespaceInKey {
+                        key: "x\ty".to_string()
+                    }
+                },
+                Warning {
+                    line: 3,
+                    kind: WarningKind::EmptyValue {
+                        key: "x\ty".to_string()
+                    }
+                },
+                Warning {
+                    line: 4,
+                    kind: WarningKind::DuplicateKey {
+                        key: "x".to_string()
+                    }
+                },
+                Warning {
+                    line: 6,
+                    kind: WarningKind::DuplicateSection {
+                        name: "a".to_string()
+                    }
+                },
+            ]
+        );
+        assert_eq!(warnings[2].to_string(), "line 4: duplicate key \"x\"");
+    }
+
+    #[test]
+    fn test_empty_key() {
+        let source = "[a]\n = 1\n";
+        assert_eq!(Conf::parse_str(source).unwrap().get_path("a."), Some("1"));
+        assert_eq!(
+            Conf::parse_str_with(source, &ParseOptions::strict()),
+            Err(ParseError::new(2, ParseErrorKind::EmptyKey))
+        );
+
+        let mut conf = Conf::new();
+        conf.add_section("a", vec![Entry::new("", "1")]);
+        assert_eq!(
+            conf.try_to_string_styled(&WriteOptions::default()),
+            Err(SerializeError {
+                section: "a".to_string(),
+                kind: SerializeErrorKind::EmptyKey,
+            })
+        );
+        let err = conf.write_to(&mut Vec::new(), &WriteOptions::default()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn test_unrepresentable() {
+        let error = |name: &str, entry: Entry| {
+            let conf = Conf::from_sections(vec![Section::new_with_entries(name, vec![entry])]);
+            conf.try_to_string_styled(&WriteOptions::default())
+                .map_err(|err| err.to_string())
+        };
+        let valid = Entry::new("k", "v");
+        for name in ["a\nb", "x] ;y", "x]#y", "x]\t# y]"].iter() {
+            assert_eq!(
+                error(name, valid.clone()).unwrap_err(),
+                format!("section {:?}: invalid section name", name)
+            );
+        }
+        for key in ["a=b", "#a", ";a", "[a", " a", "a\r"].iter() {
+            assert_eq!(
+                error("s", Entry::new(key, "v")).unwrap_err(),
+                format!("section \"s\": invalid key {:?}", key)
+            );
+        }
+        for value in ["a\nb", " a", "a\t"].iter() {
+            assert_eq!(
+                error("s", Entry::new("k", value)).unwrap_err(),
+                "section \"s\": invalid value for key \"k\""
+            );
+        }
+
+        // These read back the same, so they are allowed.
+        for (name, key, value) in [("a]b", "k]", "=[v]"), (" a ", "k k", "#v")].iter() {
+            let text = error(name, Entry::new(key, value)).unwrap();
+            assert_eq!(Conf::parse_str(&text).unwrap().sections[0].entries[0].value, *value);
+        }
+        assert!(error("a", valid).is_ok());
+
+        let section = Section::new_with_entries("a", vec![Entry::new("k", "v\n[evil]")]);
+        assert!(section.check_serializable().is_err());
+    }
+
+    #[test]
+    fn test_empty_section_name() {
+        let source = "[a]\n[  ]\nx = 1\n";
+        assert_eq!(Conf::parse_str(source).unwrap().len(), 2);
+        let err = Conf::parse_str_with(source, &ParseOptions::strict()).unwrap_err();
+        assert_eq!(err, ParseError::new(2, ParseErrorKind::EmptySectionName));
+        assert_eq!(
+            err.render(source),
+            "error: empty section name\n --> line 2\n  |\n2 | [  ]\n  | ^^^^ expected a section name\n"
+        );
+
+        let conf = Conf::from_sections(vec![Section::new("")]);
+        assert_eq!(
+            conf.try_to_string_styled(&WriteOptions::default()),
+            Err(SerializeError {
+                section: "".to_string(),
+                kind: SerializeErrorKind::EmptySectionName,
+            })
+        );
+    }
+
+    #[test]
+    fn test_text_after_section_header() {
+        let source = "[a] # about a\nx = 1\n[b]]\n[c] junk\n[d]e] f\n";
+        let (conf, warnings) = Conf::parse_str_with_warnings(source).unwrap();
+        assert_eq!(conf.section_names(), vec!["a", "b]", "c", "d"]);
+        let warnings: Vec<String> = warnings.iter().map(|w| w.to_string()).collect();
+        assert_eq!(
+            warnings,
+            vec![
+                "line 4: ignored text \"junk\" after section header",
+                "line 5: ignored text \"e] f\" after section header"
+            ]
+        );
+
+        let err = Conf::parse_str_with(source, &ParseOptions::strict()).unwrap_err();
+        assert_eq!(err, ParseError::new(4, ParseErrorKind::TextAfterSectionHeader));
+        assert_eq!(
+            err.render(source),
+            "error: text after section header\n --> line 4\n  |\n4 | [c] junk\n  |     ^^^^ expected a comment or nothing after ']'\n"
+        );
+        assert!(Conf::parse_str_with("[a] ; ok\n", &ParseOptions::strict()).is_ok());
+        assert_eq!(
+            Conf::parse_str("[a\n"),
+            Err(ParseError::new(1, ParseErrorKind::MissingClosingBracket))
+        );
+    }
+
+    #[test]
+    fn test_key_policy() {
+        let policy = KeyPolicy::ascii_word().max_length(8).case(KeyCase::Snake);
+        assert_eq!(policy.check("max_size"), Ok(()));
+        assert_eq!(
+            policy.check("max-size"),
+            Err(KeyViolation::DisallowedChar { c: '-' })
+        );
+        assert_eq!(
+            policy.check("max_size_2"),
+            Err(KeyViolation::TooLong { limit: 8 })
+        );
+        assert_eq!(
+            policy.check("MaxSize"),
+            Err(KeyViolation::WrongCase {
+                case: KeyCase::Snake
+            })
+        );
+        assert!(KeyCase::Camel.matches("maxSize2"));
+        assert!(!KeyCase::Camel.matches("MaxSize"));
+        let dotted = KeyPolicy {
+            chars: KeyChars::AsciiAlphanumericAnd(".-".into()),
+            ..KeyPolicy::default()
+        };
+        assert_eq!(dotted.check("a.b-c"), Ok(()));
+
+        let source = "[a]
+  ok_key = 1
+  bad.key = 2
+";
+        let options = ParseOptions {
+            key_policy: Some(policy.clone()),
+            ..ParseOptions::default()
+        };
+        let err = Conf::parse_str_with(source, &options).unwrap_err();
+        assert_eq!(
+            err,
+            ParseError::new(
+                3,
+                ParseErrorKind::InvalidKeyName {
+                    violation: KeyViolation::DisallowedChar { c: '.' }
+                }
+            )
+        );
+        assert_eq!(
+            err.render(source),
+            "error: invalid key: character '.' is not allowed
+ --> line 3
+  |
+3 |   bad.key = 2
+  |      ^ not allowed in keys
+"
+        );
+
+        let mut section = Section::new("a");
+        assert!(section.set_checked("ok", "1", &policy).is_ok());
+        assert!(section.set_checked("Bad", "2", &policy).is_err());
+        assert_eq!(section.to_string(), "[a]\nok = 1\n");
+    }
+
+    #[test]
+    fn test_sectionless() {
+        let source = "# kernel params\nquiet = 1\nroot = /dev/sda1\n[extra]\nx = 2\n";
+        assert_eq!(
+            Conf::parse_str(source),
+            Err(ParseError::new(2, ParseErrorKind::EntryOutsideOfSection))
+        );
+        let options = ParseOptions {
+            sectionless: true,
+            ..ParseOptions::default()
+        };
+        let conf = Conf::parse_str_with(source, &options).unwrap();
+        assert_eq!(conf.section_names(), vec!["", "extra"]);
+        assert_eq!(conf.get_path(".root"), Some("/dev/sda1"));
+
+        let write_options = WriteOptions {
+            sectionless: true,
+            ..WriteOptions::default()
+        };
+        let text = conf.try_to_string_styled(&write_options).unwrap();
+        assert_eq!(text, "quiet = 1\nroot = /dev/sda1\n\n[extra]\nx = 2\n");
+        assert_eq!(Conf::parse_str_with(&text, &options).unwrap(), conf);
+        assert!(conf.try_to_string_styled(&WriteOptions::default()).is_err());
+
+        let conf = Conf::from_sections(vec![Section::new(""), Section::new("a")]);
+        assert_eq!(conf.to_string_styled(&write_options), "[a]\n");
+        let conf = Conf::from_sections(vec![Section::new("a"), Section::new("")]);
+        assert!(conf.try_to_string_styled(&write_options).is_err());
+    }
+
+    #[test]
+    fn test_control_characters() {
+        let source = "[a]\n  x = a\tb\u{0}c\n";
+        assert!(Conf::parse_str(source).is_ok());
+        let err = Conf::parse_str_with(source, &ParseOptions::strict()).unwrap_err();
+        assert_eq!(
+            err,
+            ParseError::new(2, ParseErrorKind::ControlCharacter { column: 10 })
+        );
+        assert_eq!(
+            err.render(source),
+            "error: control character at column 10\n --> line 2\n  |\n2 |   x = a\tb\u{fffd}c\n  |          ^ control character\n"
+        );
+        assert!(Conf::parse_str_with("[a]\n# \u{1b}\n", &ParseOptions::strict()).is_ok());
+    }
+
+    #[test]
+    fn test_parse_limits() {
+        let parse = |s, limits| {
+            let options = ParseOptions {
+                limits,
+                ..Default::default()
+            };
+            Conf::parse_str_with(s, &options).map_err(|err| err.kind)
+        };
+        let source = "[a]\nx = 1\ny = 2\n[b]\n";
+        assert!(parse(source, ParseLimits::default()).is_ok());
+        assert_eq!(
+            parse(
+                source,
+                ParseLimits {
+                    max_input_size: Some(10),
+                    ..Default::default()
+                }
+            ),
+            Err(ParseErrorKind::InputTooLarge { limit: 10 })
+        );
+        assert_eq!(
+            parse(
+                source,
+                ParseLimits {
+                    max_line_length: Some(4),
+                    ..Default::default()
+                }
+            ),
+            Err(ParseErrorKind::LineTooLong { limit: 4 })
+        );
+        assert_eq!(
+            parse(
+                source,
+                ParseLimits {
+                    max_sections: Some(1),
+                    ..Default::default()
+                }
+            ),
+            Err(ParseErrorKind::TooManySections { limit: 1 })
+        );
+        assert_eq!(
+            parse(
+                source,
+                ParseLimits {
+                    max_entries_per_section: Some(1),
+                    ..Default::default()
+                }
+            ),
+            Err(ParseErrorKind::TooManyEntries { limit: 1 })
+        );
+
+        let path = std::env::temp_dir().join(format!("nbconf-limits-{}.conf", std::process::id()));
+        std::fs::write(&path, source).unwrap();
+        let options = ParseOptions {
+            limits: ParseLimits {
+                max_input_size: Some(source.len()),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert!(Conf::load_with(&path, &options).is_ok());
+        std::fs::write(&path, format!("{}\n", source)).unwrap();
+        assert!(matches!(
+            Conf::load_with(&path, &options),
+            Err(LoadError::Parse(ParseError {
+                kind: ParseErrorKind::InputTooLarge { .. },
+                ..
+            }))
+        ));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_parse_str_named() {
+        let err = Conf::parse_str_named("a.conf", "x = 1").unwrap_err();
+        assert_eq!(err.source_name.as_deref(), Some("a.conf"));
+        assert_eq!(err.to_string(), "a.conf: line 1: entry outside of any section");
+        assert!(Conf::parse_str_named("a.conf", "[a]").is_ok());
+    }
+
+    #[test]
+    fn test_write_split() {
+        let dir = std::env::temp_dir().join(format!("nbconf-split-{}", std::process::id()));
+        let conf = Conf::parse_str("[db]\nx = 1\n[log]\ny = 2\n[db.replica]\nz = 3").unwrap();
+        let paths = conf.write_split(
+            &dir,
+            |s| format!("{}.conf", s.name.split('.').next().unwrap()),
+            &WriteOptions::default(),
+        ).unwrap();
+        assert_eq!(paths, [dir.join("db.conf"), dir.join("log.conf")]);
+        assert_eq!(
+            std::fs::read_to_string(&paths[0]).unwrap(),
+            "[db]\nx = 1\n\n[db.replica]\nz = 3\n"
+        );
+        assert_eq!(std::fs::read_to_string(&paths[1]).unwrap(), "[log]\ny = 2\n");
+
+        let err = conf.write_split(&dir, |s| format!("../{}", s.name), &WriteOptions::default());
+        assert_eq!(err.unwrap_err().kind(), io::ErrorKind::InvalidInput);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_entries() {
+        let mut conf = Conf::parse_str("[a]\nx = 1\n[b]\n[c]\npassword = hunter2\ny = 3").unwrap();
+        for (_, key, value) in conf.entries_mut() {
+            if key == "password" {
+                *value = "***".to_string();
+            }
+        }
+        let entries: Vec<_> = conf.entries().collect();
+        assert_eq!(entries, [("a", "x", "1"), ("c", "password", "***"), ("c", "y", "3")]);
+    }
+
+    #[test]
+    fn test_retain() {
+        let mut conf = Conf::parse_str("[a]\ndebug_x = 1\ny = 2\n[debug]\n[b]\ndebug_z = 3").unwrap();
+        conf.retain_sections(|s| s.name != "debug");
+        for section in conf.iter_mut() {
+            section.retain(|e| !e.key.starts_with("debug_"));
+        }
+        assert_eq!(conf.to_string(), "[a]\ny = 2\n\n[b]\n");
+    }
+
+    #[test]
+    fn test_filter() {
+        let conf = Conf::parse_str("[net]\nport = 1\n[db]\n[logging]\nlevel = info\n[net]").unwrap();
+        let subset = conf.filter(|s| s.name == "logging" || s.name == "net");
+        assert_eq!(subset.to_string(), "[net]\nport = 1\n\n[logging]\nlevel = info\n\n[net]\n");
+        assert_eq!(conf.len(), 4);
+    }
+
+    #[test]
+    fn test_extract() {
+        let mut conf = Conf::parse_str("[web.a]\nx = 1\n[db]\n[web.b]\n[cache]").unwrap();
+        let web = conf.extract(|s| s.name.starts_with("web."));
+        assert_eq!(web.section_names(), vec!["web.a", "web.b"]);
+        assert_eq!(web.get_path("web.a.x"), Some("1"));
+        assert_eq!(conf.section_names(), vec!["db", "cache"]);
+        assert!(conf.extract(|s| s.name == "none").is_empty());
+    }
+
+    #[test]
+    fn test_rename() {
+        let mut conf = Conf::parse_str("[a]\nx = 1\ny = 2\nx = 3\n[b]\n[a]").unwrap();
+        assert!(conf.rename_section("a", "c"));
+        assert!(!conf.rename_section("a", "d"));
+        assert!(conf.sections[0].rename_key("x", "z"));
+        assert!(!conf.sections[0].rename_key("x", "z"));
+        assert_eq!(conf.to_string(), "[c]\nz = 1\ny = 2\nz = 3\n\n[b]\n\n[c]\n");
+    }
+
+    #[test]
+    fn test_section_prefix() {
+        let mut a = Conf::parse_str("[db]\nhost = a\n[log]").unwrap();
+        let mut b = Conf::parse_str("[db]\nhost = b").unwrap();
+        a.prefix_sections("tenant_a.");
+        b.prefix_sections("tenant_b.");
+        a.merge(&b, MergePolicy::Override);
+        assert_eq!(a.get_path("tenant_a.db.host"), Some("a"));
+        assert_eq!(a.get_path("tenant_b.db.host"), Some("b"));
+
+        let mut tenant_b = a.extract(|s| s.name.starts_with("tenant_b."));
+        assert!(tenant_b.strip_section_prefix("tenant_b."));
+        assert!(!tenant_b.strip_section_prefix("tenant_b."));
+        assert!(!tenant_b.strip_section_prefix(""));
+        assert_eq!(tenant_b.to_string(), "[db]\nhost = b\n");
+        assert_eq!(a.section_names(), vec!["tenant_a.db", "tenant_a.log"]);
+    }
+
+    #[test]
+    fn test_remove_section() {
+        let mut conf = Conf::parse_str("[a]\nx = 1\n[b]\n[a]\nx = 2\n[c]\n[a]").unwrap();
+        assert_eq!(conf.remove_section("a").unwrap().get("x"), Some("1"));
+        assert_eq!(conf.remove_section("d"), None);
+        assert_eq!(conf.remove_sections("a").len(), 2);
+        assert_eq!(conf.section_names(), ["b", "c"]);
+    }
+
+    #[test]
+    fn test_move_entry() {
+        let mut conf = Conf::parse_str("[a]\nx = 1\ny = 2\n[b]\ny = 3").unwrap();
+        assert!(conf.move_entry("a", "x", "c"));
+        assert!(conf.move_entry("a", "y", "b"));
+        assert!(!conf.move_entry("a", "y", "b"));
+        assert!(!conf.move_entry("d", "y", "b"));
+        assert_eq!(conf.to_string(), "[a]\n\n[b]\ny = 2\n\n[c]\nx = 1\n");
+    }
+
+    #[test]
+    fn test_sort_by() {
+        let mut conf = Conf::parse_str("[b]\ny = 1\nx = 2\n[a]\n[core]\nz = 3\nzz = 4").unwrap();
+        conf.sort_sections_by_name();
+        assert_eq!(conf.section_names(), ["a", "b", "core"]);
+
+        // House style: core first, then the rest in order.
+        conf.sort_sections_by(|a, b| (b.name == "core").cmp(&(a.name == "core")));
+        assert_eq!(conf.section_names(), ["core", "a", "b"]);
+
+        conf.sections[0].sort_entries_by(|a, b| b.key.len().cmp(&a.key.len()));
+        assert_eq!(conf.sections[0].to_string(), "[core]\nzz = 4\nz = 3\n");
+    }
+
+    #[test]
+    fn test_dedup() {
+        let input = "[a]\nx = 1\ny = 2\nx = 3\n[b]\n[a]\ny = 4\nz = 5";
+        let dedup_sections = |policy| {
+            let mut conf = Conf::parse_str(input).unwrap();
+            conf.dedup_sections(policy);
+            conf.to_string()
+        };
+        assert_eq!(dedup_sections(DedupPolicy::KeepFirst), "[a]\nx = 1\ny = 2\nx = 3\n\n[b]\n");
+        assert_eq!(dedup_sections(DedupPolicy::KeepLast), "[b]\n\n[a]\ny = 4\nz = 5\n");
+        assert_eq!(dedup_sections(DedupPolicy::Merge), "[a]\nx = 1\ny = 4\nx = 3\nz = 5\n\n[b]\n");
+
+        let dedup_keys = |policy| {
+            let mut conf = Conf::parse_str(input).unwrap();
+            conf.sections[0].dedup_keys(policy);
+            conf.sections[0].to_string()
+        };
+        assert_eq!(dedup_keys(DedupPolicy::KeepFirst), "[a]\nx = 1\ny = 2\n");
+        assert_eq!(dedup_keys(DedupPolicy::KeepLast), "[a]\ny = 2\nx = 3\n");
+        assert_eq!(dedup_keys(DedupPolicy::Merge), "[a]\nx = 1, 3\ny = 2\n");
+    }
+
+    #[test]
+    fn test_find_duplicates() {
+        let conf = Conf::parse_str("[a]\nx = 1\ny = 2\nx = 3\n[b]\n[a]\ny = 4\n[b]\nz = 5\nz = 6").unwrap();
+        assert_eq!(conf.find_duplicates(), DuplicateReport {
+            sections: vec![
+                DuplicateSection { name: "a".to_string(), indices: vec![0, 2] },
+                DuplicateSection { name: "b".to_string(), indices: vec![1, 3] },
+            ],
+            keys: vec![
+                DuplicateKey { section: 0, key: "x".to_string(), indices: vec![0, 2] },
+                DuplicateKey { section: 3, key: "z".to_string(), indices: vec![0, 1] },
+            ],
+        });
+        assert!(Conf::parse_str("[a]\nx = 1\n[b]\nx = 2").unwrap().find_duplicates().is_empty());
+    }
+
+    #[test]
+    fn test_len() {
+        let conf = Conf::parse_str("[a]\nx = 1\ny = 2\n[b]\n[c]\nz = 3").unwrap();
+        assert_eq!((conf.len(), conf.total_entries()), (3, 3));
+        assert_eq!((conf.sections[0].len(), conf.sections[1].is_empty()), (2, true));
+        assert!(Conf::default().is_empty());
+        assert!(Section::default().is_empty());
+    }
+
+    #[test]
+    fn test_get_path() {
+        let conf = Conf::parse_str("[server]\nport = 80\ntls.cert = a\n[server.tls]\nkey = b\n[server]\nhost = c").unwrap();
+        assert_eq!(conf.get_path("server.port"), Some("80"));
+        assert_eq!(conf.get_path("server.tls.cert"), Some("a"));
+        assert_eq!(conf.get_path("server.tls.key"), Some("b"));
+        assert_eq!(conf.get_path("server.host"), Some("c"));
+        assert_eq!(conf.get_path("server"), None);
+        assert_eq!(conf.get_path("server.other"), None);
+    }
+
+    #[test]
+    fn test_set_path() {
+        let mut conf = Conf::parse_str("[server]\nhost = a\n[server]\nport = 80\n[server.tls]").unwrap();
+        assert!(conf.set_path("server.port", "8080"));
+        assert!(conf.set_path("server.timeout", "5s"));
+        assert!(conf.set_path("server.tls.cert", "c"));
+        assert!(conf.set_path("log.file.path", "d"));
+        assert!(!conf.set_path("server", "x"));
+        assert_eq!(
+            conf.to_string(),
+            "[server]\nhost = a\ntimeout = 5s\n\n[server]\nport = 8080\n\n[server.tls]\ncert = c\n\n[log.file]\npath = d\n"
+        );
+
+        assert!(conf.remove_path("server.port"));
+        assert!(!conf.remove_path("server.port"));
+        assert!(conf.remove_path("log.file.path"));
+        assert!(!conf.remove_path("other.key"));
+        assert_eq!(conf.get_path("server.port"), None);
+        assert!(conf.sections[3].is_empty());
+    }
+
+    #[test]
+    fn test_parent_and_children() {
+        let conf = Conf::parse_str("[net]\n[net.tls]\n[net.tls.client]\n[net.dns]").unwrap();
+        assert_eq!(conf.sections[0].parent(), None);
+        assert_eq!(conf.sections[2].parent(), Some("net.tls"));
+        let children: Vec<&str> = conf.children("net").map(|s| s.name.as_str()).collect();
+        assert_eq!(children, ["net.tls", "net.dns"]);
+    }
+
+    #[test]
+    fn test_sections_matching() {
+        assert!(glob_match("peer.*", "peer."));
+        assert!(glob_match("*.node?", "peer.node1"));
+        assert!(glob_match("a*b*c", "abxbyc"));
+        assert!(!glob_match("a*b*c", "abxbyd"));
+        assert!(!glob_match("peer.?", "peer.10"));
+
+        let conf = Conf::parse_str("[peer.node1]\n[peer]\n[peer.node2]\n[peers]").unwrap();
+        let names: Vec<&str> = conf.sections_matching("peer.*").map(|s| s.name.as_str()).collect();
+        assert_eq!(names, ["peer.node1", "peer.node2"]);
+    }
+
+    #[test]
+    fn test_conf_macro() {
+        assert_eq!(conf! {}, Conf::new());
+        let port = 8080;
+        assert_eq!(
+            conf! { [a] x = port; y = "b"; [b] [a] },
+            ConfBuilder::new()
+                .section("a").entry("x", "8080").entry("y", "b")
+                .section("b")
+                .section("a")
+                .build()
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "entry \"port\" added before any section")]
+    fn test_builder_entry_outside_of_section() {
+        ConfBuilder::new().entry("port", "8080");
+    }
+
+    #[test]
+    fn test_sections_named() {
+        let conf = Conf::parse_str("[Peer]\nkey = a\n[Interface]\n[Peer]\nkey = b\nip = c").unwrap();
+        let keys: Vec<&str> = conf.sections_named("Peer").filter_map(|s| s.get("key")).collect();
+        assert_eq!(keys, ["a", "b"]);
+        assert_eq!(conf.merged_section("Peer").unwrap().to_string(), "[Peer]\nkey = a\nkey = b\nip = c\n");
+        assert_eq!(conf.merged_section("Other"), None);
+    }
+
+    #[test]
+    fn test_semantic_eq() {
+        let a = Conf::parse_str("[a]\nx = 1\ny = 2\n[b]\nz = 3\n[a]").unwrap();
+        let b = Conf::parse_str("[a]\n[b]\nz=3\n[a]\ny = 2\nx = 1").unwrap();
+        assert!(a.semantic_eq(&b));
+        assert!(!a.semantic_eq(&Conf::parse_str("[a]\nx = 1\ny = 2\n[b]\nz = 3").unwrap()));
+        assert!(!a.semantic_eq(&Conf::parse_str("[a]\nx = 1\n[b]\nz = 3\n[a]\ny = 2").unwrap()));
+        let c = Conf::parse_str("[a]\nx = 1\ny = 0\nx = 2").unwrap();
+        assert!(c.semantic_eq(&Conf::parse_str("[a]\ny = 0\nx = 1\nx = 2").unwrap()));
+        assert!(!c.semantic_eq(&Conf::parse_str("[a]\nx = 2\ny = 0\nx = 1").unwrap()));
+        let d = Conf::parse_str("[a]\nx = 1\nx = 2").unwrap();
+        let e = Conf::parse_str("[a]\nx = 2\nx = 1").unwrap();
+        assert!(!d.semantic_eq(&e));
+        assert_ne!(d.digest(), e.digest());
+
+        let mut set = std::collections::HashSet::new();
+        set.insert(Normalized::new(a));
+        assert!(set.contains(&Normalized::new(b)));
+    }
+
+    #[test]
+    fn test_canonicalize() {
+        let mut a = Conf::parse_str("[b]\nz = 3\n[a]\ny = 2\nx = 1\n[b]\nz = 4").unwrap();
+        a.sections[1].entries[0].value = " 2 ".to_string();
+        let mut b = Conf::parse_str("[a]\nx=1\ny=2\n[b]\nz=4").unwrap();
+        a.canonicalize(DedupPolicy::KeepLast);
+        b.canonicalize(DedupPolicy::KeepLast);
+        assert_eq!(a.to_string(), "[a]\nx = 1\ny = 2\n\n[b]\nz = 4\n");
+        assert_eq!(a.to_string(), b.to_string());
+    }
+
+    #[test]
+    fn test_digest() {
+        let a = Conf::parse_str("# comment\n[a]\nx = 1\ny = 2\n[b]").unwrap();
+        let mut b = Conf::parse_str("[b]\n[a]\ny=2\nx=1").unwrap();
+        assert_eq!(a.digest(), b.digest());
+        assert_eq!(Conf::new().digest(), 0xcbf2_9ce4_8422_2325);
+
+        b.sections[1].entries[0].value = " 2 ".to_string();
+        assert_eq!(a.digest(), b.digest());
+        b.sections[1].entries[0].value = "3".to_string();
+        assert_ne!(a.digest(), b.digest());
+        assert_ne!(
+            Conf::parse_str("[a]\nx = 1\n[b]").unwrap().digest(),
+            Conf::parse_str("[a]\n[b]\nx = 1").unwrap().digest()
+        );
+    }
+
+    #[test]
+    fn test_conf_section_names() {
+        let conf = Conf::from_sections(vec![
+            Section::new("sec1"),
+            Section::new("sec2"),
+        ]);
+        assert_eq!(conf.section_names(), vec!["sec1", "sec2"]);
+    }
+
+    #[test]
+    fn test_missing_closing_bracket() {
+        assert_eq!(
+            Conf::parse_str("[mySection"),
+            Err(ParseError::new(1, ParseErrorKind::MissingClosingBracket))
+        );
+    }
+
+    #[test]
+    fn test_missing_equals() {
         assert_eq!(
             Conf::parse_str("[mySection]\nmyKey"),
             Err(ParseError::new(2, ParseErrorKind::MissingEquals))
         );
     }
 
+    #[test]
+    fn test_parse_bytes() {
+        let input = b"[mySection]\na = \xffb";
+        assert_eq!(
+            Conf::parse_bytes(input, Decoding::Strict),
+            Err(ParseError::new(2, ParseErrorKind::InvalidUtf8 { offset: 16 }))
+        );
+        let conf = Conf::parse_bytes(input, Decoding::Lossy).unwrap();
+        assert_eq!(conf.sections[0].get("a"), Some("\u{fffd}b"));
+        let conf = Conf::parse_bytes(b"[sec]\na = caf\xe9 \xc3\xa9", Decoding::Latin1).unwrap();
+        assert_eq!(conf.sections[0].get("a"), Some("caf\u{e9} \u{e9}"));
+    }
+
     #[test]
     fn test_entry_outside_of_section() {
         assert_eq!(
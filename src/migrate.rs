@@ -0,0 +1,182 @@
+//! Upgrading configs written for older versions of an application.
+//!
+//! Example:
+//!
+//! ```
+//! use nbconf::migrate::Migrations;
+//! use nbconf::Conf;
+//!
+//! let migrations = Migrations::new("meta.version")
+//!     .step(2, |conf| {
+//!         conf.rename_section("db", "database");
+//!     })
+//!     .step(3, |conf| {
+//!         conf.set_path("database.pool", "4");
+//!     });
+//!
+//! let mut conf = Conf::parse_str("[meta]\nversion = 1\n[db]\nhost = a\n").unwrap();
+//! assert_eq!(migrations.upgrade(&mut conf), Ok(2));
+//! assert_eq!(
+//!     conf.to_string(),
+//!     "[meta]\nversion = 3\n\n[database]\nhost = a\npool = 4\n"
+//! );
+//! ```
+
+use crate::Conf;
+
+/// Error produced from [`Migrations::upgrade`].
+///
+/// [`Migrations::upgrade`]: struct.Migrations.html#method.upgrade
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum MigrationError {
+    /// The version entry is not a non-negative integer.
+    InvalidVersion(String),
+    /// The config is from a newer version than any step.
+    TooNew { version: u32, latest: u32 },
+}
+
+impl std::fmt::Display for MigrationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            MigrationError::InvalidVersion(value) => {
+                write!(f, "invalid config version {:?}", value)
+            }
+            MigrationError::TooNew { version, latest } => write!(
+                f,
+                "config version {} is newer than the latest supported version {}",
+                version, latest
+            ),
+        }
+    }
+}
+
+impl std::error::Error for MigrationError {}
+
+/// A step of [`Migrations`].
+///
+/// [`Migrations`]: struct.Migrations.html
+struct Step {
+    version: u32,
+    migrate: Box<dyn Fn(&mut Conf) + Send + Sync>,
+}
+
+/// Steps that upgrade a config from one version to the next.
+///
+/// The version is stored in the config at a dotted path, such as
+/// `meta.version`. A config without it is taken to be version 0.
+pub struct Migrations {
+    version_path: String,
+    steps: Vec<Step>,
+}
+
+impl std::fmt::Debug for Migrations {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let versions: Vec<u32> = self.steps.iter().map(|s| s.version).collect();
+        f.debug_struct("Migrations")
+            .field("version_path", &self.version_path)
+            .field("steps", &versions)
+            .finish()
+    }
+}
+
+impl Migrations {
+    /// Create a registry with no steps, storing the version at the dotted
+    /// `version_path`.
+    pub fn new(version_path: &str) -> Migrations {
+        Migrations {
+            version_path: version_path.to_string(),
+            steps: Vec::new(),
+        }
+    }
+
+    /// Add the step that upgrades a config to `version` from the version
+    /// before it. A step replaces any earlier one for the same version.
+    pub fn step<F>(mut self, version: u32, migrate: F) -> Migrations
+    where
+        F: Fn(&mut Conf) + Send + Sync + 'static,
+    {
+        self.steps.retain(|s| s.version != version);
+        self.steps.push(Step {
+            version,
+            migrate: Box::new(migrate),
+        });
+        self.steps.sort_by_key(|s| s.version);
+        self
+    }
+
+    /// The version that [`upgrade`] brings configs to.
+    ///
+    /// [`upgrade`]: #method.upgrade
+    pub fn latest(&self) -> u32 {
+        self.steps.last().map_or(0, |s| s.version)
+    }
+
+    /// The version of `conf`.
+    pub fn version(&self, conf: &Conf) -> Result<u32, MigrationError> {
+        match conf.get_path(&self.version_path) {
+            Some(value) => value
+                .parse()
+                .map_err(|_| MigrationError::InvalidVersion(value.to_string())),
+            None => Ok(0),
+        }
+    }
+
+    /// Apply each step newer than the version of `conf`, in order,
+    /// updating the version after each one. Returns the number of steps
+    /// applied.
+    pub fn upgrade(&self, conf: &mut Conf) -> Result<usize, MigrationError> {
+        let version = self.version(conf)?;
+        if version > self.latest() {
+            return Err(MigrationError::TooNew {
+                version,
+                latest: self.latest(),
+            });
+        }
+        let mut applied = 0;
+        for step in self.steps.iter().filter(|s| s.version > version) {
+            (step.migrate)(conf);
+            conf.set_path(&self.version_path, &step.version.to_string());
+            applied += 1;
+        }
+        Ok(applied)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_upgrade() {
+        let migrations = Migrations::new("meta.version")
+            .step(2, |conf| {
+                conf.set_path("a.two", "2");
+            })
+            .step(1, |conf| {
+                conf.set_path("a.one", "1");
+            });
+        assert_eq!(migrations.latest(), 2);
+
+        let mut conf = Conf::new();
+        assert_eq!(migrations.upgrade(&mut conf), Ok(2));
+        assert_eq!(
+            conf.to_string(),
+            "[a]\none = 1\ntwo = 2\n\n[meta]\nversion = 2\n"
+        );
+        assert_eq!(migrations.upgrade(&mut conf), Ok(0));
+
+        conf.set_path("meta.version", "3");
+        assert_eq!(
+            migrations.upgrade(&mut conf),
+            Err(MigrationError::TooNew {
+                version: 3,
+                latest: 2
+            })
+        );
+        conf.set_path("meta.version", "x");
+        assert_eq!(
+            migrations.upgrade(&mut conf),
+            Err(MigrationError::InvalidVersion("x".to_string()))
+        );
+    }
+}
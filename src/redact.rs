@@ -0,0 +1,181 @@
+//! Hiding secret values in output.
+//!
+//! Example:
+//!
+//! ```
+//! use nbconf::redact::Redaction;
+//! use nbconf::Conf;
+//!
+//! let conf = Conf::parse_str("[db]\nuser = app\npassword = hunter2\n").unwrap();
+//! let redaction = Redaction::new().pattern("*password*");
+//! assert_eq!(
+//!     conf.to_string_redacted(&redaction),
+//!     "[db]\nuser = app\npassword = ***\n"
+//! );
+//! ```
+
+use crate::{glob_match, is_comment, Conf, ParseError};
+
+/// Text that replaces secret values.
+pub const REDACTED: &str = "***";
+
+/// Which keys hold secret values.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Redaction {
+    /// Glob patterns, which may use `*` and `?`, matched against keys
+    /// without regard to ASCII case.
+    pub patterns: Vec<String>,
+}
+
+impl Redaction {
+    /// Create a redaction that hides nothing.
+    pub fn new() -> Redaction {
+        Redaction::default()
+    }
+
+    /// Also hide the values of keys matching `pattern`.
+    pub fn pattern(mut self, pattern: &str) -> Redaction {
+        self.patterns.push(pattern.to_string());
+        self
+    }
+
+    /// Whether the value of `key` is secret.
+    pub fn is_secret(&self, key: &str) -> bool {
+        let key = key.to_ascii_lowercase();
+        self.patterns
+            .iter()
+            .any(|pattern| glob_match(&pattern.to_ascii_lowercase(), &key))
+    }
+
+    /// Copy of `conf` with secret values replaced by [`REDACTED`].
+    ///
+    /// [`REDACTED`]: constant.REDACTED.html
+    pub fn apply(&self, conf: &Conf) -> Conf {
+        let mut conf = conf.clone();
+        for section in conf.iter_mut() {
+            for entry in section.iter_mut() {
+                if self.is_secret(&entry.key) {
+                    entry.value = REDACTED.to_string();
+                }
+            }
+        }
+        conf
+    }
+
+    /// Replace secret values in config text, leaving everything else as
+    /// it is. Lines that don't parse as entries are left alone.
+    pub fn redact_text(&self, text: &str) -> String {
+        let mut output = String::new();
+        // Keep a byte order mark, but don't read it as part of the first key.
+        let text = match text.strip_prefix('\u{feff}') {
+            Some(rest) => {
+                output.push('\u{feff}');
+                rest
+            }
+            None => text,
+        };
+        for line in text.split_inclusive('\n') {
+            let trimmed = line.trim_start();
+            let equals = match line.find('=') {
+                Some(equals) if !is_comment(trimmed) && !trimmed.starts_with('[') => equals,
+                _ => {
+                    output += line;
+                    continue;
+                }
+            };
+            let rest = &line[equals + 1..];
+            let value = rest.trim();
+            if value.is_empty() || !self.is_secret(line[..equals].trim()) {
+                output += line;
+                continue;
+            }
+            let start = equals + 1 + (rest.len() - rest.trim_start().len());
+            output += &line[..start];
+            output += REDACTED;
+            output += &line[start + value.len()..];
+        }
+        output
+    }
+}
+
+/// A config displayed with its secret values hidden. Returned by
+/// [`Conf::redacted`].
+///
+/// [`Conf::redacted`]: ../struct.Conf.html#method.redacted
+pub struct Redacted<'a> {
+    conf: &'a Conf,
+    redaction: &'a Redaction,
+}
+
+impl std::fmt::Debug for Redacted<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        self.redaction.apply(self.conf).fmt(f)
+    }
+}
+
+impl std::fmt::Display for Redacted<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        self.redaction.apply(self.conf).fmt(f)
+    }
+}
+
+impl Conf {
+    /// Serialize the config as a string, with secret values replaced by
+    /// [`REDACTED`].
+    ///
+    /// [`REDACTED`]: redact/constant.REDACTED.html
+    pub fn to_string_redacted(&self, redaction: &Redaction) -> String {
+        self.redacted(redaction).to_string()
+    }
+
+    /// Wrap the config so that its `Debug` and `Display` output hides
+    /// secret values, for logging.
+    pub fn redacted<'a>(&'a self, redaction: &'a Redaction) -> Redacted<'a> {
+        Redacted {
+            conf: self,
+            redaction,
+        }
+    }
+}
+
+impl ParseError {
+    /// Render the error as by [`render`], with secret values in the
+    /// quoted source line hidden.
+    ///
+    /// [`render`]: ../struct.ParseError.html#method.render
+    pub fn render_redacted(&self, source: &str, redaction: &Redaction) -> String {
+        self.render(&redaction.redact_text(source))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redaction() {
+        let redaction = Redaction::new().pattern("*password*").pattern("token");
+        let source =
+            "[db]\nuser = app\nDB_Password =  hunter2 \n# token = abc\ntoken=\n[token = x]\n";
+        assert_eq!(
+            redaction.redact_text(source),
+            "[db]\nuser = app\nDB_Password =  *** \n# token = abc\ntoken=\n[token = x]\n"
+        );
+
+        let conf = Conf::parse_str("[db]\nuser = app\ntoken = abc\n").unwrap();
+        assert_eq!(
+            conf.redacted(&redaction).to_string(),
+            "[db]\nuser = app\ntoken = ***\n"
+        );
+        assert!(!format!("{:?}", conf.redacted(&redaction)).contains("abc"));
+
+        let source = "token = abc\n";
+        let err = Conf::parse_str(source).unwrap_err();
+        assert!(!err.render_redacted(source, &redaction).contains("abc"));
+
+        let source = "\u{feff}token = abc\n";
+        assert_eq!(redaction.redact_text(source), "\u{feff}token = ***\n");
+        let err = Conf::parse_str(source).unwrap_err();
+        assert!(!err.render_redacted(source, &redaction).contains("abc"));
+    }
+}
@@ -0,0 +1,387 @@
+//! Collapsing sections that only apply in some situations into plain
+//! sections.
+
+use crate::{Conf, Section};
+use std::collections::HashMap;
+
+/// Values that conditional section headers such as `[paths if os=windows]`
+/// are checked against by [`Conf::resolve_conditions`].
+///
+/// [`Conf::resolve_conditions`]: ../struct.Conf.html#method.resolve_conditions
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ConditionContext {
+    /// Value of each name that conditions can test.
+    pub values: HashMap<String, String>,
+}
+
+impl ConditionContext {
+    /// Create an empty context.
+    pub fn new() -> ConditionContext {
+        ConditionContext::default()
+    }
+
+    /// A context with `os` and `arch` set to those of the running
+    /// platform, as in `std::env::consts`, and `hostname` set if it is
+    /// known from the `HOSTNAME` environment variable or `/etc/hostname`.
+    pub fn platform() -> ConditionContext {
+        let mut context = ConditionContext::new()
+            .with("os", std::env::consts::OS)
+            .with("arch", std::env::consts::ARCH);
+        let hostname = std::env::var("HOSTNAME")
+            .ok()
+            .or_else(|| std::fs::read_to_string("/etc/hostname").ok())
+            .map(|name| name.trim().to_string())
+            .filter(|name| !name.is_empty());
+        if let Some(hostname) = hostname {
+            context = context.with("hostname", &hostname);
+        }
+        context
+    }
+
+    /// Set `name` to `value`.
+    pub fn with(mut self, name: &str, value: &str) -> ConditionContext {
+        self.values.insert(name.to_string(), value.to_string());
+        self
+    }
+
+    /// Whether a single condition holds: `name=value`, `name!=value`,
+    /// `name` (set to anything but `false`), or `!name`.
+    fn check(&self, condition: &str) -> bool {
+        let value = |name: &str| self.values.get(name.trim()).map(String::as_str);
+        if let Some((name, expected)) = condition.split_once("!=") {
+            value(name) != Some(expected.trim())
+        } else if let Some((name, expected)) = condition.split_once('=') {
+            value(name) == Some(expected.trim())
+        } else if let Some(name) = condition.strip_prefix('!') {
+            value(name).is_none_or(|v| v == "false")
+        } else {
+            value(condition).is_some_and(|v| v != "false")
+        }
+    }
+}
+
+/// The specific type of inheritance error.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum InheritanceErrorKind {
+    /// The parent section does not exist.
+    MissingParent { parent: String },
+    /// The section inherits from itself, directly or through others.
+    Cycle,
+}
+
+/// Error produced from [`Conf::resolve_inheritance`].
+///
+/// [`Conf::resolve_inheritance`]: ../struct.Conf.html#method.resolve_inheritance
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct InheritanceError {
+    /// Name of the section that could not be resolved.
+    pub section: String,
+    /// Type of error.
+    pub kind: InheritanceErrorKind,
+}
+
+impl std::fmt::Display for InheritanceError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "section {:?}: ", self.section)?;
+        match &self.kind {
+            InheritanceErrorKind::MissingParent { parent } => {
+                write!(f, "parent section {:?} not found", parent)
+            }
+            InheritanceErrorKind::Cycle => write!(f, "section inherits from itself"),
+        }
+    }
+}
+
+impl std::error::Error for InheritanceError {}
+
+/// Split a `base:profile` section name, with no whitespace around the
+/// colon.
+fn split_profile(name: &str) -> Option<(&str, &str)> {
+    let (base, profile) = name.rsplit_once(':')?;
+    if base.ends_with(char::is_whitespace) || profile.starts_with(char::is_whitespace) {
+        return None;
+    }
+    Some((base.trim(), profile.trim()))
+}
+
+/// Split a `child : parent` section name, with whitespace on both sides
+/// of the colon, which tells it apart from a `base:profile` name.
+fn split_parent(name: &str) -> Option<(&str, &str)> {
+    let (child, parent) = name.rsplit_once(':')?;
+    if !child.ends_with(char::is_whitespace) || !parent.starts_with(char::is_whitespace) {
+        return None;
+    }
+    Some((child.trim(), parent.trim()))
+}
+
+/// Split a `base if conditions` section name.
+fn split_conditions(name: &str) -> Option<(&str, &str)> {
+    let (base, conditions) = name.split_once(" if ")?;
+    Some((base.trim(), conditions.trim()))
+}
+
+/// Resolve the entries of section `index` into `resolved`, resolving its
+/// parents first. `headers` holds the name and parent of each section;
+/// `active` marks the sections being resolved, to find cycles.
+fn inherit(
+    conf: &Conf,
+    headers: &[(&str, Option<&str>)],
+    index: usize,
+    active: &mut Vec<bool>,
+    resolved: &mut Vec<Option<Section>>,
+) -> Result<(), InheritanceError> {
+    if resolved[index].is_some() {
+        return Ok(());
+    }
+    let (name, parent) = headers[index];
+    let error = |kind| InheritanceError {
+        section: name.to_string(),
+        kind,
+    };
+    if active[index] {
+        return Err(error(InheritanceErrorKind::Cycle));
+    }
+    let mut section = Section::new(name);
+    if let Some(parent) = parent {
+        let parent_index = headers
+            .iter()
+            .position(|(n, _)| *n == parent)
+            .ok_or_else(|| {
+                error(InheritanceErrorKind::MissingParent {
+                    parent: parent.to_string(),
+                })
+            })?;
+        active[index] = true;
+        inherit(conf, headers, parent_index, active, resolved)?;
+        active[index] = false;
+        section.entries = resolved[parent_index].as_ref().unwrap().entries.clone();
+    }
+    for entry in conf.sections[index].iter() {
+        section.set(&entry.key, &entry.value);
+    }
+    resolved[index] = Some(section);
+    Ok(())
+}
+
+/// Set each entry of the override sections in the first section of
+/// `resolved` with the same base name, adding the section if needed.
+fn overlay(resolved: &mut Conf, overrides: Vec<(&str, &Section)>) {
+    for (base, section) in overrides {
+        let index = match resolved.sections.iter().position(|s| s.name == base) {
+            Some(index) => index,
+            None => {
+                resolved.sections.push(Section::new(base));
+                resolved.sections.len() - 1
+            }
+        };
+        for entry in section.iter() {
+            resolved.sections[index].set(&entry.key, &entry.value);
+        }
+    }
+}
+
+impl Conf {
+    /// Apply the sections for `profile`, such as `[database:production]`,
+    /// on top of the plain sections.
+    ///
+    /// Each entry in a section for the profile overrides the first entry
+    /// with the same key in the first plain section with the same base
+    /// name, or is added to it. If there is no such section it is added.
+    /// Sections for other profiles are dropped. A header with whitespace
+    /// around the colon, such as `[child : parent]`, is for
+    /// [`resolve_inheritance`] and is kept as it is.
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// use nbconf::Conf;
+    ///
+    /// let conf = Conf::parse_str(
+    ///     "[db]\nhost = localhost\nport = 5432\n[db:prod]\nhost = db.example.com\n[db:dev]\nport = 1\n",
+    /// )
+    /// .unwrap();
+    /// assert_eq!(
+    ///     conf.resolve_profile("prod").to_string(),
+    ///     "[db]\nhost = db.example.com\nport = 5432\n"
+    /// );
+    /// ```
+    ///
+    /// [`resolve_inheritance`]: #method.resolve_inheritance
+    pub fn resolve_profile(&self, profile: &str) -> Conf {
+        let mut resolved = Conf::new();
+        let mut overrides = Vec::new();
+        for section in self.iter() {
+            match split_profile(&section.name) {
+                Some((base, p)) if p == profile => overrides.push((base, section)),
+                Some(_) => {}
+                None => resolved.sections.push(section.clone()),
+            }
+        }
+        overlay(&mut resolved, overrides);
+        resolved
+    }
+
+    /// Apply the sections with conditional headers, such as
+    /// `[paths if os=windows]`, whose conditions hold in `context`.
+    ///
+    /// Conditions are joined with `and`. Each is one of `name=value`,
+    /// `name!=value`, `name` (set to anything but `false`), or `!name`.
+    /// The entries of a section whose conditions hold override those of
+    /// the plain section with the same base name, as in
+    /// [`resolve_profile`]. Other conditional sections are dropped.
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// use nbconf::resolve::ConditionContext;
+    /// use nbconf::Conf;
+    ///
+    /// let conf = Conf::parse_str(
+    ///     "[paths]\ndata = /var/lib/app\n[paths if os=windows]\ndata = C:\\app\n",
+    /// )
+    /// .unwrap();
+    /// let context = ConditionContext::new().with("os", "windows");
+    /// assert_eq!(conf.resolve_conditions(&context).get_path("paths.data"), Some("C:\\app"));
+    /// ```
+    ///
+    /// [`resolve_profile`]: #method.resolve_profile
+    pub fn resolve_conditions(&self, context: &ConditionContext) -> Conf {
+        let mut resolved = Conf::new();
+        let mut overrides = Vec::new();
+        for section in self.iter() {
+            match split_conditions(&section.name) {
+                Some((base, conditions)) => {
+                    if conditions.split(" and ").all(|c| context.check(c.trim())) {
+                        overrides.push((base, section));
+                    }
+                }
+                None => resolved.sections.push(section.clone()),
+            }
+        }
+        overlay(&mut resolved, overrides);
+        resolved
+    }
+
+    /// Resolve section headers of the form `[child : parent]`, so that the
+    /// child starts with the entries of the parent and overrides them
+    /// selectively. The result has plain `[child]` headers.
+    ///
+    /// The parent is the first section with that name, after its own
+    /// inheritance is resolved, so chains of inheritance work. The
+    /// whitespace around the colon is required, so that `[db:prod]`
+    /// headers for [`resolve_profile`] are kept as they are.
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// use nbconf::Conf;
+    ///
+    /// let conf = Conf::parse_str("[base]\nport = 80\nworkers = 2\n[web1 : base]\nworkers = 8\n").unwrap();
+    /// assert_eq!(
+    ///     conf.resolve_inheritance().unwrap().to_string(),
+    ///     "[base]\nport = 80\nworkers = 2\n\n[web1]\nport = 80\nworkers = 8\n"
+    /// );
+    /// ```
+    ///
+    /// [`resolve_profile`]: #method.resolve_profile
+    pub fn resolve_inheritance(&self) -> Result<Conf, InheritanceError> {
+        let headers: Vec<(&str, Option<&str>)> = self
+            .iter()
+            .map(|s| match split_parent(&s.name) {
+                Some((child, parent)) => (child, Some(parent)),
+                None => (s.name.as_str(), None),
+            })
+            .collect();
+        let mut active = vec![false; headers.len()];
+        let mut resolved = vec![None; headers.len()];
+        for index in 0..headers.len() {
+            inherit(self, &headers, index, &mut active, &mut resolved)?;
+        }
+        Ok(Conf::from_sections(
+            resolved.into_iter().map(Option::unwrap).collect(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_profile() {
+        let conf = Conf::parse_str(
+            "[web:prod]\nworkers = 8\n[db]\nhost = a\n[web]\nworkers = 1\ndebug = true\n[cache:prod]\nsize = 10\n[db:dev]\nhost = b\n\
+             [web2 : web]\n",
+        )
+        .unwrap();
+        assert_eq!(
+            conf.resolve_profile("prod").to_string(),
+            "[db]\nhost = a\n\n[web]\nworkers = 8\ndebug = true\n\n[web2 : web]\n\n[cache]\nsize = 10\n"
+        );
+        assert_eq!(
+            conf.resolve_profile("dev").to_string(),
+            "[db]\nhost = b\n\n[web]\nworkers = 1\ndebug = true\n\n[web2 : web]\n"
+        );
+    }
+
+    #[test]
+    fn test_resolve_conditions() {
+        let conf = Conf::parse_str(
+            "[a]\nx = 1\n[a if os=linux and debug]\nx = 2\n[a if os!=linux]\nx = 3\n[b if !debug]\ny = 4\n",
+        )
+        .unwrap();
+        let context = ConditionContext::new().with("os", "linux");
+        assert_eq!(
+            conf.resolve_conditions(&context).to_string(),
+            "[a]\nx = 1\n\n[b]\ny = 4\n"
+        );
+        let context = context.with("debug", "true");
+        assert_eq!(
+            conf.resolve_conditions(&context).to_string(),
+            "[a]\nx = 2\n"
+        );
+        let context = ConditionContext::new()
+            .with("os", "windows")
+            .with("debug", "false");
+        assert_eq!(
+            conf.resolve_conditions(&context).to_string(),
+            "[a]\nx = 3\n\n[b]\ny = 4\n"
+        );
+        assert_eq!(
+            ConditionContext::platform()
+                .values
+                .get("os")
+                .map(String::as_str),
+            Some(std::env::consts::OS)
+        );
+    }
+
+    #[test]
+    fn test_resolve_inheritance() {
+        let conf =
+            Conf::parse_str("[leaf : mid]\nc = 3\n[root]\na = 1\nb = 1\n[mid\t:  root]\nb = 2\n")
+                .unwrap();
+        assert_eq!(
+            conf.resolve_inheritance().unwrap().to_string(),
+            "[leaf]\na = 1\nb = 2\nc = 3\n\n[root]\na = 1\nb = 1\n\n[mid]\na = 1\nb = 2\n"
+        );
+
+        // Profile sections and other colons are not inheritance.
+        let conf = Conf::parse_str("[db:prod]\nx = 1\n[a :b]\n").unwrap();
+        assert_eq!(conf.resolve_inheritance().unwrap(), conf);
+
+        let conf = Conf::parse_str("[a : b]\n[b : c]\n[c : a]\n").unwrap();
+        assert_eq!(
+            conf.resolve_inheritance(),
+            Err(InheritanceError {
+                section: "a".to_string(),
+                kind: InheritanceErrorKind::Cycle,
+            })
+        );
+        let conf = Conf::parse_str("[a : b]\n").unwrap();
+        assert_eq!(
+            conf.resolve_inheritance().unwrap_err().to_string(),
+            "section \"a\": parent section \"b\" not found"
+        );
+    }
+}
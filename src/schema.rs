@@ -0,0 +1,1128 @@
+//! Declarative validation of configs.
+//!
+//! Example:
+//!
+//! ```
+//! use nbconf::schema::{KeySchema, Schema, SectionSchema, ValueType};
+//! use nbconf::Conf;
+//!
+//! let schema = Schema::new(vec![SectionSchema::new(
+//!     "server",
+//!     vec![
+//!         KeySchema::new("port", ValueType::Int).required(),
+//!         KeySchema::new("host", ValueType::String).default("::"),
+//!     ],
+//! )]);
+//!
+//! let mut conf = Conf::parse_str("[server]\nport = eighty").unwrap();
+//! let violations = schema.validate(&conf);
+//! assert_eq!(violations[0].to_string(), "[server] port: invalid value \"eighty\", expected an integer");
+//!
+//! schema.apply_defaults(&mut conf);
+//! assert_eq!(conf.sections[0].get("host"), Some("::"));
+//! ```
+
+use crate::redact::Redaction;
+use crate::{
+    check_entry, check_section_name, is_comment, Conf, Entry, ParseError, Section, SerializeError,
+};
+
+/// The type of a value.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ValueType {
+    /// `true` or `false`.
+    Bool,
+    /// A signed 64-bit integer.
+    Int,
+    /// Any string.
+    String,
+    /// One of the listed strings.
+    Enum(Vec<String>),
+    /// A comma-separated list of values of the inner type.
+    List(Box<ValueType>),
+}
+
+impl ValueType {
+    /// Whether `value` is valid for this type.
+    pub fn accepts(&self, value: &str) -> bool {
+        match self {
+            ValueType::Bool => value == "true" || value == "false",
+            ValueType::Int => value.parse::<i64>().is_ok(),
+            ValueType::String => true,
+            ValueType::Enum(variants) => variants.iter().any(|v| v == value),
+            ValueType::List(item) => {
+                value.is_empty() || value.split(',').all(|v| item.accepts(v.trim()))
+            }
+        }
+    }
+
+    /// Parse a type name as used by [`Schema::from_conf`]: `bool`, `int`,
+    /// `string`, `enum(a|b|c)` or `list(<type>)`.
+    ///
+    /// [`Schema::from_conf`]: struct.Schema.html#method.from_conf
+    pub fn from_name(name: &str) -> Option<ValueType> {
+        let name = name.trim();
+        let inner = |prefix: &str| name.strip_prefix(prefix)?.strip_suffix(')');
+        match name {
+            "bool" => Some(ValueType::Bool),
+            "int" => Some(ValueType::Int),
+            "string" => Some(ValueType::String),
+            _ => {
+                if let Some(variants) = inner("enum(") {
+                    let variants = variants.split('|').map(|v| v.trim().to_string()).collect();
+                    Some(ValueType::Enum(variants))
+                } else {
+                    let item = ValueType::from_name(inner("list(")?)?;
+                    Some(ValueType::List(Box::new(item)))
+                }
+            }
+        }
+    }
+
+    /// Plural description, for describing list items.
+    fn plural(&self) -> String {
+        match self {
+            ValueType::Bool => "booleans".to_string(),
+            ValueType::Int => "integers".to_string(),
+            ValueType::String => "strings".to_string(),
+            ValueType::Enum(variants) => format!("values from {}", variants.join(", ")),
+            ValueType::List(_) => "lists".to_string(),
+        }
+    }
+}
+
+impl std::fmt::Display for ValueType {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ValueType::Bool => write!(f, "a boolean"),
+            ValueType::Int => write!(f, "an integer"),
+            ValueType::String => write!(f, "a string"),
+            ValueType::Enum(variants) => write!(f, "one of {}", variants.join(", ")),
+            ValueType::List(item) => write!(f, "a list of {}", item.plural()),
+        }
+    }
+}
+
+/// Rules for a single key.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct KeySchema {
+    /// Name of the key.
+    pub name: String,
+    /// Type of the key's value.
+    pub value_type: ValueType,
+    /// Whether the key must be present.
+    pub required: bool,
+    /// Value used by [`Schema::apply_defaults`] when the key is missing.
+    ///
+    /// [`Schema::apply_defaults`]: struct.Schema.html#method.apply_defaults
+    pub default: Option<String>,
+    /// Deprecated former names of the key.
+    pub aliases: Vec<String>,
+    /// What the key is for, shown in [`Schema::example_conf`].
+    ///
+    /// [`Schema::example_conf`]: struct.Schema.html#method.example_conf
+    pub description: Option<String>,
+    /// Whether the key's value is secret, and should be hidden by the
+    /// [`Redaction`] from [`Schema::redaction`].
+    ///
+    /// [`Redaction`]: ../redact/struct.Redaction.html
+    /// [`Schema::redaction`]: struct.Schema.html#method.redaction
+    pub secret: bool,
+}
+
+impl KeySchema {
+    /// Create an optional key with no default.
+    pub fn new(name: &str, value_type: ValueType) -> KeySchema {
+        KeySchema {
+            name: name.to_string(),
+            value_type,
+            required: false,
+            default: None,
+            aliases: Vec::new(),
+            description: None,
+            secret: false,
+        }
+    }
+
+    /// Mark the key as required.
+    pub fn required(mut self) -> KeySchema {
+        self.required = true;
+        self
+    }
+
+    /// Set the key's default value.
+    pub fn default(mut self, value: &str) -> KeySchema {
+        self.default = Some(value.to_string());
+        self
+    }
+
+    /// Add a deprecated former name of the key.
+    pub fn alias(mut self, alias: &str) -> KeySchema {
+        self.aliases.push(alias.to_string());
+        self
+    }
+
+    /// Set the key's description.
+    pub fn description(mut self, description: &str) -> KeySchema {
+        self.description = Some(description.to_string());
+        self
+    }
+
+    /// Mark the key's value as secret.
+    pub fn secret(mut self) -> KeySchema {
+        self.secret = true;
+        self
+    }
+
+    /// The key's name followed by its aliases.
+    fn names(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.name.as_str()).chain(self.aliases.iter().map(String::as_str))
+    }
+}
+
+/// Rules for a section.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SectionSchema {
+    /// Name of the section.
+    pub name: String,
+    /// Whether the section must be present.
+    pub required: bool,
+    /// Keys allowed in the section.
+    pub keys: Vec<KeySchema>,
+    /// What the section is for, shown in [`Schema::example_conf`].
+    ///
+    /// [`Schema::example_conf`]: struct.Schema.html#method.example_conf
+    pub description: Option<String>,
+}
+
+impl SectionSchema {
+    /// Create an optional section.
+    pub fn new(name: &str, keys: Vec<KeySchema>) -> SectionSchema {
+        SectionSchema {
+            name: name.to_string(),
+            required: false,
+            keys,
+            description: None,
+        }
+    }
+
+    /// Mark the section as required.
+    pub fn required(mut self) -> SectionSchema {
+        self.required = true;
+        self
+    }
+
+    /// Set the section's description.
+    pub fn description(mut self, description: &str) -> SectionSchema {
+        self.description = Some(description.to_string());
+        self
+    }
+
+    /// Get the rules for `key`.
+    pub fn key(&self, key: &str) -> Option<&KeySchema> {
+        self.keys.iter().find(|k| k.name == key)
+    }
+
+    /// Get the rules for the key that has `alias` as a former name.
+    pub fn key_for_alias(&self, alias: &str) -> Option<&KeySchema> {
+        self.keys
+            .iter()
+            .find(|k| k.aliases.iter().any(|a| a == alias))
+    }
+}
+
+/// The specific type of schema violation.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ViolationKind {
+    /// A required section is missing.
+    MissingSection,
+    /// A required key is missing.
+    MissingKey,
+    /// A value does not have the expected type.
+    InvalidValue { value: String, expected: ValueType },
+    /// A section is not in the schema. The suggestion is the most
+    /// similar section name in the schema, if any is close.
+    UnknownSection { suggestion: Option<String> },
+    /// A key is not in the schema for its section. The suggestion is the
+    /// most similar key name in the section's schema, if any is close.
+    UnknownKey { suggestion: Option<String> },
+    /// A key uses a deprecated name.
+    DeprecatedKey { replacement: String },
+}
+
+/// How serious a violation is.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+/// A problem found by [`Schema::validate`].
+///
+/// [`Schema::validate`]: struct.Schema.html#method.validate
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Violation {
+    /// Section where the problem occurs.
+    pub section: String,
+    /// Key where the problem occurs, if it is not the whole section.
+    pub key: Option<String>,
+    /// Type of problem.
+    pub kind: ViolationKind,
+    /// How serious the problem is.
+    pub severity: Severity,
+    /// Line of the entry or section header where the problem occurs,
+    /// starting from 1. Only set by [`Schema::validate_str`], and not for
+    /// missing sections.
+    ///
+    /// [`Schema::validate_str`]: struct.Schema.html#method.validate_str
+    pub line: Option<usize>,
+}
+
+impl Violation {
+    fn error(section: &str, key: Option<&str>, kind: ViolationKind) -> Violation {
+        Violation {
+            section: section.to_string(),
+            key: key.map(str::to_string),
+            kind,
+            severity: Severity::Error,
+            line: None,
+        }
+    }
+
+    fn at(self, line: Option<usize>) -> Violation {
+        Violation { line, ..self }
+    }
+}
+
+/// Line numbers of the headers and entries of a config, from the text it
+/// was parsed from.
+struct SourceLines {
+    /// The header line and entry lines of each section, in order.
+    sections: Vec<(usize, Vec<usize>)>,
+}
+
+impl SourceLines {
+    /// Find the lines the way the parser reads `source`, which must have
+    /// parsed successfully with the default options.
+    fn new(source: &str) -> SourceLines {
+        let source = source.strip_prefix('\u{feff}').unwrap_or(source);
+        let mut sections: Vec<(usize, Vec<usize>)> = Vec::new();
+        for (index, line) in source.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || is_comment(line) {
+                continue;
+            }
+            if line.starts_with('[') {
+                sections.push((index + 1, Vec::new()));
+            } else if let Some((_, entries)) = sections.last_mut() {
+                entries.push(index + 1);
+            }
+        }
+        SourceLines { sections }
+    }
+}
+
+fn write_suggestion(f: &mut std::fmt::Formatter, suggestion: &Option<String>) -> std::fmt::Result {
+    match suggestion {
+        Some(suggestion) => write!(f, ", did you mean {:?}?", suggestion),
+        None => Ok(()),
+    }
+}
+
+/// Write each line of `text` as a comment.
+fn write_comments(output: &mut String, text: &Option<String>) {
+    for line in text.iter().flat_map(|text| text.lines()) {
+        if line.is_empty() {
+            *output += "#\n";
+        } else {
+            *output += &format!("# {}\n", line);
+        }
+    }
+}
+
+/// Edit distance between `a` and `b`, counted in chars, where an
+/// insertion, deletion, substitution, or swap of adjacent chars each
+/// cost one.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    // d[i][j] is the distance between a[..i] and b[..j].
+    let mut d = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in d.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in d[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            d[i][j] = (d[i - 1][j] + 1)
+                .min(d[i][j - 1] + 1)
+                .min(d[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+    d[a.len()][b.len()]
+}
+
+/// The candidate most similar to `name`, if it is close enough to be a
+/// likely typo.
+fn suggest<'a, I: Iterator<Item = &'a str>>(name: &str, candidates: I) -> Option<String> {
+    let max_distance = std::cmp::max(1, name.chars().count() / 3);
+    candidates
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate.to_string())
+}
+
+impl std::fmt::Display for Violation {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "[{}]", self.section)?;
+        if let Some(key) = &self.key {
+            write!(f, " {}", key)?;
+        }
+        match &self.kind {
+            ViolationKind::MissingSection => write!(f, ": missing required section"),
+            ViolationKind::MissingKey => write!(f, ": missing required key"),
+            ViolationKind::InvalidValue { value, expected } => {
+                write!(f, ": invalid value {:?}, expected {}", value, expected)
+            }
+            ViolationKind::UnknownSection { suggestion } => {
+                write!(f, ": unknown section")?;
+                write_suggestion(f, suggestion)
+            }
+            ViolationKind::UnknownKey { suggestion } => {
+                write!(f, ": unknown key")?;
+                write_suggestion(f, suggestion)
+            }
+            ViolationKind::DeprecatedKey { replacement } => {
+                write!(f, ": deprecated key, use {:?} instead", replacement)
+            }
+        }
+    }
+}
+
+/// The specific type of [`SchemaError`].
+///
+/// [`SchemaError`]: struct.SchemaError.html
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum SchemaErrorKind {
+    /// The type is not one of the names accepted by
+    /// [`ValueType::from_name`].
+    ///
+    /// [`ValueType::from_name`]: enum.ValueType.html#method.from_name
+    UnknownType { name: String },
+    /// The rule after the type is not `required`, `secret`,
+    /// `alias <name>` or `default <value>`.
+    UnknownRule { rule: String },
+}
+
+/// Error produced from [`Schema::from_conf`] for a key whose rules
+/// cannot be read.
+///
+/// [`Schema::from_conf`]: struct.Schema.html#method.from_conf
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SchemaError {
+    /// Section containing the key.
+    pub section: String,
+    /// The key.
+    pub key: String,
+    /// Type of error.
+    pub kind: SchemaErrorKind,
+}
+
+impl std::fmt::Display for SchemaError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "[{}] {}: ", self.section, self.key)?;
+        match &self.kind {
+            SchemaErrorKind::UnknownType { name } => write!(f, "unknown type {:?}", name),
+            SchemaErrorKind::UnknownRule { rule } => write!(f, "unknown rule {:?}", rule),
+        }
+    }
+}
+
+impl std::error::Error for SchemaError {}
+
+/// Split the first comma-separated item off `rules`.
+fn split_rule(rules: &str) -> (&str, Option<&str>) {
+    match rules.find(',') {
+        Some(comma) => (rules[..comma].trim(), Some(&rules[comma + 1..])),
+        None => (rules.trim(), None),
+    }
+}
+
+/// Read the rules for `key` from a value such as `int, required`.
+fn key_from_rules(key: &str, rules: &str) -> Result<KeySchema, SchemaErrorKind> {
+    let (name, mut rest) = split_rule(rules);
+    let value_type = ValueType::from_name(name).ok_or_else(|| SchemaErrorKind::UnknownType {
+        name: name.to_string(),
+    })?;
+    let mut key_schema = KeySchema::new(key, value_type);
+    while let Some(rules) = rest {
+        // The default is the rest of the value, so it may contain commas.
+        if let Some(default) = rules.trim_start().strip_prefix("default ") {
+            key_schema = key_schema.default(default.trim());
+            break;
+        }
+        let (rule, next) = split_rule(rules);
+        rest = next;
+        key_schema = match rule {
+            "required" => key_schema.required(),
+            "secret" => key_schema.secret(),
+            _ => match rule.strip_prefix("alias ") {
+                Some(alias) => key_schema.alias(alias.trim()),
+                None => {
+                    return Err(SchemaErrorKind::UnknownRule {
+                        rule: rule.to_string(),
+                    })
+                }
+            },
+        };
+    }
+    Ok(key_schema)
+}
+
+/// Rules that a config must follow.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Schema {
+    /// Sections allowed in the config.
+    pub sections: Vec<SectionSchema>,
+    /// Severity of sections and keys that are not in the schema, or
+    /// `None` to not report them.
+    pub unknown: Option<Severity>,
+    /// Sections and keys starting with any of these prefixes are never
+    /// reported as unknown.
+    pub allowed_prefixes: Vec<String>,
+}
+
+impl Default for Schema {
+    fn default() -> Schema {
+        Schema::new(Vec::new())
+    }
+}
+
+impl Schema {
+    /// Create a schema from its sections. Unknown sections and keys are
+    /// reported as warnings.
+    pub fn new(sections: Vec<SectionSchema>) -> Schema {
+        Schema {
+            sections,
+            unknown: Some(Severity::Warning),
+            allowed_prefixes: Vec::new(),
+        }
+    }
+
+    /// Set the severity of unknown sections and keys.
+    pub fn unknown(mut self, severity: Option<Severity>) -> Schema {
+        self.unknown = severity;
+        self
+    }
+
+    /// Read a schema from a config that lists the keys of each section,
+    /// with each value giving the key's type followed by any of
+    /// `required`, `secret` and `alias <name>`, separated by commas. A
+    /// final `default <value>` sets the default, which may itself
+    /// contain commas. Sections are optional.
+    ///
+    /// ```
+    /// use nbconf::schema::Schema;
+    /// use nbconf::Conf;
+    ///
+    /// let rules = Conf::parse_str("[server]\nport = int, required\nhost = string, default ::\n");
+    /// let schema = Schema::from_conf(&rules.unwrap()).unwrap();
+    /// let conf = Conf::parse_str("[server]\nport = 80\n").unwrap();
+    /// assert!(schema.validate(&conf).is_empty());
+    /// ```
+    pub fn from_conf(conf: &Conf) -> Result<Schema, SchemaError> {
+        let mut sections = Vec::new();
+        for section in conf.iter() {
+            let mut keys = Vec::new();
+            for entry in section.iter() {
+                let key_schema =
+                    key_from_rules(&entry.key, &entry.value).map_err(|kind| SchemaError {
+                        section: section.name.clone(),
+                        key: entry.key.clone(),
+                        kind,
+                    })?;
+                keys.push(key_schema);
+            }
+            sections.push(SectionSchema::new(&section.name, keys));
+        }
+        Ok(Schema::new(sections))
+    }
+
+    /// Never report sections or keys starting with `prefix` as unknown.
+    pub fn allow_prefix(mut self, prefix: &str) -> Schema {
+        self.allowed_prefixes.push(prefix.to_string());
+        self
+    }
+
+    /// The violation for an unknown section or key, if it should be
+    /// reported.
+    fn unknown_violation(&self, section: &str, key: Option<&str>) -> Option<Violation> {
+        let name = key.unwrap_or(section);
+        if self
+            .allowed_prefixes
+            .iter()
+            .any(|p| name.starts_with(p.as_str()))
+        {
+            return None;
+        }
+        let kind = match key {
+            Some(key) => ViolationKind::UnknownKey {
+                suggestion: self
+                    .section(section)
+                    .and_then(|s| suggest(key, s.keys.iter().map(|k| k.name.as_str()))),
+            },
+            None => ViolationKind::UnknownSection {
+                suggestion: suggest(section, self.sections.iter().map(|s| s.name.as_str())),
+            },
+        };
+        self.unknown.map(|severity| Violation {
+            severity,
+            ..Violation::error(section, key, kind)
+        })
+    }
+
+    /// Get the rules for `section`.
+    pub fn section(&self, section: &str) -> Option<&SectionSchema> {
+        self.sections.iter().find(|s| s.name == section)
+    }
+
+    /// Get the value of `key` in the sections named `section`, falling
+    /// back to the key's deprecated aliases if it is not set.
+    pub fn get<'a>(&self, conf: &'a Conf, section: &str, key: &str) -> Option<&'a str> {
+        let key_schema = self.section(section).and_then(|s| s.key(key));
+        let names: Vec<&str> = match key_schema {
+            Some(key_schema) => key_schema.names().collect(),
+            None => vec![key],
+        };
+        let sections: Vec<&Section> = conf.sections.iter().filter(|s| s.name == section).collect();
+        names
+            .iter()
+            .find_map(|name| sections.iter().find_map(|s| s.get(name)))
+    }
+
+    /// Check `conf` against the schema.
+    ///
+    /// Sections that share a name are checked together, so a required
+    /// key only needs to appear in one of them.
+    pub fn validate(&self, conf: &Conf) -> Vec<Violation> {
+        self.validate_lines(conf, None)
+    }
+
+    /// Parse `source` and check it against the schema, as [`validate`]
+    /// does, with the [`line`] of each violation set. A missing key is
+    /// reported at the first header of its section.
+    ///
+    /// ```
+    /// use nbconf::schema::{KeySchema, Schema, SectionSchema, ValueType};
+    ///
+    /// let schema = Schema::new(vec![SectionSchema::new(
+    ///     "server",
+    ///     vec![KeySchema::new("max_connections", ValueType::Int).alias("max_conns")],
+    /// )]);
+    /// let violations = schema.validate_str("[server]\n# Raised for launch.\nmax_conns = 9\n")?;
+    /// assert_eq!(violations[0].line, Some(3));
+    /// # Ok::<(), nbconf::ParseError>(())
+    /// ```
+    ///
+    /// [`validate`]: #method.validate
+    /// [`line`]: struct.Violation.html#structfield.line
+    pub fn validate_str(&self, source: &str) -> Result<Vec<Violation>, ParseError> {
+        let conf = Conf::parse_str(source)?;
+        Ok(self.validate_lines(&conf, Some(&SourceLines::new(source))))
+    }
+
+    fn validate_lines(&self, conf: &Conf, lines: Option<&SourceLines>) -> Vec<Violation> {
+        let header_line = |index: usize| lines.map(|l| l.sections[index].0);
+        let entry_line = |index: usize, entry: usize| lines.map(|l| l.sections[index].1[entry]);
+        let mut violations = Vec::new();
+        for section_schema in self.sections.iter() {
+            let sections: Vec<(usize, &Section)> = conf
+                .sections
+                .iter()
+                .enumerate()
+                .filter(|(_, s)| s.name == section_schema.name)
+                .collect();
+            if sections.is_empty() {
+                if section_schema.required {
+                    violations.push(Violation::error(
+                        &section_schema.name,
+                        None,
+                        ViolationKind::MissingSection,
+                    ));
+                }
+                continue;
+            }
+
+            for (index, section) in sections.iter() {
+                for (entry_index, entry) in section.entries.iter().enumerate() {
+                    let line = entry_line(*index, entry_index);
+                    let mut key_schema = section_schema.key(&entry.key);
+                    if key_schema.is_none() {
+                        key_schema = section_schema.key_for_alias(&entry.key);
+                        if let Some(key_schema) = key_schema {
+                            let violation = Violation::error(
+                                &section_schema.name,
+                                Some(&entry.key),
+                                ViolationKind::DeprecatedKey {
+                                    replacement: key_schema.name.clone(),
+                                },
+                            );
+                            violations.push(Violation {
+                                severity: Severity::Warning,
+                                ..violation.at(line)
+                            });
+                        }
+                    }
+                    match key_schema {
+                        Some(key_schema) => {
+                            if !key_schema.value_type.accepts(&entry.value) {
+                                let violation = Violation::error(
+                                    &section_schema.name,
+                                    Some(&entry.key),
+                                    ViolationKind::InvalidValue {
+                                        value: entry.value.clone(),
+                                        expected: key_schema.value_type.clone(),
+                                    },
+                                );
+                                violations.push(violation.at(line));
+                            }
+                        }
+                        None => violations.extend(
+                            self.unknown_violation(&section_schema.name, Some(&entry.key))
+                                .map(|v| v.at(line)),
+                        ),
+                    }
+                }
+            }
+            for key_schema in section_schema.keys.iter().filter(|k| k.required) {
+                if key_schema
+                    .names()
+                    .all(|name| sections.iter().all(|(_, s)| s.get(name).is_none()))
+                {
+                    let violation = Violation::error(
+                        &section_schema.name,
+                        Some(&key_schema.name),
+                        ViolationKind::MissingKey,
+                    );
+                    violations.push(violation.at(header_line(sections[0].0)));
+                }
+            }
+        }
+        for (index, section) in conf.sections.iter().enumerate() {
+            let is_repeat = conf.sections[..index]
+                .iter()
+                .any(|s| s.name == section.name);
+            if !is_repeat && self.section(&section.name).is_none() {
+                violations.extend(
+                    self.unknown_violation(&section.name, None)
+                        .map(|v| v.at(header_line(index))),
+                );
+            }
+        }
+        violations
+    }
+
+    /// Write an example config with every section and key in the schema,
+    /// each preceded by comments giving its description and rules.
+    ///
+    /// Keys with a default are set to it. Keys without one are written
+    /// commented out, to be filled in. Fails if a section name, key or
+    /// default would not read back as given.
+    pub fn example_conf(&self) -> Result<String, SerializeError> {
+        let mut output = String::new();
+        for (index, section_schema) in self.sections.iter().enumerate() {
+            if index != 0 {
+                output.push('\n');
+            }
+            write_comments(&mut output, &section_schema.description);
+            if section_schema.required {
+                output += "# Required.\n";
+            }
+            check_section_name(&section_schema.name)?;
+            output += &format!("[{}]\n", section_schema.name);
+
+            for key_schema in section_schema.keys.iter() {
+                let default = key_schema.default.as_deref().unwrap_or("");
+                check_entry(&section_schema.name, &key_schema.name, default)?;
+                write_comments(&mut output, &key_schema.description);
+                output += &format!("# Type: {}.", key_schema.value_type);
+                if key_schema.required {
+                    output += " Required.";
+                }
+                match &key_schema.default {
+                    Some(default) => {
+                        output += &format!(" Default: {}.\n", default);
+                        output += &format!("{} = {}\n", key_schema.name, default);
+                    }
+                    None => output += &format!("\n# {} =\n", key_schema.name),
+                }
+            }
+        }
+        Ok(output)
+    }
+
+    /// A [`Redaction`] that hides the values of the keys marked secret,
+    /// under their names and aliases.
+    ///
+    /// [`Redaction`]: ../redact/struct.Redaction.html
+    pub fn redaction(&self) -> Redaction {
+        let mut redaction = Redaction::new();
+        for key_schema in self.sections.iter().flat_map(|s| s.keys.iter()) {
+            if key_schema.secret {
+                redaction
+                    .patterns
+                    .extend(key_schema.names().map(str::to_string));
+            }
+        }
+        redaction
+    }
+
+    /// Add the default value of each missing key that has one. Missing
+    /// sections are created if any of their keys have defaults.
+    pub fn apply_defaults(&self, conf: &mut Conf) {
+        for section_schema in self.sections.iter() {
+            for key_schema in section_schema.keys.iter() {
+                let default = match &key_schema.default {
+                    Some(default) => default,
+                    None => continue,
+                };
+                if self
+                    .get(conf, &section_schema.name, &key_schema.name)
+                    .is_some()
+                {
+                    continue;
+                }
+                let index = match conf
+                    .sections
+                    .iter()
+                    .position(|s| s.name == section_schema.name)
+                {
+                    Some(index) => index,
+                    None => {
+                        conf.sections.push(Section::new(&section_schema.name));
+                        conf.sections.len() - 1
+                    }
+                };
+                conf.sections[index]
+                    .entries
+                    .push(Entry::new(&key_schema.name, default));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SerializeErrorKind;
+
+    fn schema() -> Schema {
+        Schema::new(vec![
+            SectionSchema::new(
+                "server",
+                vec![
+                    KeySchema::new("port", ValueType::Int).required(),
+                    KeySchema::new("tls", ValueType::Bool).default("false"),
+                    KeySchema::new(
+                        "mode",
+                        ValueType::Enum(vec!["fast".to_string(), "safe".to_string()]),
+                    ),
+                    KeySchema::new("peers", ValueType::List(Box::new(ValueType::Int))),
+                ],
+            )
+            .required(),
+            SectionSchema::new(
+                "log",
+                vec![KeySchema::new("level", ValueType::String).required()],
+            ),
+        ])
+    }
+
+    #[test]
+    fn test_validate() {
+        let conf =
+            Conf::parse_str("[server]\ntls = yes\nmode = slow\npeers = 1, 2\n[server]\nport = 1")
+                .unwrap();
+        let violations: Vec<String> = schema()
+            .validate(&conf)
+            .iter()
+            .map(|v| v.to_string())
+            .collect();
+        assert_eq!(
+            violations,
+            vec![
+                "[server] tls: invalid value \"yes\", expected a boolean",
+                "[server] mode: invalid value \"slow\", expected one of fast, safe",
+            ]
+        );
+
+        let conf = Conf::parse_str("[log]\nlevel = info\n[log]").unwrap();
+        assert_eq!(
+            schema().validate(&conf),
+            vec![Violation::error(
+                "server",
+                None,
+                ViolationKind::MissingSection
+            )]
+        );
+
+        let conf = Conf::parse_str("[server]\npeers = 1, x\n[log]").unwrap();
+        let violations: Vec<String> = schema()
+            .validate(&conf)
+            .iter()
+            .map(|v| v.to_string())
+            .collect();
+        assert_eq!(
+            violations,
+            vec![
+                "[server] peers: invalid value \"1, x\", expected a list of integers",
+                "[server] port: missing required key",
+                "[log] level: missing required key",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unknown() {
+        let conf = Conf::parse_str(
+            "[server]\nport = 1\nprot = 2\nx-note = 3\n[log]\nlevel = a\n[x-extra]\n[db]",
+        )
+        .unwrap();
+        assert_eq!(
+            schema().allow_prefix("x-").validate(&conf),
+            vec![
+                Violation {
+                    severity: Severity::Warning,
+                    ..Violation::error(
+                        "server",
+                        Some("prot"),
+                        ViolationKind::UnknownKey {
+                            suggestion: Some("port".to_string())
+                        }
+                    )
+                },
+                Violation {
+                    severity: Severity::Warning,
+                    ..Violation::error(
+                        "db",
+                        None,
+                        ViolationKind::UnknownSection { suggestion: None }
+                    )
+                },
+            ]
+        );
+        assert_eq!(
+            schema().unknown(None).allow_prefix("x-").validate(&conf),
+            vec![]
+        );
+        assert_eq!(
+            schema()
+                .unknown(Some(Severity::Error))
+                .validate(&conf)
+                .len(),
+            4
+        );
+    }
+
+    #[test]
+    fn test_aliases() {
+        let schema = Schema::new(vec![SectionSchema::new(
+            "server",
+            vec![KeySchema::new("max_connections", ValueType::Int)
+                .required()
+                .alias("max_conns")],
+        )]);
+        let conf = Conf::parse_str("[server]\nmax_conns = x").unwrap();
+        let violations: Vec<String> = schema
+            .validate(&conf)
+            .iter()
+            .map(|v| v.to_string())
+            .collect();
+        assert_eq!(
+            violations,
+            vec![
+                "[server] max_conns: deprecated key, use \"max_connections\" instead",
+                "[server] max_conns: invalid value \"x\", expected an integer",
+            ]
+        );
+        assert_eq!(schema.get(&conf, "server", "max_connections"), Some("x"));
+
+        let conf = Conf::parse_str("[server]\nmax_conns = 1\nmax_connections = 2").unwrap();
+        assert_eq!(schema.get(&conf, "server", "max_connections"), Some("2"));
+        assert_eq!(schema.get(&conf, "server", "other"), None);
+    }
+
+    #[test]
+    fn test_violation_lines() {
+        let source =
+            "\u{feff}# about\n[server]\n\ntls = yes\n[extra]\n[server]\n  ; note\nprot = 1\n";
+        let lines: Vec<(String, Option<usize>)> = schema()
+            .validate_str(source)
+            .unwrap()
+            .into_iter()
+            .map(|v| (v.key.unwrap_or(v.section), v.line))
+            .collect();
+        assert_eq!(
+            lines,
+            vec![
+                ("tls".to_string(), Some(4)),
+                ("prot".to_string(), Some(8)),
+                ("port".to_string(), Some(2)),
+                ("extra".to_string(), Some(5)),
+            ]
+        );
+        assert!(schema().validate_str("[server").is_err());
+        let conf = Conf::parse_str(source).unwrap();
+        assert!(schema().validate(&conf).iter().all(|v| v.line.is_none()));
+    }
+
+    #[test]
+    fn test_suggestions() {
+        assert_eq!(edit_distance("timout", "timeout"), 1);
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("prot", "port"), 1);
+
+        let conf = Conf::parse_str("[sever]\n[server]\nprt = 1\nlvl = 2").unwrap();
+        let violations: Vec<String> = schema()
+            .validate(&conf)
+            .iter()
+            .map(|v| v.to_string())
+            .collect();
+        assert_eq!(
+            violations,
+            vec![
+                "[server] prt: unknown key, did you mean \"port\"?",
+                "[server] lvl: unknown key",
+                "[server] port: missing required key",
+                "[sever]: unknown section, did you mean \"server\"?",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_example_conf() {
+        let schema = Schema::new(vec![
+            SectionSchema::new(
+                "server",
+                vec![
+                    KeySchema::new("port", ValueType::Int)
+                        .required()
+                        .description("Port to listen on."),
+                    KeySchema::new("tls", ValueType::Bool)
+                        .default("false")
+                        .description("Whether to use TLS.\n\nRequires a certificate."),
+                ],
+            )
+            .required()
+            .description("Server settings."),
+            SectionSchema::new("log", vec![KeySchema::new("level", ValueType::String)]),
+        ]);
+        let example = schema.example_conf().unwrap();
+        assert_eq!(
+            example,
+            "# Server settings.
+# Required.
+[server]
+# Port to listen on.
+# Type: an integer. Required.
+# port =
+# Whether to use TLS.
+#
+# Requires a certificate.
+# Type: a boolean. Default: false.
+tls = false
+
+[log]
+# Type: a string.
+# level =
+"
+        );
+
+        let conf = Conf::parse_str(&example).unwrap();
+        assert_eq!(conf.to_string(), "[server]\ntls = false\n\n[log]\n");
+
+        let padded = Schema::new(vec![SectionSchema::new(
+            "a",
+            vec![KeySchema::new("x", ValueType::String).default(" padded")],
+        )]);
+        assert_eq!(
+            padded.example_conf().unwrap_err().kind,
+            SerializeErrorKind::InvalidValue { key: "x".into() }
+        );
+        let comment = Schema::new(vec![SectionSchema::new("b] # c", vec![])]);
+        assert_eq!(
+            comment.example_conf().unwrap_err().kind,
+            SerializeErrorKind::InvalidSectionName
+        );
+        let key = Schema::new(vec![SectionSchema::new(
+            "a",
+            vec![KeySchema::new("x = y", ValueType::String)],
+        )]);
+        assert_eq!(
+            key.example_conf().unwrap_err().kind,
+            SerializeErrorKind::InvalidKey {
+                key: "x = y".into()
+            }
+        );
+    }
+
+    #[test]
+    fn test_apply_defaults() {
+        let mut conf = Conf::new();
+        schema().apply_defaults(&mut conf);
+        assert_eq!(conf.to_string(), "[server]\ntls = false\n");
+    }
+
+    #[test]
+    fn test_from_conf() {
+        let rules = Conf::parse_str(
+            "[server]\nport = int, required\ntls = bool, default false\n\
+             mode = enum(fast | safe)\npeers = list(int)\n\
+             [log]\nlevel = string, alias verbosity, secret, default a, b\n",
+        )
+        .unwrap();
+        let loaded = Schema::from_conf(&rules).unwrap();
+        assert_eq!(loaded.sections[0].keys, schema().sections[0].keys);
+        assert_eq!(
+            loaded.sections[1].keys,
+            vec![KeySchema::new("level", ValueType::String)
+                .alias("verbosity")
+                .secret()
+                .default("a, b")]
+        );
+
+        let error = |rules: &str| {
+            let conf = Conf::parse_str(&format!("[s]\nk = {}\n", rules)).unwrap();
+            Schema::from_conf(&conf).unwrap_err().to_string()
+        };
+        assert_eq!(error("integer"), "[s] k: unknown type \"integer\"");
+        assert_eq!(error("list(int"), "[s] k: unknown type \"list(int\"");
+        assert_eq!(error("int, optional"), "[s] k: unknown rule \"optional\"");
+    }
+
+    #[test]
+    fn test_redaction() {
+        let schema = Schema::new(vec![SectionSchema::new(
+            "db",
+            vec![
+                KeySchema::new("user", ValueType::String),
+                KeySchema::new("password", ValueType::String)
+                    .alias("pass")
+                    .secret(),
+            ],
+        )]);
+        let conf = Conf::parse_str("[db]\nuser = app\npass = hunter2\n").unwrap();
+        assert_eq!(
+            conf.to_string_redacted(&schema.redaction()),
+            "[db]\nuser = app\npass = ***\n"
+        );
+    }
+}
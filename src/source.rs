@@ -0,0 +1,232 @@
+//! Values that refer to somewhere else, such as `@file:/run/secrets/db`
+//! or `@env:HOME`.
+//!
+//! References are resolved when read through a [`ResolvedConf`], so
+//! secrets can stay out of the config file itself.
+//!
+//! Example:
+//!
+//! ```
+//! use nbconf::Conf;
+//!
+//! std::env::set_var("DB_HOST", "db.internal");
+//! let conf = Conf::parse_str("[db]\nhost = @env:DB_HOST\nport = 5432\n").unwrap();
+//! let resolved = conf.resolved();
+//! assert_eq!(resolved.get("db", "host"), Ok(Some("db.internal".to_string())));
+//! assert_eq!(resolved.get("db", "port"), Ok(Some("5432".to_string())));
+//! ```
+//!
+//! [`ResolvedConf`]: struct.ResolvedConf.html
+
+use crate::Conf;
+
+/// Looks up the value for a reference such as `@scheme:reference`.
+pub trait ValueResolver {
+    /// Get the value for `reference`, the text after `@scheme:`, or a
+    /// message describing why it is not available.
+    fn resolve(&self, reference: &str) -> Result<String, String>;
+}
+
+/// Resolves `@file:path` to the contents of the file at `path`, without
+/// a trailing line terminator.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct FileResolver;
+
+impl ValueResolver for FileResolver {
+    fn resolve(&self, reference: &str) -> Result<String, String> {
+        let contents = std::fs::read_to_string(reference).map_err(|err| err.to_string())?;
+        let contents = contents.strip_suffix('\n').unwrap_or(&contents);
+        Ok(contents.strip_suffix('\r').unwrap_or(contents).to_string())
+    }
+}
+
+/// Resolves `@env:NAME` to the value of the environment variable `NAME`.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct EnvResolver;
+
+impl ValueResolver for EnvResolver {
+    fn resolve(&self, reference: &str) -> Result<String, String> {
+        std::env::var(reference).map_err(|err| err.to_string())
+    }
+}
+
+/// Error produced when a [`ValueResolver`] fails.
+///
+/// [`ValueResolver`]: trait.ValueResolver.html
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ResolveError {
+    /// Name of the section containing the value.
+    pub section: String,
+    /// Key of the value.
+    pub key: String,
+    /// The value, such as `@file:/run/secrets/db`.
+    pub value: String,
+    /// Why the value could not be resolved.
+    pub message: String,
+}
+
+impl std::fmt::Display for ResolveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{}.{}: cannot resolve {:?}: {}",
+            self.section, self.key, self.value, self.message
+        )
+    }
+}
+
+impl std::error::Error for ResolveError {}
+
+/// A view of a config that resolves `@scheme:reference` values through
+/// registered [`ValueResolver`]s as they are read.
+///
+/// Values starting with `@` whose scheme has no resolver are returned as
+/// they are.
+///
+/// [`ValueResolver`]: trait.ValueResolver.html
+pub struct ResolvedConf<'a> {
+    conf: &'a Conf,
+    resolvers: Vec<(String, Box<dyn ValueResolver + 'a>)>,
+}
+
+impl std::fmt::Debug for ResolvedConf<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let schemes: Vec<&str> = self.resolvers.iter().map(|(s, _)| s.as_str()).collect();
+        f.debug_struct("ResolvedConf")
+            .field("conf", &self.conf)
+            .field("resolvers", &schemes)
+            .finish()
+    }
+}
+
+impl<'a> ResolvedConf<'a> {
+    /// Create a view of `conf` that resolves `@file:` and `@env:`.
+    pub fn new(conf: &'a Conf) -> ResolvedConf<'a> {
+        ResolvedConf::without_resolvers(conf)
+            .resolver("file", FileResolver)
+            .resolver("env", EnvResolver)
+    }
+
+    /// Create a view of `conf` with no resolvers.
+    pub fn without_resolvers(conf: &'a Conf) -> ResolvedConf<'a> {
+        ResolvedConf {
+            conf,
+            resolvers: Vec::new(),
+        }
+    }
+
+    /// Resolve `@scheme:` values with `resolver`, replacing any earlier
+    /// resolver for `scheme`.
+    pub fn resolver<R: ValueResolver + 'a>(
+        mut self,
+        scheme: &str,
+        resolver: R,
+    ) -> ResolvedConf<'a> {
+        self.resolvers.retain(|(s, _)| s != scheme);
+        self.resolvers
+            .push((scheme.to_string(), Box::new(resolver)));
+        self
+    }
+
+    /// The config being viewed.
+    pub fn conf(&self) -> &'a Conf {
+        self.conf
+    }
+
+    fn resolve(&self, section: &str, key: &str, value: &str) -> Result<String, ResolveError> {
+        let reference = value
+            .strip_prefix('@')
+            .and_then(|rest| rest.split_once(':'))
+            .and_then(|(scheme, reference)| {
+                let (_, resolver) = self.resolvers.iter().find(|(s, _)| s == scheme)?;
+                Some((resolver, reference))
+            });
+        match reference {
+            Some((resolver, reference)) => {
+                resolver.resolve(reference).map_err(|message| ResolveError {
+                    section: section.to_string(),
+                    key: key.to_string(),
+                    value: value.to_string(),
+                    message,
+                })
+            }
+            None => Ok(value.to_string()),
+        }
+    }
+
+    /// Get the value of `key` in the first section named `section` that
+    /// has it, resolving it if it is a reference.
+    pub fn get(&self, section: &str, key: &str) -> Result<Option<String>, ResolveError> {
+        let value = self
+            .conf
+            .iter()
+            .filter(|s| s.name == section)
+            .find_map(|s| s.get(key));
+        value.map(|v| self.resolve(section, key, v)).transpose()
+    }
+
+    /// Get a value by its dotted path, as [`Conf::get_path`] does,
+    /// resolving it if it is a reference.
+    ///
+    /// [`Conf::get_path`]: ../struct.Conf.html#method.get_path
+    pub fn get_path(&self, path: &str) -> Result<Option<String>, ResolveError> {
+        match (self.conf.resolve_path(path), self.conf.get_path(path)) {
+            (Some((section, key)), Some(value)) => self.resolve(section, key, value).map(Some),
+            _ => Ok(None),
+        }
+    }
+}
+
+impl Conf {
+    /// A view of this config that resolves `@file:` and `@env:` values
+    /// as they are read. See [`ResolvedConf`] to add other schemes.
+    ///
+    /// [`ResolvedConf`]: source/struct.ResolvedConf.html
+    pub fn resolved(&self) -> ResolvedConf<'_> {
+        ResolvedConf::new(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Upper;
+
+    impl ValueResolver for Upper {
+        fn resolve(&self, reference: &str) -> Result<String, String> {
+            if reference.is_empty() {
+                return Err("empty reference".to_string());
+            }
+            Ok(reference.to_uppercase())
+        }
+    }
+
+    #[test]
+    fn test_resolved_conf() {
+        let path = std::env::temp_dir().join(format!("nbconf-secret-{}", std::process::id()));
+        std::fs::write(&path, "hunter2\n").unwrap();
+        let conf = Conf::parse_str(&format!(
+            "[a.b]\npass = @file:{}\nname = @upper:abc\nbad = @upper:\nmail = @example:x\n",
+            path.display()
+        ))
+        .unwrap();
+        let resolved = ResolvedConf::new(&conf).resolver("upper", Upper);
+        assert_eq!(
+            resolved.get_path("a.b.pass"),
+            Ok(Some("hunter2".to_string()))
+        );
+        assert_eq!(resolved.get("a.b", "name"), Ok(Some("ABC".to_string())));
+        assert_eq!(
+            resolved.get("a.b", "mail"),
+            Ok(Some("@example:x".to_string()))
+        );
+        assert_eq!(resolved.get("a.b", "missing"), Ok(None));
+        assert_eq!(
+            resolved.get("a.b", "bad").unwrap_err().to_string(),
+            "a.b.bad: cannot resolve \"@upper:\": empty reference"
+        );
+        std::fs::remove_file(&path).unwrap();
+        assert!(resolved.get("a.b", "pass").is_err());
+    }
+}
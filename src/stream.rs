@@ -0,0 +1,224 @@
+//! Writing a config one section at a time, without building a [`Conf`].
+//!
+//! Example:
+//!
+//! ```
+//! use nbconf::stream::ConfWriter;
+//! use nbconf::WriteOptions;
+//!
+//! let mut writer = ConfWriter::new(Vec::new(), WriteOptions::default());
+//! writer.start_section("numbers")?;
+//! for i in 0..3 {
+//!     writer.write_entry(&format!("n{}", i), &i.to_string())?;
+//! }
+//! let output = writer.finish()?;
+//! assert_eq!(output, b"[numbers]\nn0 = 0\nn1 = 1\nn2 = 2\n");
+//! # Ok::<(), std::io::Error>(())
+//! ```
+//!
+//! [`Conf`]: ../struct.Conf.html
+
+use crate::{check_entry, check_section_name, Section, WriteOptions};
+use std::io;
+
+/// Writes sections and entries to `W` as they are given, flushing it
+/// after each section.
+///
+/// The output is the same as [`Conf::write_to`] with the same options,
+/// except that [`WriteOptions::sort`] and [`WriteOptions::align_equals`]
+/// only apply to the entries of a [`write_section`] call, and sections
+/// are written in the order given. Call [`finish`] after the last
+/// entry.
+///
+/// [`Conf::write_to`]: ../struct.Conf.html#method.write_to
+/// [`WriteOptions::sort`]: ../struct.WriteOptions.html#structfield.sort
+/// [`WriteOptions::align_equals`]: ../struct.WriteOptions.html#structfield.align_equals
+/// [`write_section`]: #method.write_section
+/// [`finish`]: #method.finish
+#[derive(Debug)]
+pub struct ConfWriter<W: io::Write> {
+    writer: W,
+    options: WriteOptions,
+    /// Name of the section being written.
+    section: Option<String>,
+    started: bool,
+    /// Whether the last line written still needs its line terminator.
+    pending_eol: bool,
+}
+
+impl<W: io::Write> ConfWriter<W> {
+    /// Create a writer that writes to `writer` using `options`.
+    pub fn new(writer: W, options: WriteOptions) -> ConfWriter<W> {
+        ConfWriter {
+            writer,
+            options,
+            section: None,
+            started: false,
+            pending_eol: false,
+        }
+    }
+
+    /// Whether a section named `name` would be started without a header.
+    fn headerless(&self, name: &str) -> bool {
+        self.options.sectionless && name.is_empty() && self.section.is_none()
+    }
+
+    fn line(&mut self, text: &str) -> io::Result<()> {
+        if !self.started && self.options.bom {
+            self.writer.write_all("\u{feff}".as_bytes())?;
+        }
+        self.started = true;
+        if self.pending_eol {
+            self.writer.write_all(self.options.eol().as_bytes())?;
+        }
+        self.pending_eol = true;
+        self.writer.write_all(text.as_bytes())
+    }
+
+    /// Write the header of a new section, flushing the previous section.
+    /// Entries written after this belong to the new section. Fails with
+    /// `InvalidInput` if the name cannot be written.
+    ///
+    /// With [`WriteOptions::sectionless`], a first section with an empty
+    /// name is started without writing a header.
+    ///
+    /// [`WriteOptions::sectionless`]: ../struct.WriteOptions.html#structfield.sectionless
+    pub fn start_section(&mut self, name: &str) -> io::Result<()> {
+        if self.headerless(name) {
+            self.section = Some(String::new());
+            return Ok(());
+        }
+        check_section_name(name)?;
+        if self.section.is_some() {
+            self.writer.flush()?;
+        }
+        if self.started {
+            for _ in 0..self.options.blank_lines_between_sections {
+                self.line("")?;
+            }
+        }
+        self.line(&format!("[{}]", name))?;
+        self.section = Some(name.to_string());
+        Ok(())
+    }
+
+    /// Write an entry in the current section. Fails with `InvalidInput`
+    /// if no section has been started or the entry cannot be written.
+    pub fn write_entry(&mut self, key: &str, value: &str) -> io::Result<()> {
+        let section = match &self.section {
+            Some(section) => section,
+            None => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "entry outside of any section",
+                ))
+            }
+        };
+        check_entry(section, key, value)?;
+        self.entry(key, value, 0)
+    }
+
+    fn entry(&mut self, key: &str, value: &str, key_width: usize) -> io::Result<()> {
+        let equals = if self.options.spaces_around_equals {
+            " = "
+        } else {
+            "="
+        };
+        let text = format!(
+            "{}{:width$}{}{}",
+            self.options.indent,
+            key,
+            equals,
+            value,
+            width = key_width
+        );
+        self.line(&text)
+    }
+
+    /// Write a whole section and flush. Fails with `InvalidInput`,
+    /// writing nothing, if the section cannot be written.
+    pub fn write_section(&mut self, section: &Section) -> io::Result<()> {
+        if self.headerless(&section.name) {
+            section.check_entries_serializable()?;
+        } else {
+            section.check_serializable()?;
+        }
+        self.start_section(&section.name)?;
+        let mut entries: Vec<_> = section.entries.iter().collect();
+        if self.options.sort {
+            entries.sort_by(|a, b| a.key.cmp(&b.key));
+        }
+        let key_width = if self.options.align_equals {
+            entries
+                .iter()
+                .map(|e| e.key.chars().count())
+                .max()
+                .unwrap_or(0)
+        } else {
+            0
+        };
+        for entry in entries {
+            self.entry(&entry.key, &entry.value, key_width)?;
+        }
+        self.writer.flush()
+    }
+
+    /// Terminate the last line, flush, and return the underlying writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        if !self.started && self.options.bom {
+            self.writer.write_all("\u{feff}".as_bytes())?;
+        }
+        if self.pending_eol && self.options.trailing_newline {
+            self.writer.write_all(self.options.eol().as_bytes())?;
+        }
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Conf, LineEnding};
+
+    #[test]
+    fn test_conf_writer() {
+        let conf = Conf::parse_str("[b]\ny = 2\nlong = 3\n[a]\nx = 1\n[empty]\n").unwrap();
+        let options = WriteOptions {
+            line_ending: LineEnding::CrLf,
+            blank_lines_between_sections: 2,
+            align_equals: true,
+            trailing_newline: false,
+            ..WriteOptions::default()
+        };
+        let mut writer = ConfWriter::new(Vec::new(), options.clone());
+        for section in conf.iter() {
+            writer.write_section(section).unwrap();
+        }
+        let output = String::from_utf8(writer.finish().unwrap()).unwrap();
+        assert_eq!(output, conf.to_string_styled(&options));
+
+        let mut writer = ConfWriter::new(Vec::new(), WriteOptions::default());
+        assert!(writer.write_entry("x", "1").is_err());
+        writer.start_section("a").unwrap();
+        writer.write_entry("x", "1").unwrap();
+        assert!(writer.write_entry("y", " 2").is_err());
+        assert!(writer.start_section("").is_err());
+        writer.write_section(&conf.sections[1]).unwrap();
+        assert_eq!(
+            writer.finish().unwrap(),
+            b"[a]\nx = 1\n\n[a]\nx = 1\n".to_vec()
+        );
+
+        let options = WriteOptions {
+            sectionless: true,
+            ..WriteOptions::default()
+        };
+        let mut writer = ConfWriter::new(Vec::new(), options);
+        writer.start_section("").unwrap();
+        writer.write_entry("quiet", "1").unwrap();
+        assert!(writer.start_section("").is_err());
+        writer.start_section("a").unwrap();
+        assert_eq!(writer.finish().unwrap(), b"quiet = 1\n\n[a]\n".to_vec());
+    }
+}
@@ -0,0 +1,260 @@
+//! Classifying the text of a config, for syntax highlighting and other
+//! editor features.
+//!
+//! Example:
+//!
+//! ```
+//! use nbconf::tokens::{tokenize, TokenKind};
+//!
+//! let input = "[server]\nport = 80\n";
+//! let kinds: Vec<(TokenKind, &str)> = tokenize(input)
+//!     .into_iter()
+//!     .map(|t| (t.kind, &input[t.range]))
+//!     .collect();
+//! assert_eq!(
+//!     kinds,
+//!     vec![
+//!         (TokenKind::SectionHeader, "[server]"),
+//!         (TokenKind::Key, "port"),
+//!         (TokenKind::Operator, "="),
+//!         (TokenKind::Value, "80"),
+//!     ]
+//! );
+//! ```
+
+use crate::{is_comment, split_header};
+use std::ops::Range;
+
+/// What a [`Token`] is.
+///
+/// [`Token`]: struct.Token.html
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum TokenKind {
+    /// A section header, including its brackets but not a comment after
+    /// it.
+    SectionHeader,
+    /// The key of an entry.
+    Key,
+    /// The `=` between a key and its value.
+    Operator,
+    /// The value of an entry.
+    Value,
+    /// A comment line, including its `#` or `;`.
+    Comment,
+}
+
+/// A classified piece of the text.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Token {
+    /// What the token is.
+    pub kind: TokenKind,
+    /// Byte range of the token in the text.
+    pub range: Range<usize>,
+}
+
+/// Split `input` into tokens, in order. Whitespace around tokens is not
+/// included in them, and empty keys and values produce no token.
+///
+/// This never fails, so that text being edited can still be highlighted.
+/// A line starting with `[` is a section header even without its closing
+/// bracket, and any other line without `=` is a key.
+pub fn tokenize(input: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut offset = if input.starts_with('\u{feff}') {
+        '\u{feff}'.len_utf8()
+    } else {
+        0
+    };
+    let mut push = |kind, start: usize, text: &str| {
+        if !text.is_empty() {
+            tokens.push(Token {
+                kind,
+                range: start..start + text.len(),
+            });
+        }
+    };
+    for raw_line in input[offset..].split_inclusive('\n') {
+        let line = raw_line.trim_start();
+        let start = offset + raw_line.len() - line.len();
+        let line = line.trim_end();
+        offset += raw_line.len();
+
+        if is_comment(line) {
+            push(TokenKind::Comment, start, line);
+        } else if line.starts_with('[') {
+            // Ignored text after the header is not a token.
+            let (header, text) = match split_header(line) {
+                Some((name, text)) => (&line[..name.len() + 2], text),
+                None => (line, ""),
+            };
+            push(TokenKind::SectionHeader, start, header);
+            if is_comment(text) {
+                push(TokenKind::Comment, start + line.len() - text.len(), text);
+            }
+        } else if let Some(equals) = line.find('=') {
+            let value = line[equals + 1..].trim_start();
+            push(TokenKind::Key, start, line[..equals].trim_end());
+            push(TokenKind::Operator, start + equals, "=");
+            push(TokenKind::Value, start + line.len() - value.len(), value);
+        } else {
+            push(TokenKind::Key, start, line);
+        }
+    }
+    tokens
+}
+
+/// Which part of an entry or header a position is in.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum NodePart {
+    /// A section header.
+    SectionHeader,
+    /// The key of an entry.
+    Key,
+    /// The value of an entry.
+    Value,
+}
+
+/// The section header or entry at a position. Returned by [`node_at`].
+///
+/// [`node_at`]: fn.node_at.html
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Node<'a> {
+    /// Name of the section the position is in, or `None` before the first
+    /// header.
+    pub section: Option<&'a str>,
+    /// Key of the entry the position is in, or `None` in a header or an
+    /// entry with an empty key.
+    pub key: Option<&'a str>,
+    /// Which part the position is in.
+    pub part: NodePart,
+    /// Byte range of that part in the text.
+    pub range: Range<usize>,
+}
+
+/// The section name in a section header token.
+fn header_name(text: &str) -> &str {
+    let name = &text[1..];
+    name.strip_suffix(']').unwrap_or(name)
+}
+
+/// Find the header, key or value containing byte `offset` in `input`,
+/// including the position just after it. Returns `None` in comments,
+/// whitespace and at the `=`.
+pub fn node_at(input: &str, offset: usize) -> Option<Node<'_>> {
+    let mut section = None;
+    let mut key: Option<(&str, usize)> = None;
+    for token in tokenize(input) {
+        let text = &input[token.range.clone()];
+        let part = match token.kind {
+            TokenKind::SectionHeader => {
+                section = Some(header_name(text));
+                key = None;
+                NodePart::SectionHeader
+            }
+            TokenKind::Key => {
+                key = Some((text, token.range.end));
+                NodePart::Key
+            }
+            TokenKind::Value => NodePart::Value,
+            TokenKind::Operator | TokenKind::Comment => continue,
+        };
+        if token.range.start > offset {
+            break;
+        }
+        if offset > token.range.end {
+            continue;
+        }
+        let key = match part {
+            NodePart::SectionHeader => None,
+            NodePart::Key => Some(text),
+            // The key belongs to this entry if it is on the same line.
+            NodePart::Value => key
+                .filter(|(_, end)| !input[*end..token.range.start].contains('\n'))
+                .map(|(key, _)| key),
+        };
+        return Some(Node {
+            section,
+            key,
+            part,
+            range: token.range,
+        });
+    }
+    None
+}
+
+/// The byte range of each section that has entries, from the start of its
+/// header to the end of its last entry, for folding in an editor.
+pub fn folding_ranges(input: &str) -> Vec<Range<usize>> {
+    let mut ranges: Vec<Range<usize>> = Vec::new();
+    let mut current: Option<Range<usize>> = None;
+    for token in tokenize(input) {
+        match token.kind {
+            TokenKind::SectionHeader => {
+                ranges.extend(current.take().filter(|r| !r.is_empty()));
+                current = Some(token.range.start..token.range.start);
+            }
+            TokenKind::Comment => {}
+            _ => {
+                if let Some(range) = &mut current {
+                    range.end = token.range.end;
+                }
+            }
+        }
+    }
+    ranges.extend(current.filter(|r| !r.is_empty()));
+    ranges
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tokenize() {
+        let input = "\u{feff}# hi\r\n [a] # c \r\n\tk  =\tv w \r\n=\nkey\n\n; x = y\n[b";
+        let tokens: Vec<(TokenKind, &str)> = tokenize(input)
+            .into_iter()
+            .map(|t| (t.kind, &input[t.range]))
+            .collect();
+        assert_eq!(
+            tokens,
+            vec![
+                (TokenKind::Comment, "# hi"),
+                (TokenKind::SectionHeader, "[a]"),
+                (TokenKind::Comment, "# c"),
+                (TokenKind::Key, "k"),
+                (TokenKind::Operator, "="),
+                (TokenKind::Value, "v w"),
+                (TokenKind::Operator, "="),
+                (TokenKind::Key, "key"),
+                (TokenKind::Comment, "; x = y"),
+                (TokenKind::SectionHeader, "[b"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_node_at() {
+        let input = "k = 0\n[a]\nx = 1\n# c\n\n;d\n[b]\n= 2\n[c]\ny\n= 3\n";
+        let node =
+            |offset| node_at(input, offset).map(|n| (n.section, n.key, n.part, &input[n.range]));
+        assert_eq!(node(0), Some((None, Some("k"), NodePart::Key, "k")));
+        assert_eq!(node(2), None);
+        assert_eq!(
+            node(8),
+            Some((Some("a"), None, NodePart::SectionHeader, "[a]"))
+        );
+        assert_eq!(node(15), Some((Some("a"), Some("x"), NodePart::Value, "1")));
+        assert_eq!(node(19), None);
+        assert_eq!(node(36), Some((Some("c"), Some("y"), NodePart::Key, "y")));
+        assert_eq!(node(30), Some((Some("b"), None, NodePart::Value, "2")));
+        assert_eq!(node(40), Some((Some("c"), None, NodePart::Value, "3")));
+
+        let folds: Vec<&str> = folding_ranges(input)
+            .into_iter()
+            .map(|r| &input[r])
+            .collect();
+        assert_eq!(folds, vec!["[a]\nx = 1", "[b]\n= 2", "[c]\ny\n= 3"]);
+        assert_eq!(folding_ranges("[a]\n# x\n[b]\n"), vec![]);
+    }
+}
@@ -0,0 +1,115 @@
+//! Nested view of sections with dotted names.
+//!
+//! Sections such as `[net]`, `[net.tls]` and `[net.tls.client]` form a
+//! tree, where each dot separates a parent from a child.
+//!
+//! Example:
+//!
+//! ```
+//! let conf = nbconf::Conf::parse_str("[net]\n[net.tls]\n[net.tls.client]\n[log]").unwrap();
+//! let tree = conf.tree();
+//! let net = tree.child("net").unwrap();
+//! assert_eq!(net.child("tls").unwrap().path, "net.tls");
+//! assert_eq!(tree.children.len(), 2);
+//! ```
+
+use crate::{Conf, Section};
+
+/// A node in the tree of sections returned by [`Conf::tree`].
+///
+/// [`Conf::tree`]: ../struct.Conf.html#method.tree
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct TreeNode<'a> {
+    /// Last part of the node's path, or empty for the root.
+    pub name: &'a str,
+    /// Full dotted path of the node, or empty for the root.
+    pub path: &'a str,
+    /// Sections whose name is the node's path. This is empty if the node
+    /// only exists because it has children, and has more than one
+    /// section if the name is repeated.
+    pub sections: Vec<&'a Section>,
+    /// Child nodes, in order of their first section.
+    pub children: Vec<TreeNode<'a>>,
+}
+
+impl<'a> TreeNode<'a> {
+    /// Get the child named `name`.
+    pub fn child(&self, name: &str) -> Option<&TreeNode<'a>> {
+        self.children.iter().find(|c| c.name == name)
+    }
+
+    /// Flatten the node and its descendants back into a config with
+    /// dotted section names. Each node's sections come before those of
+    /// its children.
+    pub fn to_conf(&self) -> Conf {
+        let mut conf = Conf::new();
+        self.flatten_into(&mut conf);
+        conf
+    }
+
+    fn flatten_into(&self, conf: &mut Conf) {
+        conf.sections
+            .extend(self.sections.iter().map(|s| (*s).clone()));
+        for child in self.children.iter() {
+            child.flatten_into(conf);
+        }
+    }
+}
+
+pub(crate) fn build(conf: &Conf) -> TreeNode<'_> {
+    let mut root = TreeNode::default();
+    for section in conf.sections.iter() {
+        let mut node = &mut root;
+        let mut end = 0;
+        for name in section.name.split('.') {
+            if end != 0 {
+                end += 1;
+            }
+            end += name.len();
+            let index = match node.children.iter().position(|c| c.name == name) {
+                Some(index) => index,
+                None => {
+                    node.children.push(TreeNode {
+                        name,
+                        path: &section.name[..end],
+                        ..TreeNode::default()
+                    });
+                    node.children.len() - 1
+                }
+            };
+            node = &mut node.children[index];
+        }
+        node.sections.push(section);
+    }
+    root
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tree() {
+        let conf =
+            Conf::parse_str("[net.tls]\na = 1\n[log]\n[net]\nb = 2\n[net.tls.client]\n[net.tls]")
+                .unwrap();
+        let tree = conf.tree();
+        let names: Vec<&str> = tree.children.iter().map(|c| c.name).collect();
+        assert_eq!(names, ["net", "log"]);
+
+        let net = tree.child("net").unwrap();
+        assert_eq!(net.sections, [&conf.sections[2]]);
+        let tls = net.child("tls").unwrap();
+        assert_eq!((tls.path, tls.sections.len()), ("net.tls", 2));
+        assert_eq!(tls.child("client").unwrap().path, "net.tls.client");
+
+        assert_eq!(
+            tree.to_conf().section_names(),
+            ["net", "net.tls", "net.tls", "net.tls.client", "log"]
+        );
+        assert_eq!(
+            tls.to_conf().section_names(),
+            ["net.tls", "net.tls", "net.tls.client"]
+        );
+    }
+}
@@ -0,0 +1,968 @@
+//! Typed accessors for entry values.
+//!
+//! Example:
+//!
+//! ```
+//! use std::time::Duration;
+//!
+//! let conf = nbconf::Conf::parse_str("[server]\ntimeout = 1m30s").unwrap();
+//! assert_eq!(
+//!     conf.sections[0].get_duration("timeout"),
+//!     Ok(Duration::from_secs(90))
+//! );
+//! ```
+//!
+//! [`Entry::typed_value`] guesses the type of a value from its text:
+//!
+//! ```
+//! use nbconf::value::Value;
+//!
+//! let conf = nbconf::Conf::parse_str("[server]\nport = 80\npublic = true").unwrap();
+//! assert_eq!(conf.sections[0].entries[0].typed_value(), Value::Int(80));
+//! assert_eq!(conf.sections[0].entries[1].typed_value(), Value::Bool(true));
+//! ```
+//!
+//! [`Conf::typed`] detects the type of every value once, for code that
+//! reads the same values many times:
+//!
+//! ```
+//! use nbconf::value::Value;
+//!
+//! let conf = nbconf::Conf::parse_str("[server]\nport = 80\npublic = true").unwrap();
+//! let typed = conf.typed();
+//! assert_eq!(typed.get("server", "port"), Some(Value::Int(80)));
+//! assert_eq!(typed.get_path("server.public"), Some(Value::Bool(true)));
+//! ```
+//!
+//! [`Entry::typed_value`]: ../struct.Entry.html#method.typed_value
+//! [`Conf::typed`]: ../struct.Conf.html#method.typed
+
+use crate::{split_path, Conf, Entry, Section};
+use std::convert::TryFrom;
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// The specific type of value error.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ValueErrorKind {
+    /// The key is not in the section.
+    Missing,
+    /// The value could not be parsed. `expected` describes the type of
+    /// value that was expected, and `reason` what is wrong with it.
+    Invalid { expected: String, reason: String },
+}
+
+/// Error returned by the typed getters on [`Section`].
+///
+/// [`Section`]: ../struct.Section.html
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ValueError {
+    /// Key that was looked up.
+    pub key: String,
+    /// Value of the key, if it is present.
+    pub value: Option<String>,
+    /// Type of error.
+    pub kind: ValueErrorKind,
+}
+
+impl ValueError {
+    fn invalid(key: &str, value: &str, expected: &str, reason: &str) -> ValueError {
+        ValueError {
+            key: key.to_string(),
+            value: Some(value.to_string()),
+            kind: ValueErrorKind::Invalid {
+                expected: expected.to_string(),
+                reason: reason.to_string(),
+            },
+        }
+    }
+}
+
+impl std::fmt::Display for ValueError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match &self.kind {
+            ValueErrorKind::Missing => write!(f, "{}: missing key", self.key),
+            ValueErrorKind::Invalid { expected, reason } => write!(
+                f,
+                "{}: invalid value {:?}, expected {}: {}",
+                self.key,
+                self.value.as_deref().unwrap_or_default(),
+                expected,
+                reason
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ValueError {}
+
+/// Parse a duration such as `250ms`, `30s`, `5m`, or `1h30m`. A plain
+/// number is a count of seconds.
+fn parse_duration(value: &str) -> Result<Duration, String> {
+    if value.is_empty() {
+        return Err("empty value".to_string());
+    }
+    if let Ok(secs) = value.parse::<u64>() {
+        return Ok(Duration::from_secs(secs));
+    }
+
+    let mut total = Duration::from_secs(0);
+    let mut rest = value;
+    while !rest.is_empty() {
+        let digits = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        if digits == 0 {
+            return Err(format!("expected a number at {:?}", rest));
+        }
+        let number: u64 = rest[..digits]
+            .parse()
+            .map_err(|_| format!("number {} is too large", &rest[..digits]))?;
+        rest = &rest[digits..];
+
+        let unit_len = rest
+            .find(|c: char| c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let unit = &rest[..unit_len];
+        let nanos_per_unit: u64 = match unit {
+            "ns" => 1,
+            "us" | "µs" => 1_000,
+            "ms" => 1_000_000,
+            "s" => 1_000_000_000,
+            "m" => 60 * 1_000_000_000,
+            "h" => 60 * 60 * 1_000_000_000,
+            "d" => 24 * 60 * 60 * 1_000_000_000,
+            "" => return Err(format!("missing unit after {}", number)),
+            _ => {
+                return Err(format!(
+                    "unknown unit {:?}, expected one of ns, us, ms, s, m, h, d",
+                    unit
+                ))
+            }
+        };
+        rest = &rest[unit_len..];
+
+        total = number
+            .checked_mul(nanos_per_unit)
+            .map(Duration::from_nanos)
+            .and_then(|part| total.checked_add(part))
+            .ok_or_else(|| "duration is too large".to_string())?;
+    }
+    Ok(total)
+}
+
+/// Meaning of unit prefixes without an `i`, such as `KB` or `G`, in
+/// [`Section::get_bytes_with`]. Prefixes with an `i`, such as `KiB`, are
+/// always powers of 1024.
+///
+/// [`Section::get_bytes_with`]: ../struct.Section.html#method.get_bytes_with
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum ByteUnits {
+    /// Powers of 1000, so `1KB` is 1000 bytes.
+    #[default]
+    Decimal,
+    /// Powers of 1024, so `1KB` is 1024 bytes.
+    Binary,
+}
+
+/// Parse a byte size such as `512`, `10KB`, `4MiB`, or `1.5G`.
+fn parse_bytes(value: &str, units: ByteUnits) -> Result<u64, String> {
+    let number_len = value
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(number_len);
+    let (whole, fraction) = match number.find('.') {
+        Some(dot) => (&number[..dot], &number[dot + 1..]),
+        None => (number, ""),
+    };
+    if whole.is_empty() || (number.contains('.') && fraction.is_empty()) {
+        return Err("expected a number".to_string());
+    }
+    if fraction.contains('.') {
+        return Err(format!("invalid number {}", number));
+    }
+
+    let unit = unit.trim_start();
+    let base: u128 = if unit.contains('i') || units == ByteUnits::Binary {
+        1024
+    } else {
+        1000
+    };
+    let power = match unit.to_ascii_lowercase().as_str() {
+        "" | "b" => 0,
+        "k" | "kb" | "kib" => 1,
+        "m" | "mb" | "mib" => 2,
+        "g" | "gb" | "gib" => 3,
+        "t" | "tb" | "tib" => 4,
+        "p" | "pb" | "pib" => 5,
+        "e" | "eb" | "eib" => 6,
+        _ => {
+            return Err(format!(
+                "unknown unit {:?}, expected one of B, KB, MB, GB, TB, PB, EB, KiB, MiB, GiB, TiB, PiB, EiB",
+                unit
+            ))
+        }
+    };
+    let multiplier = base.pow(power);
+
+    let too_large = || "size is too large".to_string();
+    let whole: u128 = whole.parse().map_err(|_| too_large())?;
+    let mut bytes = whole.checked_mul(multiplier).ok_or_else(too_large)?;
+    if !fraction.is_empty() {
+        let fraction_value: u128 = fraction.parse().map_err(|_| too_large())?;
+        let scale = 10u128
+            .checked_pow(fraction.len() as u32)
+            .ok_or_else(too_large)?;
+        let scaled = fraction_value
+            .checked_mul(multiplier)
+            .ok_or_else(too_large)?;
+        if scaled % scale != 0 {
+            return Err("not a whole number of bytes".to_string());
+        }
+        bytes += scaled / scale;
+    }
+    u64::try_from(bytes).map_err(|_| too_large())
+}
+
+/// Forms accepted by [`Section::get_bool_with`].
+///
+/// [`Section::get_bool_with`]: ../struct.Section.html#method.get_bool_with
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum BoolSyntax {
+    /// Only `true` and `false`.
+    #[default]
+    Strict,
+    /// `true/false`, `yes/no`, `on/off`, `1/0`, and `enabled/disabled`,
+    /// in any case.
+    Extended,
+}
+
+/// Parse a boolean in the given syntax.
+fn parse_bool(value: &str, syntax: BoolSyntax) -> Result<bool, String> {
+    let (true_forms, false_forms): (&[&str], &[&str]) = match syntax {
+        BoolSyntax::Strict => (&["true"], &["false"]),
+        BoolSyntax::Extended => (
+            &["true", "yes", "on", "1", "enabled"],
+            &["false", "no", "off", "0", "disabled"],
+        ),
+    };
+    let matches = |forms: &[&str]| match syntax {
+        BoolSyntax::Strict => forms.contains(&value),
+        BoolSyntax::Extended => forms.iter().any(|f| f.eq_ignore_ascii_case(value)),
+    };
+    if matches(true_forms) {
+        Ok(true)
+    } else if matches(false_forms) {
+        Ok(false)
+    } else {
+        let forms: Vec<String> = true_forms
+            .iter()
+            .zip(false_forms)
+            .map(|(t, f)| format!("{}/{}", t, f))
+            .collect();
+        Err(format!("accepted forms are {}", forms.join(", ")))
+    }
+}
+
+/// An entry value with its type detected from its text. Returned by
+/// [`Entry::typed_value`].
+///
+/// [`Entry::typed_value`]: ../struct.Entry.html#method.typed_value
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Value<'a> {
+    /// Any value that is not one of the others.
+    Str(&'a str),
+    /// A whole number that fits in an `i64`, such as `-12`.
+    Int(i64),
+    /// A number with a decimal point or an exponent, such as `1.5` or
+    /// `2e10`.
+    Float(f64),
+    /// `true` or `false`.
+    Bool(bool),
+}
+
+impl<'a> Value<'a> {
+    /// Detect the type of `value`. Only the forms listed on each variant
+    /// are detected, so `inf`, `yes` and `0x10` are strings.
+    pub fn detect(value: &'a str) -> Value<'a> {
+        if let Ok(value) = parse_bool(value, BoolSyntax::Strict) {
+            return Value::Bool(value);
+        }
+        if let Ok(value) = value.parse() {
+            return Value::Int(value);
+        }
+        let digits = value.trim_start_matches(&['+', '-'][..]);
+        let number = digits.starts_with(|c: char| c.is_ascii_digit() || c == '.')
+            && digits.contains(|c: char| c.is_ascii_digit())
+            && digits.contains(&['.', 'e', 'E'][..])
+            && digits
+                .chars()
+                .all(|c| c.is_ascii_digit() || "+-.eE".contains(c));
+        match value.parse() {
+            Ok(float) if number => Value::Float(float),
+            _ => Value::Str(value),
+        }
+    }
+}
+
+impl Entry {
+    /// The entry's value with its type detected as by [`Value::detect`].
+    ///
+    /// [`Value::detect`]: value/enum.Value.html#method.detect
+    pub fn typed_value(&self) -> Value<'_> {
+        Value::detect(&self.value)
+    }
+}
+
+/// A read-only view of a config with the type of every value detected
+/// once, as by [`Value::detect`]. Returned by [`Conf::typed`].
+///
+/// There is one value per entry, so repeated keys and sections keep all
+/// of their values, and [`section_values`] lines up with the entries of
+/// the same section.
+///
+/// [`Conf::typed`]: ../struct.Conf.html#method.typed
+/// [`Value::detect`]: enum.Value.html#method.detect
+/// [`section_values`]: #method.section_values
+#[derive(Clone, Debug)]
+pub struct TypedConf<'a> {
+    conf: &'a Conf,
+    /// The value of each entry, section by section.
+    values: Vec<Vec<Value<'a>>>,
+}
+
+impl<'a> TypedConf<'a> {
+    /// The config being viewed.
+    pub fn conf(&self) -> &'a Conf {
+        self.conf
+    }
+
+    /// The values of the entries of the section at `index` in
+    /// [`Conf::sections`], in order.
+    ///
+    /// [`Conf::sections`]: ../struct.Conf.html#structfield.sections
+    pub fn section_values(&self, index: usize) -> &[Value<'a>] {
+        self.values.get(index).map_or(&[], Vec::as_slice)
+    }
+
+    /// Get the value of `key` in the first section named `section` that
+    /// has it.
+    pub fn get(&self, section: &str, key: &str) -> Option<Value<'a>> {
+        self.conf
+            .sections
+            .iter()
+            .zip(self.values.iter())
+            .filter(|(s, _)| s.name == section)
+            .find_map(|(s, values)| {
+                let index = s.entries.iter().position(|e| e.key == key)?;
+                Some(values[index])
+            })
+    }
+
+    /// Get a value by its dotted path, as [`Conf::get_path`] does.
+    ///
+    /// [`Conf::get_path`]: ../struct.Conf.html#method.get_path
+    pub fn get_path(&self, path: &str) -> Option<Value<'a>> {
+        split_path(path).find_map(|(section, key)| self.get(section, key))
+    }
+}
+
+impl Conf {
+    /// Detect the type of every value into a [`TypedConf`], so that
+    /// typed lookups do not parse the same text again. Every entry is
+    /// detected up front, whether or not it is looked up.
+    ///
+    /// [`TypedConf`]: value/struct.TypedConf.html
+    pub fn typed(&self) -> TypedConf<'_> {
+        TypedConf {
+            conf: self,
+            values: self
+                .sections
+                .iter()
+                .map(|s| s.entries.iter().map(Entry::typed_value).collect())
+                .collect(),
+        }
+    }
+}
+
+/// Split a list on `separator`, trimming whitespace around each item.
+/// Items in double quotes may contain the separator, and `\"` and `\\`
+/// inside quotes are a literal quote and backslash.
+fn parse_list(value: &str, separator: char) -> Result<Vec<String>, String> {
+    let mut items = Vec::new();
+    if value.trim().is_empty() {
+        return Ok(items);
+    }
+    let mut chars = value.chars().peekable();
+    loop {
+        while chars.peek().is_some_and(|c| c.is_whitespace()) {
+            chars.next();
+        }
+        let mut item = String::new();
+        if chars.peek() == Some(&'"') {
+            chars.next();
+            loop {
+                match chars.next() {
+                    Some('"') => break,
+                    Some('\\') => match chars.next() {
+                        Some(c @ '"') | Some(c @ '\\') => item.push(c),
+                        Some(c) => return Err(format!("unknown escape \\{}", c)),
+                        None => return Err("unterminated quote".to_string()),
+                    },
+                    Some(c) => item.push(c),
+                    None => return Err("unterminated quote".to_string()),
+                }
+            }
+            while chars.peek().is_some_and(|c| c.is_whitespace()) {
+                chars.next();
+            }
+            match chars.next() {
+                None => {
+                    items.push(item);
+                    return Ok(items);
+                }
+                Some(c) if c == separator => items.push(item),
+                Some(c) => return Err(format!("unexpected {:?} after quoted item", c)),
+            }
+        } else {
+            loop {
+                match chars.next() {
+                    None => {
+                        items.push(item.trim_end().to_string());
+                        return Ok(items);
+                    }
+                    Some(c) if c == separator => break,
+                    Some(c) => item.push(c),
+                }
+            }
+            items.push(item.trim_end().to_string());
+        }
+    }
+}
+
+/// Join `items` with `separator` so that [`parse_list`] gives them back,
+/// quoting items where needed.
+///
+/// [`parse_list`]: fn.parse_list.html
+fn format_list<S: AsRef<str>>(items: &[S], separator: char) -> String {
+    let items: Vec<String> = items
+        .iter()
+        .map(|item| {
+            let item = item.as_ref();
+            let needs_quotes = item.is_empty()
+                || item.contains(separator)
+                || item.starts_with('"')
+                || item.trim() != item;
+            if needs_quotes {
+                format!("\"{}\"", item.replace('\\', "\\\\").replace('"', "\\\""))
+            } else {
+                item.to_string()
+            }
+        })
+        .collect();
+    let mut joined = separator.to_string();
+    if separator != ' ' {
+        joined.push(' ');
+    }
+    items.join(&joined)
+}
+
+/// The current user's home directory, from the environment.
+fn home_dir() -> Option<String> {
+    let var = if cfg!(windows) { "USERPROFILE" } else { "HOME" };
+    std::env::var(var).ok().filter(|home| !home.is_empty())
+}
+
+/// The home directory of `user`, from `/etc/passwd`.
+fn user_home_dir(user: &str) -> Option<String> {
+    let passwd = std::fs::read_to_string("/etc/passwd").ok()?;
+    passwd.lines().find_map(|line| {
+        let fields: Vec<&str> = line.split(':').collect();
+        if fields.len() >= 6 && fields[0] == user {
+            Some(fields[5].to_string())
+        } else {
+            None
+        }
+    })
+}
+
+fn is_var_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+/// Expand a leading `~` or `~user`, and `$VAR`, `${VAR}` and `%VAR%`
+/// anywhere in `value`. `var` looks up environment variables, and `home`
+/// looks up the home directory of a user, or of the current user when
+/// given an empty name.
+fn expand_path(
+    value: &str,
+    var: &dyn Fn(&str) -> Option<String>,
+    home: &dyn Fn(&str) -> Option<String>,
+) -> Result<PathBuf, String> {
+    if value.is_empty() {
+        return Err("empty value".to_string());
+    }
+    let mut rest = value;
+    let mut expanded = String::new();
+    if let Some(after_tilde) = rest.strip_prefix('~') {
+        let user_len = after_tilde.find(['/', '\\']).unwrap_or(after_tilde.len());
+        let user = &after_tilde[..user_len];
+        expanded = home(user).ok_or_else(|| {
+            if user.is_empty() {
+                "home directory is unknown".to_string()
+            } else {
+                format!("unknown user {:?}", user)
+            }
+        })?;
+        rest = &after_tilde[user_len..];
+    }
+
+    let lookup = |name: &str, expanded: &mut String| match var(name) {
+        Some(value) => {
+            *expanded += &value;
+            Ok(())
+        }
+        None => Err(format!("environment variable {} is not set", name)),
+    };
+    while let Some(start) = rest.find(['$', '%']) {
+        expanded += &rest[..start];
+        let sigil = rest[start..].chars().next().unwrap();
+        let after = &rest[start + 1..];
+        if sigil == '$' && after.starts_with('{') {
+            let end = after
+                .find('}')
+                .ok_or_else(|| "missing closing brace".to_string())?;
+            lookup(&after[1..end], &mut expanded)?;
+            rest = &after[end + 1..];
+            continue;
+        }
+        let name_len = after.find(|c| !is_var_char(c)).unwrap_or(after.len());
+        let name = &after[..name_len];
+        if sigil == '$' && !name.is_empty() {
+            lookup(name, &mut expanded)?;
+            rest = &after[name_len..];
+        } else if sigil == '%' && !name.is_empty() && after[name_len..].starts_with('%') {
+            lookup(name, &mut expanded)?;
+            rest = &after[name_len + 1..];
+        } else {
+            // Not a variable, so keep the sigil as it is.
+            expanded.push(sigil);
+            rest = after;
+        }
+    }
+    expanded += rest;
+    Ok(PathBuf::from(expanded))
+}
+
+impl Section {
+    /// Get the value of `key` and parse it with `parse`.
+    fn get_parsed<T>(
+        &self,
+        key: &str,
+        expected: &str,
+        parse: impl FnOnce(&str) -> Result<T, String>,
+    ) -> Result<T, ValueError> {
+        let value = self.get(key).ok_or_else(|| ValueError {
+            key: key.to_string(),
+            value: None,
+            kind: ValueErrorKind::Missing,
+        })?;
+        parse(value).map_err(|reason| ValueError::invalid(key, value, expected, &reason))
+    }
+
+    /// Get the value of `key` as a duration, such as `250ms`, `30s`,
+    /// `5m`, or `1h30m`. A plain number is a count of seconds.
+    ///
+    /// The units are `ns`, `us`, `ms`, `s`, `m`, `h` and `d`.
+    pub fn get_duration(&self, key: &str) -> Result<Duration, ValueError> {
+        self.get_parsed(key, "a duration", parse_duration)
+    }
+
+    /// Get the value of `key` as a boolean, either `true` or `false`.
+    pub fn get_bool(&self, key: &str) -> Result<bool, ValueError> {
+        self.get_bool_with(key, BoolSyntax::Strict)
+    }
+
+    /// Get the value of `key` as a boolean written in `syntax`.
+    pub fn get_bool_with(&self, key: &str, syntax: BoolSyntax) -> Result<bool, ValueError> {
+        self.get_parsed(key, "a boolean", |value| parse_bool(value, syntax))
+    }
+
+    /// Get the value of `key` as a comma-separated list.
+    ///
+    /// See [`get_list_with`] for the syntax.
+    ///
+    /// [`get_list_with`]: #method.get_list_with
+    pub fn get_list(&self, key: &str) -> Result<Vec<String>, ValueError> {
+        self.get_list_with(key, ',')
+    }
+
+    /// Get the value of `key` as a list split on `separator`.
+    ///
+    /// Whitespace around each item is removed, and an empty value is an
+    /// empty list. Items in double quotes may contain the separator or
+    /// surrounding whitespace; inside quotes, `\"` is a quote and `\\`
+    /// is a backslash.
+    pub fn get_list_with(&self, key: &str, separator: char) -> Result<Vec<String>, ValueError> {
+        self.get_parsed(key, "a list", |value| parse_list(value, separator))
+    }
+
+    /// Set `key` to a comma-separated list of `items`, quoting items
+    /// where needed so that [`get_list`] gives them back.
+    ///
+    /// [`get_list`]: #method.get_list
+    pub fn set_list<S: AsRef<str>>(&mut self, key: &str, items: &[S]) {
+        self.set_list_with(key, items, ',')
+    }
+
+    /// Set `key` to a list of `items` separated by `separator`.
+    pub fn set_list_with<S: AsRef<str>>(&mut self, key: &str, items: &[S], separator: char) {
+        self.set(key, &format_list(items, separator))
+    }
+
+    /// Get the value of `key` as a path.
+    ///
+    /// A leading `~` or `~user` is replaced with the home directory of
+    /// the current user or of `user`, and `$VAR`, `${VAR}` and `%VAR%` are
+    /// replaced with the value of the environment variable. It is an
+    /// error if a variable is not set.
+    pub fn get_path(&self, key: &str) -> Result<PathBuf, ValueError> {
+        self.get_parsed(key, "a path", |value| {
+            let home = |user: &str| {
+                if user.is_empty() {
+                    home_dir()
+                } else {
+                    user_home_dir(user)
+                }
+            };
+            expand_path(value, &|name| std::env::var(name).ok(), &home)
+        })
+    }
+
+    /// Get the value of `key` as a path like [`get_path`], then resolve
+    /// it against `base` if it is relative.
+    ///
+    /// When the config was read from a file, pass the file's directory
+    /// as `base` so that relative paths are relative to the config file.
+    ///
+    /// [`get_path`]: #method.get_path
+    pub fn get_path_relative_to(&self, key: &str, base: &Path) -> Result<PathBuf, ValueError> {
+        self.get_path(key).map(|path| base.join(path))
+    }
+
+    /// Get the value of `key` as an IPv4 or IPv6 address.
+    pub fn get_ip(&self, key: &str) -> Result<IpAddr, ValueError> {
+        self.get_parsed(key, "an IP address", |value| {
+            value
+                .parse()
+                .map_err(|err: std::net::AddrParseError| err.to_string())
+        })
+    }
+
+    /// Get the value of `key` as an IP address and port, such as
+    /// `127.0.0.1:80` or `[::1]:80`.
+    pub fn get_socket_addr(&self, key: &str) -> Result<SocketAddr, ValueError> {
+        self.get_parsed(key, "a socket address", |value| {
+            value
+                .parse()
+                .map_err(|err: std::net::AddrParseError| err.to_string())
+        })
+    }
+
+    /// Get the value of `key` as a port number.
+    pub fn get_port(&self, key: &str) -> Result<u16, ValueError> {
+        self.get_parsed(key, "a port", |value| {
+            value
+                .parse()
+                .map_err(|_| "not a number from 0 to 65535".to_string())
+        })
+    }
+
+    /// Get the value of `key` as a number of bytes, such as `512`,
+    /// `10KB`, `4MiB`, or `1.5G`. Units without an `i` are powers of
+    /// 1000.
+    pub fn get_bytes(&self, key: &str) -> Result<u64, ValueError> {
+        self.get_bytes_with(key, ByteUnits::Decimal)
+    }
+
+    /// Get the value of `key` as a number of bytes, with `units` giving
+    /// the meaning of units without an `i`.
+    ///
+    /// Units are case-insensitive, and may be separated from the number
+    /// by whitespace.
+    pub fn get_bytes_with(&self, key: &str, units: ByteUnits) -> Result<u64, ValueError> {
+        self.get_parsed(key, "a byte size", |value| parse_bytes(value, units))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Entry;
+
+    #[test]
+    fn test_get_duration() {
+        assert_eq!(parse_duration("30"), Ok(Duration::from_secs(30)));
+        assert_eq!(parse_duration("30s"), Ok(Duration::from_secs(30)));
+        assert_eq!(parse_duration("5m"), Ok(Duration::from_secs(300)));
+        assert_eq!(parse_duration("1h30m"), Ok(Duration::from_secs(5400)));
+        assert_eq!(parse_duration("250ms"), Ok(Duration::from_millis(250)));
+        assert_eq!(parse_duration("1s500ms"), Ok(Duration::from_millis(1500)));
+        assert_eq!(parse_duration(""), Err("empty value".to_string()));
+        assert_eq!(
+            parse_duration("1h30"),
+            Err("missing unit after 30".to_string())
+        );
+        assert_eq!(
+            parse_duration("m"),
+            Err("expected a number at \"m\"".to_string())
+        );
+        assert_eq!(
+            parse_duration("99999999999999999999d"),
+            Err("number 99999999999999999999 is too large".to_string())
+        );
+        assert_eq!(
+            parse_duration("999999999999d"),
+            Err("duration is too large".to_string())
+        );
+
+        let section = Section::new_with_entries(
+            "server",
+            vec![Entry::new("timeout", "2m"), Entry::new("retry", "5 s")],
+        );
+        assert_eq!(
+            section.get_duration("timeout"),
+            Ok(Duration::from_secs(120))
+        );
+        assert_eq!(
+            section.get_duration("retry").unwrap_err().to_string(),
+            "retry: invalid value \"5 s\", expected a duration: unknown unit \" s\", expected one of ns, us, ms, s, m, h, d"
+        );
+        assert_eq!(
+            section.get_duration("other").unwrap_err().to_string(),
+            "other: missing key"
+        );
+    }
+
+    #[test]
+    fn test_typed_value() {
+        assert_eq!(Value::detect("true"), Value::Bool(true));
+        assert_eq!(Value::detect("True"), Value::Str("True"));
+        assert_eq!(Value::detect("-12"), Value::Int(-12));
+        assert_eq!(Value::detect("+7"), Value::Int(7));
+        assert_eq!(Value::detect("1.5"), Value::Float(1.5));
+        assert_eq!(Value::detect("-.5e2"), Value::Float(-50.0));
+        assert_eq!(Value::detect("2E3"), Value::Float(2000.0));
+        assert_eq!(
+            Value::detect("99999999999999999999"),
+            Value::Str("99999999999999999999")
+        );
+        for text in ["inf", "NaN", "1.2.3", ".", "e5", "0x10", "", "1e"].iter() {
+            assert_eq!(Value::detect(text), Value::Str(text));
+        }
+        assert_eq!(Entry::new("port", "80").typed_value(), Value::Int(80));
+    }
+
+    #[test]
+    fn test_typed_conf() {
+        let conf = Conf::parse_str(
+            "[a]\nn = -12\nf = 2e3\nb = false\ns = yes\n[b]\nx = 1.0.0\n[a]\nn = 7\ne =\n",
+        )
+        .unwrap();
+        let typed = conf.typed();
+        assert_eq!(
+            typed.section_values(0),
+            &[
+                Value::Int(-12),
+                Value::Float(2000.0),
+                Value::Bool(false),
+                Value::Str("yes")
+            ][..]
+        );
+        assert_eq!(typed.section_values(1), &[Value::Str("1.0.0")][..]);
+        assert_eq!(
+            typed.section_values(2),
+            &[Value::Int(7), Value::Str("")][..]
+        );
+        assert!(typed.section_values(3).is_empty());
+
+        assert_eq!(typed.get("a", "n"), Some(Value::Int(-12)));
+        assert_eq!(typed.get("a", "e"), Some(Value::Str("")));
+        assert_eq!(typed.get("b", "n"), None);
+        assert_eq!(typed.get_path("b.x"), Some(Value::Str("1.0.0")));
+        assert!(std::ptr::eq(typed.conf(), &conf));
+    }
+
+    #[test]
+    fn test_get_bool() {
+        let section = Section::new_with_entries(
+            "server",
+            vec![
+                Entry::new("a", "true"),
+                Entry::new("b", "Off"),
+                Entry::new("c", "Enabled"),
+                Entry::new("d", "maybe"),
+            ],
+        );
+        assert_eq!(section.get_bool("a"), Ok(true));
+        assert_eq!(
+            section.get_bool("b").unwrap_err().to_string(),
+            "b: invalid value \"Off\", expected a boolean: accepted forms are true/false"
+        );
+        assert_eq!(section.get_bool_with("b", BoolSyntax::Extended), Ok(false));
+        assert_eq!(section.get_bool_with("c", BoolSyntax::Extended), Ok(true));
+        assert_eq!(
+            section.get_bool_with("d", BoolSyntax::Extended).unwrap_err().to_string(),
+            "d: invalid value \"maybe\", expected a boolean: accepted forms are true/false, yes/no, on/off, 1/0, enabled/disabled"
+        );
+    }
+
+    #[test]
+    fn test_get_list() {
+        assert_eq!(parse_list("", ','), Ok(vec![]));
+        assert_eq!(
+            parse_list(" a ,b,, c d ", ','),
+            Ok(vec![
+                "a".to_string(),
+                "b".to_string(),
+                "".to_string(),
+                "c d".to_string()
+            ])
+        );
+        assert_eq!(
+            parse_list(r#""a, b" , "say \"hi\"", c"#, ','),
+            Ok(vec![
+                "a, b".to_string(),
+                "say \"hi\"".to_string(),
+                "c".to_string()
+            ])
+        );
+        assert_eq!(
+            parse_list("a b  c", ' '),
+            Ok(vec!["a".to_string(), "b".to_string(), "c".to_string()])
+        );
+        assert_eq!(
+            parse_list("\"a", ','),
+            Err("unterminated quote".to_string())
+        );
+        assert_eq!(
+            parse_list("\"a\" b", ','),
+            Err("unexpected 'b' after quoted item".to_string())
+        );
+
+        let mut section = Section::new("server");
+        let items = ["a", "b, c", " d", "", "\"q\"", "x\\y"];
+        section.set_list("peers", &items);
+        assert_eq!(
+            section.get("peers"),
+            Some(r#"a, "b, c", " d", "", "\"q\"", x\y"#)
+        );
+        assert_eq!(section.get_list("peers").unwrap(), items);
+
+        section.set_list_with("peers", &["x", "y"], ':');
+        assert_eq!(section.get("peers"), Some("x: y"));
+        assert_eq!(section.get_list_with("peers", ':').unwrap(), ["x", "y"]);
+        assert_eq!(section.entries.len(), 1);
+    }
+
+    #[test]
+    fn test_get_path() {
+        let var = |name: &str| match name {
+            "DATA" => Some("/srv/data".to_string()),
+            "USER" => Some("alice".to_string()),
+            _ => None,
+        };
+        let home = |user: &str| match user {
+            "" => Some("/home/alice".to_string()),
+            "bob" => Some("/home/bob".to_string()),
+            _ => None,
+        };
+        let expand = |value| expand_path(value, &var, &home);
+        assert_eq!(expand("~/x"), Ok(PathBuf::from("/home/alice/x")));
+        assert_eq!(expand("~"), Ok(PathBuf::from("/home/alice")));
+        assert_eq!(expand("~bob/x"), Ok(PathBuf::from("/home/bob/x")));
+        assert_eq!(expand("a/~/b"), Ok(PathBuf::from("a/~/b")));
+        assert_eq!(
+            expand("$DATA/$USER.db"),
+            Ok(PathBuf::from("/srv/data/alice.db"))
+        );
+        assert_eq!(
+            expand("${DATA}x/%USER%"),
+            Ok(PathBuf::from("/srv/datax/alice"))
+        );
+        assert_eq!(expand("50% $ %"), Ok(PathBuf::from("50% $ %")));
+        assert_eq!(expand("~carol"), Err("unknown user \"carol\"".to_string()));
+        assert_eq!(
+            expand("$NOPE/x"),
+            Err("environment variable NOPE is not set".to_string())
+        );
+        assert_eq!(expand("${DATA"), Err("missing closing brace".to_string()));
+
+        let section = Section::new_with_entries("server", vec![Entry::new("log", "logs/app.log")]);
+        assert_eq!(
+            section.get_path_relative_to("log", Path::new("/etc/app")),
+            Ok(PathBuf::from("/etc/app/logs/app.log"))
+        );
+    }
+
+    #[test]
+    fn test_get_addresses() {
+        let section = Section::new_with_entries(
+            "server",
+            vec![
+                Entry::new("ip", "::1"),
+                Entry::new("listen", "[::1]:8080"),
+                Entry::new("port", "8080"),
+                Entry::new("bad", "localhost:99999"),
+            ],
+        );
+        assert_eq!(section.get_ip("ip"), Ok("::1".parse().unwrap()));
+        assert_eq!(
+            section.get_socket_addr("listen"),
+            Ok("[::1]:8080".parse().unwrap())
+        );
+        assert_eq!(section.get_port("port"), Ok(8080));
+        assert_eq!(
+            section.get_ip("bad").unwrap_err().to_string(),
+            "bad: invalid value \"localhost:99999\", expected an IP address: invalid IP address syntax"
+        );
+        assert_eq!(
+            section.get_socket_addr("bad").unwrap_err().to_string(),
+            "bad: invalid value \"localhost:99999\", expected a socket address: invalid socket address syntax"
+        );
+        assert_eq!(
+            section.get_port("bad").unwrap_err().to_string(),
+            "bad: invalid value \"localhost:99999\", expected a port: not a number from 0 to 65535"
+        );
+    }
+
+    #[test]
+    fn test_get_bytes() {
+        let decimal = |value| parse_bytes(value, ByteUnits::Decimal);
+        let binary = |value| parse_bytes(value, ByteUnits::Binary);
+        assert_eq!(decimal("512"), Ok(512));
+        assert_eq!(decimal("10KB"), Ok(10_000));
+        assert_eq!(binary("10KB"), Ok(10_240));
+        assert_eq!(decimal("4MiB"), Ok(4 * 1024 * 1024));
+        assert_eq!(decimal("1.5G"), Ok(1_500_000_000));
+        assert_eq!(binary("1.5 gb"), Ok(3 * 512 * 1024 * 1024));
+        assert_eq!(decimal("16EiB"), Err("size is too large".to_string()));
+        assert_eq!(
+            decimal("1.5"),
+            Err("not a whole number of bytes".to_string())
+        );
+        assert_eq!(decimal("KB"), Err("expected a number".to_string()));
+        assert_eq!(decimal("1."), Err("expected a number".to_string()));
+        assert_eq!(decimal("1.2.3"), Err("invalid number 1.2.3".to_string()));
+
+        let section = Section::new_with_entries("cache", vec![Entry::new("size", "10 XB")]);
+        assert_eq!(
+            section.get_bytes("size").unwrap_err().kind,
+            ValueErrorKind::Invalid {
+                expected: "a byte size".to_string(),
+                reason: "unknown unit \"XB\", expected one of B, KB, MB, GB, TB, PB, EB, KiB, MiB, GiB, TiB, PiB, EiB".to_string()
+            }
+        );
+    }
+}
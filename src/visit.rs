@@ -0,0 +1,116 @@
+//! Walking every section and entry of a config with a visitor.
+//!
+//! Example:
+//!
+//! ```
+//! use nbconf::visit::ConfVisitor;
+//! use nbconf::{Conf, Entry, Section};
+//!
+//! struct Longest(usize);
+//!
+//! impl ConfVisitor for Longest {
+//!     fn visit_entry(&mut self, _section: &Section, entry: &Entry) {
+//!         self.0 = self.0.max(entry.value.len());
+//!     }
+//! }
+//!
+//! let conf = Conf::parse_str("[a]\nx = 1\n[b]\ny = 333\n").unwrap();
+//! let mut longest = Longest(0);
+//! conf.accept(&mut longest);
+//! assert_eq!(longest.0, 3);
+//! ```
+
+use crate::{Conf, Entry, Section};
+
+/// Hooks called by [`Conf::accept`] for each section and entry. Both do
+/// nothing by default.
+///
+/// [`Conf::accept`]: ../struct.Conf.html#method.accept
+pub trait ConfVisitor {
+    /// Called for each section, before its entries.
+    fn visit_section(&mut self, _section: &Section) {}
+
+    /// Called for each entry, with the section containing it.
+    fn visit_entry(&mut self, _section: &Section, _entry: &Entry) {}
+}
+
+/// Hooks called by [`Conf::accept_mut`] for each section and entry, which
+/// may change them. Both do nothing by default.
+///
+/// [`Conf::accept_mut`]: ../struct.Conf.html#method.accept_mut
+pub trait ConfVisitorMut {
+    /// Called for each section, before its entries. Entries added to the
+    /// section here are visited too.
+    fn visit_section_mut(&mut self, _section: &mut Section) {}
+
+    /// Called for each entry, with the name of the section containing it.
+    fn visit_entry_mut(&mut self, _section: &str, _entry: &mut Entry) {}
+}
+
+impl Conf {
+    /// Call `visitor` for each section and each of its entries, in order.
+    pub fn accept<V: ConfVisitor + ?Sized>(&self, visitor: &mut V) {
+        for section in self.sections.iter() {
+            visitor.visit_section(section);
+            for entry in section.entries.iter() {
+                visitor.visit_entry(section, entry);
+            }
+        }
+    }
+
+    /// Call `visitor` for each section and each of its entries, in order,
+    /// allowing it to change them.
+    pub fn accept_mut<V: ConfVisitorMut + ?Sized>(&mut self, visitor: &mut V) {
+        for section in self.sections.iter_mut() {
+            visitor.visit_section_mut(section);
+            let Section { name, entries } = section;
+            for entry in entries.iter_mut() {
+                visitor.visit_entry_mut(name, entry);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct Recorder(Vec<String>);
+
+    impl ConfVisitor for Recorder {
+        fn visit_section(&mut self, section: &Section) {
+            self.0.push(section.name.clone());
+        }
+
+        fn visit_entry(&mut self, section: &Section, entry: &Entry) {
+            self.0.push(format!("{}.{}", section.name, entry.key));
+        }
+    }
+
+    struct Prefixer;
+
+    impl ConfVisitorMut for Prefixer {
+        fn visit_section_mut(&mut self, section: &mut Section) {
+            section.set("added", "0");
+        }
+
+        fn visit_entry_mut(&mut self, section: &str, entry: &mut Entry) {
+            entry.value = format!("{}:{}", section, entry.value);
+        }
+    }
+
+    #[test]
+    fn test_accept() {
+        let mut conf = Conf::parse_str("[a]\nx = 1\ny = 2\n[b]\n[a]\nz = 3\n").unwrap();
+        let mut recorder = Recorder::default();
+        conf.accept(&mut recorder);
+        assert_eq!(recorder.0, vec!["a", "a.x", "a.y", "b", "a", "a.z"]);
+
+        conf.accept_mut(&mut Prefixer);
+        assert_eq!(
+            conf.to_string(),
+            "[a]\nx = a:1\ny = a:2\nadded = a:0\n\n[b]\nadded = b:0\n\n[a]\nz = a:3\nadded = a:0\n"
+        );
+    }
+}
@@ -0,0 +1,499 @@
+//! Reloading a config when its file changes.
+//!
+//! This module requires the `watch` feature. The file is polled for
+//! changes to its modification time and size, so no platform file
+//! notification API is needed. [`DirWatcher`] watches a directory of
+//! config files, such as `conf.d`, in the same way.
+//!
+//! Example:
+//!
+//! ```no_run
+//! use nbconf::watch::{ConfWatcher, WatchEvent, WatchOptions};
+//!
+//! let watcher = ConfWatcher::open_with("app.conf", WatchOptions::default(), |event| {
+//!     match event {
+//!         WatchEvent::Changed { old, new } => {
+//!             for change in old.diff(&new) {
+//!                 println!("{}", change);
+//!             }
+//!         }
+//!         WatchEvent::Error(err) => eprintln!("app.conf: {}", err),
+//!     }
+//! })?;
+//! let port = watcher.current().get_path("server.port").map(str::to_string);
+//! # Ok::<(), nbconf::LoadError>(())
+//! ```
+//!
+//! [`DirWatcher`]: struct.DirWatcher.html
+
+use crate::diff::Change;
+use crate::{Conf, LoadError, MergePolicy};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, SystemTime};
+
+/// Options for [`ConfWatcher::open_with`].
+///
+/// [`ConfWatcher::open_with`]: struct.ConfWatcher.html#method.open_with
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct WatchOptions {
+    /// How often to check the file for changes.
+    pub poll_interval: Duration,
+    /// How long the file must stay unchanged before it is reloaded, so
+    /// that a file being written is not read half way.
+    pub debounce: Duration,
+}
+
+impl Default for WatchOptions {
+    fn default() -> WatchOptions {
+        WatchOptions {
+            poll_interval: Duration::from_secs(1),
+            debounce: Duration::from_millis(250),
+        }
+    }
+}
+
+/// A change seen by a [`ConfWatcher`].
+///
+/// [`ConfWatcher`]: struct.ConfWatcher.html
+#[derive(Debug)]
+pub enum WatchEvent {
+    /// The file changed and was reloaded.
+    Changed { old: Arc<Conf>, new: Arc<Conf> },
+    /// The file changed but could not be loaded. The previous config is
+    /// kept as the current one.
+    Error(LoadError),
+}
+
+/// Modification time and size of a file, or `None` if it cannot be
+/// read.
+type Stamp = Option<(SystemTime, u64)>;
+
+fn stamp(path: &Path) -> Stamp {
+    let metadata = std::fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+/// Call `reload` from a background thread whenever `stamp` returns a
+/// different value and then keeps returning it for the debounce time.
+/// The thread stops when the returned sender is dropped.
+fn spawn_poller<S, F, R>(
+    options: WatchOptions,
+    mut last_stamp: S,
+    stamp: F,
+    mut reload: R,
+) -> (mpsc::Sender<()>, JoinHandle<()>)
+where
+    S: PartialEq + Send + 'static,
+    F: Fn() -> S + Send + 'static,
+    R: FnMut(&S) + Send + 'static,
+{
+    let (stop, stopped) = mpsc::channel();
+    let thread = std::thread::spawn(move || {
+        let wait = |duration| match stopped.recv_timeout(duration) {
+            Err(RecvTimeoutError::Timeout) => true,
+            Ok(()) | Err(RecvTimeoutError::Disconnected) => false,
+        };
+        while wait(options.poll_interval) {
+            let mut new_stamp = stamp();
+            if new_stamp == last_stamp {
+                continue;
+            }
+            // Wait for the file to settle.
+            loop {
+                if !wait(options.debounce) {
+                    return;
+                }
+                let settled = stamp();
+                if settled == new_stamp {
+                    break;
+                }
+                new_stamp = settled;
+            }
+            reload(&new_stamp);
+            last_stamp = new_stamp;
+        }
+    });
+    (stop, thread)
+}
+
+/// A handle to a config that is reloaded in a background thread when
+/// its file changes. The thread stops when the handle is dropped.
+#[derive(Debug)]
+pub struct ConfWatcher {
+    current: Arc<Mutex<Arc<Conf>>>,
+    stop: Option<mpsc::Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl ConfWatcher {
+    /// Load the config at `path` and watch it with the default options.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<ConfWatcher, LoadError> {
+        ConfWatcher::open_with(path, WatchOptions::default(), |_| {})
+    }
+
+    /// Load the config at `path` and watch it, calling `on_event` from
+    /// the background thread after each reload or failed reload.
+    ///
+    /// Fails if the config cannot be loaded initially.
+    pub fn open_with<P, F>(
+        path: P,
+        options: WatchOptions,
+        mut on_event: F,
+    ) -> Result<ConfWatcher, LoadError>
+    where
+        P: AsRef<Path>,
+        F: FnMut(WatchEvent) + Send + 'static,
+    {
+        let path: PathBuf = path.as_ref().to_path_buf();
+        let last_stamp = stamp(&path);
+        let current = Arc::new(Mutex::new(Arc::new(Conf::load(&path)?)));
+
+        let thread_current = current.clone();
+        let stamp_path = path.clone();
+        let (stop, thread) = spawn_poller(
+            options,
+            last_stamp,
+            move || stamp(&stamp_path),
+            move |_| match Conf::load(&path) {
+                Ok(new) => {
+                    let new = Arc::new(new);
+                    let old = std::mem::replace(&mut *thread_current.lock().unwrap(), new.clone());
+                    if old != new {
+                        on_event(WatchEvent::Changed { old, new });
+                    }
+                }
+                Err(err) => on_event(WatchEvent::Error(err)),
+            },
+        );
+
+        Ok(ConfWatcher {
+            current,
+            stop: Some(stop),
+            thread: Some(thread),
+        })
+    }
+
+    /// The most recently loaded config.
+    pub fn current(&self) -> Arc<Conf> {
+        self.current.lock().unwrap().clone()
+    }
+}
+
+impl Drop for ConfWatcher {
+    fn drop(&mut self) {
+        // Dropping the sender wakes the thread and tells it to stop.
+        self.stop.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// A change seen by a [`DirWatcher`].
+///
+/// [`DirWatcher`]: struct.DirWatcher.html
+#[derive(Debug)]
+pub enum DirEvent {
+    /// A config file appeared in the directory.
+    FileAdded { path: PathBuf },
+    /// A config file was removed from the directory.
+    FileRemoved { path: PathBuf },
+    /// A key has a different value in the merged config. `old` or `new`
+    /// is `None` if the key was added or removed. `file` is the file the
+    /// new value comes from, or for a removed key the file the old value
+    /// came from.
+    KeyChanged {
+        section: String,
+        key: String,
+        old: Option<String>,
+        new: Option<String>,
+        file: PathBuf,
+    },
+    /// A file changed but could not be loaded. The previous version of
+    /// the file, if any, is kept in the merged config.
+    Error(LoadError),
+}
+
+/// Each loaded file, in the order they are merged.
+type Files = Vec<(PathBuf, Stamp, Arc<Conf>)>;
+
+/// The `.conf` files in `dir` with their stamps, sorted by path.
+fn dir_stamps(dir: &Path) -> Vec<(PathBuf, Stamp)> {
+    let mut stamps: Vec<(PathBuf, Stamp)> = match std::fs::read_dir(dir) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.extension() == Some("conf".as_ref()) && path.is_file())
+            .map(|path| {
+                let stamp = stamp(&path);
+                (path, stamp)
+            })
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    stamps.sort();
+    stamps
+}
+
+fn merge_files(files: &Files) -> Conf {
+    let mut merged = Conf::new();
+    for (_, _, conf) in files.iter() {
+        merged.merge(conf, MergePolicy::Override);
+    }
+    merged
+}
+
+/// The last file that sets `key` in `section`, which is the one its
+/// merged value comes from.
+fn source_file(files: &Files, section: &str, key: &str) -> PathBuf {
+    files
+        .iter()
+        .rev()
+        .find(|(_, _, conf)| conf.sections_named(section).any(|s| s.get(key).is_some()))
+        .map(|(path, _, _)| path.clone())
+        .unwrap_or_default()
+}
+
+/// A handle to the merged config of every `.conf` file in a directory,
+/// which is updated in a background thread when files are added,
+/// removed or changed. The thread stops when the handle is dropped.
+///
+/// Files are merged in order of their names with
+/// [`MergePolicy::Override`], so later files override earlier ones.
+///
+/// [`MergePolicy::Override`]: ../enum.MergePolicy.html#variant.Override
+#[derive(Debug)]
+pub struct DirWatcher {
+    current: Arc<Mutex<Arc<Conf>>>,
+    stop: Option<mpsc::Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl DirWatcher {
+    /// Load the config files in `dir` and watch them, calling `on_event`
+    /// from the background thread for each change to the files and to
+    /// the merged config.
+    ///
+    /// Fails if the directory or one of its files cannot be loaded
+    /// initially.
+    pub fn open_with<P, F>(
+        dir: P,
+        options: WatchOptions,
+        mut on_event: F,
+    ) -> Result<DirWatcher, LoadError>
+    where
+        P: AsRef<Path>,
+        F: FnMut(DirEvent) + Send + 'static,
+    {
+        let dir: PathBuf = dir.as_ref().to_path_buf();
+        std::fs::read_dir(&dir).map_err(|err| LoadError::from(err).with_path(&dir))?;
+        let last_stamps = dir_stamps(&dir);
+        let mut files: Files = Vec::new();
+        for (path, stamp) in last_stamps.iter() {
+            files.push((path.clone(), *stamp, Arc::new(Conf::load(path)?)));
+        }
+        let current = Arc::new(Mutex::new(Arc::new(merge_files(&files))));
+
+        let thread_current = current.clone();
+        let (stop, thread) = spawn_poller(
+            options,
+            last_stamps,
+            move || dir_stamps(&dir),
+            move |stamps| {
+                let mut new_files: Files = Vec::new();
+                let mut events = Vec::new();
+                for (path, stamp) in stamps.iter() {
+                    let old = files.iter().find(|(p, _, _)| p == path);
+                    if let Some((_, old_stamp, conf)) = old {
+                        if old_stamp == stamp {
+                            new_files.push((path.clone(), *stamp, conf.clone()));
+                            continue;
+                        }
+                    }
+                    match Conf::load(path) {
+                        Ok(conf) => {
+                            if old.is_none() {
+                                events.push(DirEvent::FileAdded { path: path.clone() });
+                            }
+                            new_files.push((path.clone(), *stamp, Arc::new(conf)));
+                        }
+                        Err(err) => {
+                            events.push(DirEvent::Error(err));
+                            if let Some((_, _, conf)) = old {
+                                new_files.push((path.clone(), *stamp, conf.clone()));
+                            }
+                        }
+                    }
+                }
+                for (path, _, _) in files.iter() {
+                    if !stamps.iter().any(|(p, _)| p == path) {
+                        events.push(DirEvent::FileRemoved { path: path.clone() });
+                    }
+                }
+
+                let new = Arc::new(merge_files(&new_files));
+                let old = std::mem::replace(&mut *thread_current.lock().unwrap(), new.clone());
+                for change in old.diff(&new) {
+                    let (section, key, old, new) = match change {
+                        Change::Added {
+                            section,
+                            key,
+                            value,
+                        } => (section, key, None, Some(value)),
+                        Change::Removed {
+                            section,
+                            key,
+                            value,
+                        } => (section, key, Some(value), None),
+                        Change::Changed {
+                            section,
+                            key,
+                            old,
+                            new,
+                        } => (section, key, Some(old), Some(new)),
+                        Change::SectionAdded { .. } | Change::SectionRemoved { .. } => continue,
+                    };
+                    let file = if new.is_some() {
+                        source_file(&new_files, &section, &key)
+                    } else {
+                        source_file(&files, &section, &key)
+                    };
+                    events.push(DirEvent::KeyChanged {
+                        section,
+                        key,
+                        old,
+                        new,
+                        file,
+                    });
+                }
+                files = new_files;
+                for event in events {
+                    on_event(event);
+                }
+            },
+        );
+
+        Ok(DirWatcher {
+            current,
+            stop: Some(stop),
+            thread: Some(thread),
+        })
+    }
+
+    /// The most recently merged config.
+    pub fn current(&self) -> Arc<Conf> {
+        self.current.lock().unwrap().clone()
+    }
+}
+
+impl Drop for DirWatcher {
+    fn drop(&mut self) {
+        self.stop.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_watcher() {
+        let path = std::env::temp_dir().join(format!("nbconf-watch-{}.conf", std::process::id()));
+        std::fs::write(&path, "[server]\nport = 80\n").unwrap();
+        let options = WatchOptions {
+            poll_interval: Duration::from_millis(10),
+            debounce: Duration::from_millis(10),
+        };
+        let (events, received) = mpsc::channel();
+        let watcher = ConfWatcher::open_with(&path, options, move |event| {
+            events.send(event).unwrap();
+        })
+        .unwrap();
+        assert_eq!(watcher.current().get_path("server.port"), Some("80"));
+
+        let timeout = Duration::from_secs(10);
+        std::fs::write(&path, "[server]\nport = 8080\n").unwrap();
+        match received.recv_timeout(timeout).unwrap() {
+            WatchEvent::Changed { old, new } => {
+                assert_eq!(old.get_path("server.port"), Some("80"));
+                assert_eq!(new.get_path("server.port"), Some("8080"));
+            }
+            event => panic!("unexpected event: {:?}", event),
+        }
+        assert_eq!(watcher.current().get_path("server.port"), Some("8080"));
+
+        std::fs::write(&path, "[server\n").unwrap();
+        match received.recv_timeout(timeout).unwrap() {
+            WatchEvent::Error(LoadError::Parse(err)) => assert_eq!(err.line, 1),
+            event => panic!("unexpected event: {:?}", event),
+        }
+        assert_eq!(watcher.current().get_path("server.port"), Some("8080"));
+
+        drop(watcher);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_dir_watcher() {
+        let dir = std::env::temp_dir().join(format!("nbconf-watch-{}.d", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("10-base.conf"), "[server]\nport = 80\nhost = a\n").unwrap();
+        std::fs::write(dir.join("notes.txt"), "not a config").unwrap();
+        let options = WatchOptions {
+            poll_interval: Duration::from_millis(10),
+            debounce: Duration::from_millis(10),
+        };
+        let (events, received) = mpsc::channel();
+        let watcher = DirWatcher::open_with(&dir, options, move |event| {
+            events.send(event).unwrap();
+        })
+        .unwrap();
+        assert_eq!(watcher.current().get_path("server.port"), Some("80"));
+
+        let timeout = Duration::from_secs(10);
+        let next = || received.recv_timeout(timeout).unwrap();
+        let local = dir.join("20-local.conf");
+        std::fs::write(&local, "[server]\nport = 8080\n").unwrap();
+        match next() {
+            DirEvent::FileAdded { path } => assert_eq!(path, local),
+            event => panic!("unexpected event: {:?}", event),
+        }
+        match next() {
+            DirEvent::KeyChanged {
+                section,
+                key,
+                old,
+                new,
+                file,
+            } => {
+                assert_eq!((section.as_str(), key.as_str()), ("server", "port"));
+                assert_eq!((old.as_deref(), new.as_deref()), (Some("80"), Some("8080")));
+                assert_eq!(file, local);
+            }
+            event => panic!("unexpected event: {:?}", event),
+        }
+        assert_eq!(watcher.current().get_path("server.port"), Some("8080"));
+
+        std::fs::remove_file(dir.join("10-base.conf")).unwrap();
+        match next() {
+            DirEvent::FileRemoved { path } => assert_eq!(path, dir.join("10-base.conf")),
+            event => panic!("unexpected event: {:?}", event),
+        }
+        match next() {
+            DirEvent::KeyChanged { key, new, file, .. } => {
+                assert_eq!((key.as_str(), new), ("host", None));
+                assert_eq!(file, dir.join("10-base.conf"));
+            }
+            event => panic!("unexpected event: {:?}", event),
+        }
+
+        drop(watcher);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
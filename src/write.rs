@@ -0,0 +1,109 @@
+//! Serialization shared by [`Conf`] and the formatter.
+//!
+//! [`Conf`]: ../struct.Conf.html
+
+use crate::WriteOptions;
+
+/// An entry to write, with the comment lines that precede it.
+pub(crate) struct EntryView<'a> {
+    pub comments: Vec<&'a str>,
+    pub key: &'a str,
+    pub value: &'a str,
+}
+
+/// A section to write, with the comment lines that precede its header.
+pub(crate) struct SectionView<'a> {
+    pub comments: Vec<&'a str>,
+    pub name: &'a str,
+    pub entries: Vec<EntryView<'a>>,
+}
+
+/// A whole document to write.
+#[derive(Default)]
+pub(crate) struct DocumentView<'a> {
+    /// Comment lines at the start, set apart from the first section.
+    pub preamble: Vec<&'a str>,
+    pub sections: Vec<SectionView<'a>>,
+    /// Comment lines after the last entry.
+    pub trailing: Vec<&'a str>,
+}
+
+pub(crate) fn write(mut document: DocumentView, options: &WriteOptions) -> String {
+    let mut output = String::new();
+    if options.bom {
+        output.push('\u{feff}');
+    }
+    let eol = options.eol();
+    let equals = if options.spaces_around_equals {
+        " = "
+    } else {
+        "="
+    };
+    let line = |output: &mut String, indent: &str, text: &str| {
+        *output += indent;
+        *output += text;
+        *output += eol;
+    };
+
+    for comment in document.preamble.iter() {
+        line(&mut output, "", comment);
+    }
+    if !document.preamble.is_empty() && !document.sections.is_empty() {
+        output += &eol.repeat(options.blank_lines_between_sections);
+    }
+
+    if options.sort {
+        document.sections.sort_by(|a, b| a.name.cmp(b.name));
+    }
+    // With `sectionless`, a first section with an empty name has no
+    // header, and nothing to separate from the next one if it is empty.
+    let headerless =
+        options.sectionless && document.sections.first().is_some_and(|s| s.name.is_empty());
+    let separate_second = !headerless || !document.sections[0].entries.is_empty();
+    for (index, section) in document.sections.iter_mut().enumerate() {
+        if index > 1 || (index == 1 && separate_second) {
+            output += &eol.repeat(options.blank_lines_between_sections);
+        }
+        for comment in section.comments.iter() {
+            line(&mut output, "", comment);
+        }
+        if index != 0 || !headerless {
+            line(&mut output, "", &format!("[{}]", section.name));
+        }
+
+        if options.sort {
+            section.entries.sort_by(|a, b| a.key.cmp(b.key));
+        }
+        let key_width = if options.align_equals {
+            section
+                .entries
+                .iter()
+                .map(|e| e.key.chars().count())
+                .max()
+                .unwrap_or(0)
+        } else {
+            0
+        };
+        for entry in section.entries.iter() {
+            for comment in entry.comments.iter() {
+                line(&mut output, &options.indent, comment);
+            }
+            let text = format!(
+                "{:width$}{}{}",
+                entry.key,
+                equals,
+                entry.value,
+                width = key_width
+            );
+            line(&mut output, &options.indent, &text);
+        }
+    }
+
+    for comment in document.trailing.iter() {
+        line(&mut output, "", comment);
+    }
+    if !options.trailing_newline && output.ends_with(eol) {
+        output.truncate(output.len() - eol.len());
+    }
+    output
+}